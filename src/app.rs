@@ -1,25 +1,63 @@
 use fltk::{
-    app,
+    app, dialog,
     enums::*,
     menu::MenuBar,
     prelude::*,
-    text::{self, StyleTableEntry, TextBuffer, TextEditor},
+    text::{TextBuffer, TextEditor},
     window::Window,
 };
 use std::{cell::RefCell, rc::Rc};
 
+mod auto_indent;
+mod bookmarks;
+mod brackets;
+mod config;
+mod data_format;
+mod dock;
+mod edit;
+mod editorconfig;
+mod encode;
 mod encoding;
+mod filter;
+mod find_in_files;
+mod fonts;
+mod goto;
+mod gutter;
+mod highlight;
+mod history;
 mod icon;
+mod indent;
+mod languages;
+mod lines;
+mod markers;
 mod menu;
+mod palette;
+mod preferences;
+mod replace_in_files;
+mod results;
+mod save_actions;
 mod search;
+mod settings;
+mod state;
 mod status;
-
-use encoding::load_as_utf8;
-use search::{SearchState, attach_search_logic, update_result_status};
+mod theme;
+mod transform;
+mod whitespace;
+
+use dock::{DOCK_HEIGHT, Relayout};
+use editorconfig::EditorConfig;
+use filter::FilterState;
+use history::SearchHistory;
+use indent::Indent;
+use markers::{LineMarks, MARKER_STRIP_WIDTH};
+use search::{SearchOptions, SearchState, attach_search_logic, make_goto_match, step_match};
+use settings::Settings;
+use state::{AppState, DocumentState};
 use status::{
     attach_status_path_actions, create_status_bar, hide_search_controls, make_update_status,
     show_search_controls,
 };
+use theme::Theme;
 
 pub fn run() {
     let app = app::App::default();
@@ -33,14 +71,14 @@ pub fn run() {
 
     let buf = Rc::new(RefCell::new(TextBuffer::default()));
 
-    let mut editor = TextEditor::new(0, 30, 800, 510, "");
+    let mut editor = TextEditor::new(0, 30, 800 - MARKER_STRIP_WIDTH, 510, "");
     editor.set_buffer(Some(buf.borrow().clone()));
-    editor.set_scrollbar_size(16);
-    editor.wrap_mode(text::WrapMode::AtBounds, 0);
     editor.set_text_font(fltk::enums::Font::Courier);
     editor.remove_key_binding(Key::from_char('f'), Shortcut::Ctrl);
     win.resizable(&editor);
 
+    let marker_strip = markers::create_marker_strip(800 - MARKER_STRIP_WIDTH, 30, 510);
+
     let status_bar = create_status_bar(0, 570, 800, 30);
 
     let sb = status_bar.borrow();
@@ -48,70 +86,163 @@ pub fn run() {
     let sb_y = sb.y();
     drop(sb);
 
-    let search_state = Rc::new(RefCell::new(SearchState {
-        results: vec![],
-        current: 0,
-        visible: false,
-        filepath: "".into(),
+    let dock = dock::create_dock(0, sb_y - DOCK_HEIGHT, sb_w);
+    let results_panel = results::create_results_panel(&dock);
+
+    let settings = Settings::load().unwrap_or_else(|e| {
+        app::add_timeout3(0.0, move |_| {
+            dialog::alert_default(&format!(
+                "{}\nUsing default settings. The file is copied to settings.toml.bak \
+                 before any change is saved.",
+                e
+            ))
+        });
+        Settings::default()
+    });
+
+    let app_state = Rc::new(RefCell::new(AppState {
+        search: SearchState {
+            pattern: String::new(),
+            options: SearchOptions {
+                case_sensitive: settings.search.case_sensitive,
+                regex: settings.search.regex,
+            },
+            results: vec![],
+            current: 0,
+            visible: false,
+            history: SearchHistory::load(settings.search.max_history),
+        },
+        document: DocumentState {
+            filepath: "".into(),
+            modified: false,
+            filter: FilterState::default(),
+            marks: LineMarks::default(),
+            indent: Indent::from_settings(&settings.editor),
+            editorconfig: EditorConfig::default(),
+        },
         recent_files: vec![],
+        theme: Theme::light(),
+        status_message: None,
+        settings,
     }));
-    menu::load_recent_files_into_state(&search_state);
+    menu::load_recent_files_into_state(&app_state);
+    menu::attach_modified_tracking(&buf, &app_state);
+    markers::attach_line_mark_tracking(&buf, &app_state);
 
     let search_controls = Rc::new(RefCell::new(search::create_search_controls(sb_y, sb_w)));
 
-    let stylebuf = Rc::new(RefCell::new(TextBuffer::default()));
-    let styles = vec![
-        StyleTableEntry {
-            color: fltk::enums::Color::Black,
-            font: fltk::enums::Font::Helvetica,
-            size: app::font_size(),
-        },
-        StyleTableEntry {
-            color: fltk::enums::Color::Black,
-            font: fltk::enums::Font::HelveticaBold,
-            size: app::font_size(),
-        },
-    ];
-    editor.set_highlight_data(stylebuf.borrow().clone(), styles);
+    let highlighter = highlight::create_highlighter(&editor, &buf, &app_state.borrow().theme);
 
-    let update_status = make_update_status(&status_bar, &editor, &search_state);
-    attach_status_path_actions(&status_bar, &search_state);
+    let update_status = make_update_status(&status_bar, &editor, &app_state);
+    let goto_line = goto::make_goto_line(&editor, &buf, &app_state, &update_status);
+    attach_status_path_actions(&status_bar, &app_state, &goto_line);
 
-    {
+    let gutter = gutter::create_gutter(&editor, &buf, &app_state);
+    let apply_file_settings = {
+        let app_state = Rc::clone(&app_state);
+        let editor = editor.clone();
+        let gutter = Rc::clone(&gutter);
+        let highlighter = Rc::clone(&highlighter);
         let buf = Rc::clone(&buf);
-        let stylebuf = Rc::clone(&stylebuf);
+        let menu = menu.clone();
+        move || {
+            preferences::apply_file_settings(&app_state, &editor, &gutter, &highlighter, &menu);
+            indent::apply_indent(&app_state, &buf, &editor, &menu);
+        }
+    };
+
+    let open_path = menu::make_open_path(&menu, &buf, &highlighter, &app_state, {
         let update_status = update_status.clone();
-        let state = Rc::clone(&search_state);
-        let mut recent_menu = menu.clone();
+        let apply_file_settings = apply_file_settings.clone();
+        move || {
+            apply_file_settings();
+            update_status()
+        }
+    });
+
+    let filter_ui = filter::create_filter(&app_state, &editor, &buf, &highlighter, &update_status);
+
+    let themes = theme::create_themes(
+        &app_state,
+        &editor,
+        &highlighter,
+        &status_bar,
+        &search_controls,
+        &marker_strip,
+    );
+    let fonts = fonts::create_fonts(&editor, &highlighter, &app_state, &gutter);
 
-        editor.handle(move |_, ev| match ev {
+    {
+        let update_status = update_status.clone();
+        let open_path = Rc::clone(&open_path);
+        let app_state = Rc::clone(&app_state);
+        let gutter = Rc::clone(&gutter);
+        let fonts = fonts.clone();
+        let highlighter = Rc::clone(&highlighter);
+
+        editor.handle(move |ed, ev| match ev {
+            Event::Push | Event::Drag | Event::Released
+                if gutter::handle_gutter_event(&gutter, ed, ev) =>
+            {
+                update_status();
+                true
+            }
             Event::Paste => {
                 let dropped = app::event_text().trim().to_string();
-                if dropped.is_empty() {
-                    return false;
-                }
-                if let Some(content) = load_as_utf8(&dropped) {
-                    let len = content.len();
-
-                    buf.borrow_mut().set_text(&content);
-                    stylebuf.borrow_mut().set_text(&"A".repeat(len.max(1)));
-
-                    state.borrow_mut().filepath = dropped.clone();
-                    update_status();
-                    let update_status_recent = update_status.clone();
-                    let recent_status_cb = move || (update_status_recent)();
-                    menu::remember_recent_and_refresh(
-                        &mut recent_menu,
-                        &buf,
-                        &stylebuf,
-                        &state,
-                        &recent_status_cb,
-                        &dropped,
-                    );
-
+                if !dropped.is_empty() && open_path(&dropped) {
                     return true;
                 }
-                false
+                filter::handle_view_event(&app_state, ev)
+            }
+            Event::KeyDown if filter::handle_view_event(&app_state, ev) => true,
+            Event::KeyDown
+                if app::event_key() == Key::Tab
+                    && !app::event_state()
+                        .intersects(EventState::Ctrl | EventState::Alt | EventState::Meta) =>
+            {
+                let shift = app::event_state().contains(EventState::Shift);
+                let handled = indent::handle_tab_key(ed, &app_state, shift);
+                update_status();
+                handled
+            }
+            Event::KeyDown
+                if matches!(app::event_key(), Key::Enter | Key::KPEnter)
+                    && !app::event_state()
+                        .intersects(EventState::Ctrl | EventState::Alt | EventState::Meta) =>
+            {
+                let handled = auto_indent::handle_enter_key(ed, &app_state, &highlighter);
+                update_status();
+                handled
+            }
+            Event::KeyDown
+                if app::event_key() == Key::BackSpace
+                    && !app::event_state()
+                        .intersects(EventState::Ctrl | EventState::Alt | EventState::Meta) =>
+            {
+                let handled = brackets::handle_backspace_key(ed, &app_state)
+                    || indent::handle_backspace_key(ed, &app_state);
+                update_status();
+                handled
+            }
+            Event::KeyDown
+                if !app::event_state()
+                    .intersects(EventState::Ctrl | EventState::Alt | EventState::Meta)
+                    && brackets::handle_typed_char(ed, &app_state, &app::event_text()) =>
+            {
+                brackets::update_bracket_match(ed, &highlighter, &app_state);
+                update_status();
+                true
+            }
+            Event::MouseWheel if app::event_state().contains(EventState::Ctrl) => {
+                let steps = match app::event_dy() {
+                    app::MouseWheel::Up => 1,
+                    app::MouseWheel::Down => -1,
+                    _ => 0,
+                };
+                if steps != 0 {
+                    fonts::zoom(&fonts, steps);
+                }
+                true
             }
             Event::KeyDown
             | Event::KeyUp
@@ -119,33 +250,46 @@ pub fn run() {
             | Event::Released
             | Event::Drag
             | Event::MouseWheel => {
+                brackets::update_bracket_match(ed, &highlighter, &app_state);
                 update_status();
                 false
             }
             _ => false,
         });
     }
-    let search_editor = editor.clone();
-    let search_buf = buf.clone();
-    let search_state_clone = search_state.clone();
-    let search_update_status = update_status.clone();
+    let goto_match = make_goto_match(
+        &editor,
+        &buf,
+        &search_controls.borrow(),
+        &app_state,
+        &results_panel,
+        update_status.clone(),
+    );
+    markers::attach_marker_strip_logic(
+        &marker_strip,
+        &editor,
+        &buf,
+        &highlighter,
+        &app_state,
+        goto_match.clone(),
+        update_status.clone(),
+    );
+    results::attach_results_panel_logic(&results_panel, Rc::clone(&app_state), goto_match.clone());
 
     attach_search_logic(
         &mut search_controls.borrow_mut(),
-        Rc::clone(&search_state),
+        Rc::clone(&app_state),
         Rc::clone(&buf),
-        Rc::clone(&stylebuf),
-        &mut editor,
-        update_status.clone(),
+        Rc::clone(&highlighter),
+        &results_panel,
+        goto_match.clone(),
+        &menu,
     );
 
     win.handle({
-        let search_state = search_state_clone;
-        let search_controls = Rc::clone(&search_controls);
-        let _status_bar = Rc::clone(&status_bar);
-        let mut editor = search_editor;
-        let buf = search_buf;
-        let update_status = search_update_status;
+        let app_state = Rc::clone(&app_state);
+        let goto = goto_match.clone();
+        let fonts = fonts.clone();
 
         move |_, ev| match ev {
             Event::Shortcut | Event::KeyDown => {
@@ -153,38 +297,31 @@ pub fn run() {
                 let st = app::event_state();
                 let command = st.contains(EventState::Ctrl) || st.contains(EventState::Meta);
                 let text = app::event_text();
-                let ctrl_j = command && (key == Key::from_char('j') || text == "\n");
-                let ctrl_k = command && (key == Key::from_char('k') || text == "\u{b}");
-
-                if ctrl_j {
-                    let mut s = search_state.borrow_mut();
-                    if !s.results.is_empty() {
-                        if s.current == 0 {
-                            s.current = s.results.len() - 1;
-                        } else {
-                            s.current -= 1;
-                        }
-                        let (start, end) = s.results[s.current];
-                        editor.set_insert_position(start);
-                        editor.show_insert_position();
-                        buf.borrow_mut().select(start, end);
-                        update_result_status(&search_controls.borrow().results, &s);
-                        update_status();
+                // Ctrl+Shift+J and Ctrl+Shift+K belong to the Edit menu.
+                let step = command && !st.contains(EventState::Shift);
+                let ctrl_j = step && (key == Key::from_char('j') || text == "\n");
+                let ctrl_k = step && (key == Key::from_char('k') || text == "\u{b}");
+
+                if ctrl_j || ctrl_k {
+                    if let Some((start, end)) = step_match(&app_state, ctrl_k) {
+                        goto(start, end);
                     }
                     return true;
                 }
 
-                if ctrl_k {
-                    let mut s = search_state.borrow_mut();
-                    if !s.results.is_empty() {
-                        s.current = (s.current + 1) % s.results.len();
-                        let (start, end) = s.results[s.current];
-                        editor.set_insert_position(start);
-                        editor.show_insert_position();
-                        buf.borrow_mut().select(start, end);
-                        update_result_status(&search_controls.borrow().results, &s);
-                        update_status();
-                    }
+                // The menu binds Ctrl+= and Ctrl+-; also take Ctrl with a `+`
+                // key of its own and the keypad's plus and minus.
+                let keypad = |c: char| Key::from_i32(Key::KP.bits() + c as i32);
+                let zoom_steps = if key == keypad('+') || key == Key::from_char('+') || text == "+"
+                {
+                    Some(1)
+                } else if key == keypad('-') {
+                    Some(-1)
+                } else {
+                    None
+                };
+                if command && let Some(steps) = zoom_steps {
+                    fonts::zoom(&fonts, steps);
                     return true;
                 }
 
@@ -192,54 +329,25 @@ pub fn run() {
                     return false;
                 }
 
-                let key = app::event_key();
-                let st = app::event_state();
-                let mut s = search_state.borrow_mut();
-
-                if s.visible && !s.results.is_empty() {
-                    if key == Key::Down {
-                        s.current = (s.current + 1) % s.results.len();
-                        let (start, end) = s.results[s.current];
-                        editor.set_insert_position(start);
-                        editor.show_insert_position();
-                        buf.borrow_mut().select(start, end);
-                        update_result_status(&search_controls.borrow().results, &s);
-                        update_status();
-                        return true;
-                    }
-
-                    if key == Key::Up {
-                        if s.current == 0 {
-                            s.current = s.results.len() - 1;
-                        } else {
-                            s.current -= 1;
-                        }
-                        let (start, end) = s.results[s.current];
-                        editor.set_insert_position(start);
-                        editor.show_insert_position();
-                        buf.borrow_mut().select(start, end);
-                        update_result_status(&search_controls.borrow().results, &s);
-                        update_status();
-                        return true;
-                    }
-
-                    if key == Key::Enter {
-                        let shift = st.contains(EventState::Shift);
-                        if shift {
-                            if s.current == 0 {
-                                s.current = s.results.len() - 1;
-                            } else {
-                                s.current -= 1;
-                            }
-                        } else {
-                            s.current = (s.current + 1) % s.results.len();
+                let visible = {
+                    let s = app_state.borrow();
+                    s.search.visible && !s.search.results.is_empty()
+                };
+
+                if visible {
+                    // Alt+Up and Alt+Down move lines instead.
+                    let plain = !(command || st.contains(EventState::Alt));
+                    let forward = match key {
+                        Key::Down if plain => Some(true),
+                        Key::Up if plain => Some(false),
+                        Key::Enter => Some(!st.contains(EventState::Shift)),
+                        _ => None,
+                    };
+
+                    if let Some(forward) = forward {
+                        if let Some((start, end)) = step_match(&app_state, forward) {
+                            goto(start, end);
                         }
-                        let (start, end) = s.results[s.current];
-                        editor.set_insert_position(start);
-                        editor.show_insert_position();
-                        buf.borrow_mut().select(start, end);
-                        update_result_status(&search_controls.borrow().results, &s);
-                        update_status();
                         return true;
                     }
                 }
@@ -251,50 +359,127 @@ pub fn run() {
         }
     });
 
-    menu::add_file_menu_items(&mut menu, &buf, &stylebuf, &search_state, {
-        let update_status = update_status.clone();
-        move || (update_status)()
-    });
-
-    menu::add_search_menu(&mut menu, &search_state, &search_controls, &editor, &buf, {
-        let update_status = update_status.clone();
-        move || (update_status)()
-    });
-
-    editor.set_callback({
-        let update_status = update_status.clone();
-        move |_| update_status()
-    });
-
-    win.end();
-
-    {
-        let mut editor = editor.clone();
+    let layout = {
+        let editor = editor.clone();
         let status_bar = Rc::clone(&status_bar);
-        let search_state = Rc::clone(&search_state);
+        let app_state = Rc::clone(&app_state);
+        let search_controls = Rc::clone(&search_controls);
+        let dock = Rc::clone(&dock);
+        let marker_strip = Rc::clone(&marker_strip);
+
+        Rc::new(move |w: i32, h: i32| {
+            let dock_h = if dock.borrow().visible {
+                DOCK_HEIGHT
+            } else {
+                0
+            };
 
-        win.resize_callback(move |_win, _x, _y, w, h| {
+            let mut editor = editor.clone();
             status_bar.borrow_mut().resize(0, h - 30, w, 30);
-            editor.resize(0, 30, w, h - 30 - 30);
+            dock.borrow_mut()
+                .tabs
+                .resize(0, h - 30 - DOCK_HEIGHT, w, DOCK_HEIGHT);
+            editor.resize(0, 30, w - MARKER_STRIP_WIDTH, h - 30 - 30 - dock_h);
+            marker_strip.borrow_mut().resize(
+                w - MARKER_STRIP_WIDTH,
+                30,
+                MARKER_STRIP_WIDTH,
+                h - 30 - 30 - dock_h,
+            );
 
             let sb = status_bar.borrow();
             let sb_w = sb.w();
             let sb_y = sb.y();
             drop(sb);
 
-            if search_state.borrow().visible {
+            if app_state.borrow().search.visible {
                 let mut sc = search_controls.borrow_mut();
                 if w < 300 {
-                    hide_search_controls(&mut *sc);
-                    search_state.borrow_mut().visible = false;
+                    hide_search_controls(&mut sc);
+                    app_state.borrow_mut().search.visible = false;
                 } else {
-                    show_search_controls(&mut *sc);
+                    show_search_controls(&mut sc);
                     sc.input.set_pos(sb_w - 200, sb_y + 5);
                     sc.results.borrow_mut().set_pos(sb_w - 275, sb_y + 5);
                 }
             }
-        });
-    }
+        })
+    };
+
+    let relayout: Relayout = {
+        let layout = Rc::clone(&layout);
+        let win = win.clone();
+        Rc::new(move || {
+            let mut win = win.clone();
+            layout(win.w(), win.h());
+            win.redraw();
+        })
+    };
+
+    menu::add_file_menu_items(&mut menu, &buf, &highlighter, &app_state, {
+        let update_status = update_status.clone();
+        let apply_file_settings = apply_file_settings.clone();
+        move || {
+            apply_file_settings();
+            update_status()
+        }
+    });
+
+    menu::add_search_menu(&mut menu, &app_state, &search_controls, &goto_match, {
+        let update_status = update_status.clone();
+        move || (update_status)()
+    });
+    menu::add_results_panel_menu(&mut menu, &dock, &results_panel, &relayout);
+    menu::add_filter_menu(&mut menu, &filter_ui);
+    menu::add_goto_menu(&mut menu, &goto_line);
+    menu::add_view_menu(&mut menu, &gutter, &editor, &highlighter, &app_state);
+    menu::add_line_menu(&mut menu, &editor, &app_state, &update_status);
+    menu::add_convert_menu(&mut menu, &editor, &app_state, &update_status);
+    menu::add_encode_menu(&mut menu, &editor, &app_state, &update_status);
+    menu::add_data_format_menu(&mut menu, &editor, &app_state, &update_status);
+    menu::add_indentation_menu(&mut menu, &editor, &buf, &app_state, &update_status);
+    menu::add_bracket_menu(&mut menu, &editor, &highlighter, &app_state, &update_status);
+    menu::add_theme_menu(&mut menu, &themes);
+    menu::add_font_menu(&mut menu, &fonts);
+    menu::add_command_palette_menu(&mut menu);
+    apply_file_settings();
+
+    let preferences = preferences::PreferencesUi {
+        state: Rc::clone(&app_state),
+        editor: editor.clone(),
+        gutter: Rc::clone(&gutter),
+        highlighter: Rc::clone(&highlighter),
+        menu: menu.clone(),
+        themes: themes.clone(),
+        fonts: fonts.clone(),
+        open_path: Rc::clone(&open_path),
+    };
+    menu::add_preferences_menu(&mut menu, &preferences);
+    preferences::attach_settings_reload(&preferences);
+
+    let bookmarks =
+        bookmarks::create_bookmarks(&dock, &relayout, &app_state, &editor, &buf, &update_status);
+    menu::add_bookmark_menu(&mut menu, &bookmarks);
+
+    let find_in_files = find_in_files::create_find_in_files(
+        &dock,
+        &relayout,
+        &open_path,
+        &app_state,
+        &editor,
+        &buf,
+        &update_status,
+    );
+    menu::add_find_in_files_menu(&mut menu, &find_in_files, &app_state, &buf);
+
+    editor.set_callback({
+        let update_status = update_status.clone();
+        move |_| update_status()
+    });
+
+    win.end();
+
+    win.resize_callback(move |_win, _x, _y, w, h| layout(w, h));
 
     win.show();
     app.run().unwrap();
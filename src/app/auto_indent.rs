@@ -0,0 +1,293 @@
+use fltk::{prelude::*, text::TextEditor};
+use std::{cell::RefCell, rc::Rc};
+
+use super::highlight::SharedHighlighter;
+use super::indent::Indent;
+use super::state::AppState;
+
+/// Python statements after which the next line steps back out of the block.
+const PYTHON_DEDENTS: [&str; 5] = ["return", "pass", "break", "continue", "raise"];
+
+/// What Enter does to the cursor's line: `text` replaces the bytes from `from`
+/// up to the cursor plus `skip` bytes after it, and the cursor lands at `cursor`
+/// within `text`.
+struct Newline {
+    from: usize,
+    skip: usize,
+    text: String,
+    cursor: usize,
+}
+
+fn line_comment_prefixes(language: Option<&str>) -> &'static [&'static str] {
+    match language {
+        Some("Rust") => &["///", "//!", "//"],
+        Some("Python" | "Shell" | "TOML") => &["#"],
+        Some("INI") => &[";", "#"],
+        _ => &[],
+    }
+}
+
+/// The prefix to repeat when `content` is a comment line.
+fn comment_marker(language: Option<&str>, content: &str) -> Option<String> {
+    if content.starts_with("#!") {
+        return None;
+    }
+    let prefix = line_comment_prefixes(language)
+        .iter()
+        .find(|p| content.starts_with(**p))?;
+    let rest = &content[prefix.len()..];
+    let spaces = rest.len() - rest.trim_start_matches(' ').len();
+    Some(format!("{}{}", prefix, &rest[..spaces]))
+}
+
+/// The marker for the next item when `content` is a list item, and the text
+/// of the current one.
+fn list_marker(content: &str) -> Option<(String, &str)> {
+    let digits = content.len()
+        - content
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    let (bullet, rest) = if digits > 0 {
+        let rest = &content[digits..];
+        let separator = rest.chars().next().filter(|c| matches!(c, '.' | ')'))?;
+        let number: u64 = content[..digits].parse().ok()?;
+        (format!("{}{}", number + 1, separator), &rest[1..])
+    } else {
+        let bullet = content
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '-' | '*' | '+'))?;
+        (bullet.to_string(), &content[1..])
+    };
+
+    let item = rest.trim_start_matches(' ');
+    if item.len() == rest.len() {
+        return None;
+    }
+    let spacing = &rest[..rest.len() - item.len()];
+    let (checkbox, item) = match item.get(..3) {
+        Some("[ ]" | "[x]" | "[X]") => ("[ ] ", item[3..].trim_start()),
+        _ => ("", item),
+    };
+    Some((format!("{}{}{}", bullet, spacing, checkbox), item))
+}
+
+fn closer(opener: char) -> char {
+    match opener {
+        '{' => '}',
+        '[' => ']',
+        _ => ')',
+    }
+}
+
+/// `lead` with one indentation level taken off.
+fn outdent(lead: &str, indent: Indent) -> &str {
+    if let Some(rest) = lead.strip_suffix('\t') {
+        return rest;
+    }
+    let spaces = lead.len() - lead.trim_end_matches(' ').len();
+    &lead[..lead.len() - spaces.min(indent.width)]
+}
+
+/// Works out Enter for the cursor's line, split into the text `before` and
+/// `after` the cursor.
+fn smart_newline(language: Option<&str>, before: &str, after: &str, indent: Indent) -> Newline {
+    let content = before.trim_start_matches([' ', '\t']);
+    let lead = &before[..before.len() - content.len()];
+    let trimmed = content.trim_end_matches([' ', '\t']);
+    let newline = |text: String, cursor: usize| Newline {
+        from: lead.len() + trimmed.len(),
+        skip: after.len() - after.trim_start_matches([' ', '\t']).len(),
+        text,
+        cursor,
+    };
+    let line = |lead: &str, marker: &str| {
+        let text = format!("\n{}{}", lead, marker);
+        let cursor = text.len();
+        newline(text, cursor)
+    };
+
+    if let Some(marker) = comment_marker(language, content) {
+        return line(lead, &marker);
+    }
+    if language == Some("Rust") {
+        if trimmed.starts_with("/*") && !trimmed.ends_with("*/") {
+            return line(lead, " * ");
+        }
+        if trimmed.starts_with("*/") {
+            return line(lead.strip_suffix(' ').unwrap_or(lead), "");
+        }
+        if trimmed == "*" || trimmed.starts_with("* ") {
+            return line(lead, "* ");
+        }
+    }
+    if matches!(language, None | Some("Markdown"))
+        && let Some((marker, item)) = list_marker(content)
+    {
+        // Enter on an empty item ends the list.
+        if item.trim().is_empty() && after.trim().is_empty() {
+            return Newline {
+                from: lead.len(),
+                skip: 0,
+                text: String::new(),
+                cursor: 0,
+            };
+        }
+        return line(lead, &marker);
+    }
+
+    if let Some(opener) = trimmed
+        .chars()
+        .last()
+        .filter(|c| matches!(c, '{' | '[' | '('))
+    {
+        let inner = format!("\n{}{}", lead, indent.unit());
+        let cursor = inner.len();
+        if after.trim_start().starts_with(closer(opener)) {
+            return newline(format!("{}\n{}", inner, lead), cursor);
+        }
+        return newline(inner, cursor);
+    }
+    if language == Some("Python") {
+        if trimmed.ends_with(':') {
+            return line(lead, &indent.unit());
+        }
+        let statement = trimmed.split([' ', '(']).next().unwrap_or_default();
+        if PYTHON_DEDENTS.contains(&statement) {
+            return line(outdent(lead, indent), "");
+        }
+    }
+    line(lead, "")
+}
+
+/// Enter keeps the line's indentation, indents after an opening bracket (or a
+/// `:` in Python) and continues comments and list items. Returns true when the
+/// key was handled.
+pub fn handle_enter_key(
+    editor: &mut TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    highlighter: &SharedHighlighter,
+) -> bool {
+    let Some(mut buf) = editor.buffer() else {
+        return false;
+    };
+    let (enabled, indent) = {
+        let s = state.borrow();
+        (s.settings.editor.auto_indent, s.document.indent)
+    };
+    if !enabled {
+        return false;
+    }
+    let language = highlighter.borrow().language_name().map(str::to_string);
+
+    let pos = editor.insert_position();
+    let (start, end) = buf.selection_position().unwrap_or((pos, pos));
+    let line_start = buf.line_start(start);
+    let before = buf.text_range(line_start, start).unwrap_or_default();
+    let after = buf.text_range(end, buf.line_end(end)).unwrap_or_default();
+    let newline = smart_newline(language.as_deref(), &before, &after, indent);
+
+    let from = line_start + newline.from as i32;
+    buf.replace(from, end + newline.skip as i32, &newline.text);
+    buf.unselect();
+    editor.set_insert_position(from + newline.cursor as i32);
+    editor.show_insert_position();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FOUR: Indent = Indent {
+        tabs: false,
+        width: 4,
+    };
+
+    /// Presses Enter at the `|` in `line` and shows where the cursor ends up.
+    fn enter(language: Option<&str>, line: &str) -> String {
+        let (before, after) = line.split_once('|').unwrap();
+        let n = smart_newline(language, before, after, FOUR);
+        format!(
+            "{}{}|{}{}",
+            &before[..n.from],
+            &n.text[..n.cursor],
+            &n.text[n.cursor..],
+            &after[n.skip..]
+        )
+    }
+
+    #[test]
+    fn keeps_indentation_and_trims_around_the_break() {
+        assert_eq!(enter(None, "    foo|"), "    foo\n    |");
+        assert_eq!(enter(None, "  foo   |  bar"), "  foo\n  |bar");
+        assert_eq!(enter(None, "\tx|"), "\tx\n\t|");
+    }
+
+    #[test]
+    fn indents_after_an_opening_bracket() {
+        assert_eq!(enter(Some("Rust"), "fn x() {|"), "fn x() {\n    |");
+        assert_eq!(enter(Some("Rust"), "  f(|)"), "  f(\n      |\n  )");
+        assert_eq!(enter(None, "let a = [| ]"), "let a = [\n    |\n]");
+    }
+
+    #[test]
+    fn python_blocks() {
+        assert_eq!(enter(Some("Python"), "if x:|"), "if x:\n    |");
+        assert_eq!(
+            enter(Some("Python"), "        return 1|"),
+            "        return 1\n    |"
+        );
+        assert_eq!(enter(Some("Python"), "\t\tpass|"), "\t\tpass\n\t|");
+        assert_eq!(
+            enter(Some("Python"), "    passed = 1|"),
+            "    passed = 1\n    |"
+        );
+    }
+
+    #[test]
+    fn continues_comments() {
+        assert_eq!(enter(Some("Rust"), "    // note|"), "    // note\n    // |");
+        assert_eq!(enter(Some("Rust"), "///  doc|"), "///  doc\n///  |");
+        assert_eq!(
+            enter(Some("Python"), "#!/usr/bin/env python|"),
+            "#!/usr/bin/env python\n|"
+        );
+        assert_eq!(enter(Some("INI"), "; key|"), "; key\n; |");
+        assert_eq!(enter(None, "// not code|"), "// not code\n|");
+    }
+
+    #[test]
+    fn continues_block_comments() {
+        assert_eq!(enter(Some("Rust"), "/* start|"), "/* start\n * |");
+        assert_eq!(enter(Some("Rust"), " * more|"), " * more\n * |");
+        assert_eq!(enter(Some("Rust"), " */|"), " */\n|");
+        assert_eq!(enter(Some("Rust"), "/* done */|"), "/* done */\n|");
+    }
+
+    #[test]
+    fn list_markers() {
+        assert_eq!(list_marker("- item"), Some(("- ".into(), "item")));
+        assert_eq!(list_marker("9. nine"), Some(("10. ".into(), "nine")));
+        assert_eq!(list_marker("3)  x"), Some(("4)  ".into(), "x")));
+        assert_eq!(list_marker("* [x] done"), Some(("* [ ] ".into(), "done")));
+        assert_eq!(list_marker("-5 degrees"), None);
+        assert_eq!(list_marker("1.5 apples"), None);
+        assert_eq!(list_marker("plain"), None);
+    }
+
+    #[test]
+    fn continues_and_ends_lists() {
+        assert_eq!(enter(None, "  - item|"), "  - item\n  - |");
+        assert_eq!(enter(Some("Markdown"), "9. nine|"), "9. nine\n10. |");
+        assert_eq!(
+            enter(Some("Markdown"), "- [x] done|"),
+            "- [x] done\n- [ ] |"
+        );
+        // Enter on an empty item removes its marker instead.
+        assert_eq!(enter(None, "  - |"), "  |");
+        assert_eq!(enter(Some("Markdown"), "10. |"), "|");
+        // Lists only continue in prose.
+        assert_eq!(enter(Some("Rust"), "- x|"), "- x\n|");
+    }
+}
@@ -0,0 +1,214 @@
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    rc::Rc,
+};
+
+use super::config::{config_file, write_config_file};
+use super::dock::{Relayout, SharedDock, add_dock_page};
+use super::filter::select_source_range;
+use super::goto::{GotoTarget, current_source_line, resolve_goto};
+use super::results::context_text;
+use super::state::AppState;
+use super::status::UpdateStatus;
+
+const BOOKMARKS_FILE: &str = "bookmarks.txt";
+const PANEL_REFRESH_DELAY: f64 = 0.3;
+
+pub type BookmarksPanel = Rc<RefCell<HoldBrowser>>;
+
+#[derive(Clone)]
+pub struct BookmarksUi {
+    pub state: Rc<RefCell<AppState>>,
+    pub panel: BookmarksPanel,
+    pub editor: TextEditor,
+    pub buf: Rc<RefCell<TextBuffer>>,
+    pub dock: SharedDock,
+    pub relayout: Relayout,
+    pub update_status: UpdateStatus,
+}
+
+/// Reads the bookmarks file as (path, 0-based lines) entries.
+fn load_all() -> Vec<(String, Vec<usize>)> {
+    let Ok(content) = fs::read_to_string(config_file(BOOKMARKS_FILE)) else {
+        return vec![];
+    };
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let (path, lines) = line.rsplit_once('\t')?;
+            let lines = lines
+                .split(',')
+                .filter_map(|n| n.trim().parse::<usize>().ok())
+                .map(|n| n.saturating_sub(1))
+                .collect();
+            Some((path.to_string(), lines))
+        })
+        .collect()
+}
+
+pub fn load_bookmarks(path: &str) -> Vec<usize> {
+    load_all()
+        .into_iter()
+        .find(|(p, _)| p == path)
+        .map(|(_, lines)| lines)
+        .unwrap_or_default()
+}
+
+/// Stores the bookmarks for `path`, dropping its entry when there are none left.
+pub fn store_bookmarks(path: &str, lines: &[usize]) {
+    if path.is_empty() {
+        return;
+    }
+
+    let mut all = load_all();
+    all.retain(|(p, _)| p != path);
+    if !lines.is_empty() {
+        all.push((path.to_string(), lines.to_vec()));
+    }
+
+    let content: Vec<String> = all
+        .iter()
+        .map(|(p, lines)| {
+            let numbers: Vec<String> = lines.iter().map(|l| (l + 1).to_string()).collect();
+            format!("{}\t{}", p, numbers.join(","))
+        })
+        .collect();
+    write_config_file(BOOKMARKS_FILE, &content.join("\n"));
+}
+
+pub fn save_current_bookmarks(state: &Rc<RefCell<AppState>>) {
+    let s = state.borrow();
+    let lines: Vec<usize> = s.document.marks.bookmarked_lines().collect();
+    store_bookmarks(&s.document.filepath, &lines);
+}
+
+pub fn refresh_bookmarks_panel(ui: &BookmarksUi) {
+    let mut browser = ui.panel.borrow_mut();
+    browser.clear();
+
+    let s = ui.state.borrow();
+    let text = ui.buf.borrow().text();
+    let mut lines = text.split('\n').enumerate();
+    for line in s.document.marks.bookmarked_lines() {
+        let Some((_, content)) = lines.find(|(idx, _)| *idx == line) else {
+            break;
+        };
+        browser.add(&format!("{}\t{}", line + 1, context_text(content)));
+    }
+}
+
+fn jump_to_line(ui: &BookmarksUi, line: usize) {
+    let text = ui.buf.borrow().text();
+    let pos = resolve_goto(&text, 0, &GotoTarget::Line(line + 1, None)) as i32;
+    select_source_range(&ui.editor, &ui.buf, &ui.state, pos, pos);
+    (ui.update_status)();
+}
+
+pub fn toggle_bookmark(ui: &BookmarksUi) {
+    let line = current_source_line(&ui.editor, &ui.state.borrow());
+    ui.state.borrow_mut().document.marks.toggle_bookmark(line);
+    save_current_bookmarks(&ui.state);
+    refresh_bookmarks_panel(ui);
+    ui.editor.clone().redraw();
+}
+
+/// Jumps to the next (or previous) bookmark after the cursor line, wrapping around.
+pub fn step_bookmark(ui: &BookmarksUi, forward: bool) {
+    let line = current_source_line(&ui.editor, &ui.state.borrow());
+    let lines: Vec<usize> = ui
+        .state
+        .borrow()
+        .document
+        .marks
+        .bookmarked_lines()
+        .collect();
+
+    let target = if forward {
+        lines.iter().find(|l| **l > line).or(lines.first())
+    } else {
+        lines.iter().rev().find(|l| **l < line).or(lines.last())
+    };
+    if let Some(target) = target.copied() {
+        jump_to_line(ui, target);
+    }
+}
+
+pub fn clear_bookmarks(ui: &BookmarksUi) {
+    ui.state.borrow_mut().document.marks.clear_bookmarks();
+    save_current_bookmarks(&ui.state);
+    refresh_bookmarks_panel(ui);
+    ui.editor.clone().redraw();
+}
+
+pub fn create_bookmarks(
+    dock: &SharedDock,
+    relayout: &Relayout,
+    state: &Rc<RefCell<AppState>>,
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    update_status: &UpdateStatus,
+) -> BookmarksUi {
+    let mut browser = add_dock_page(dock, "Bookmarks");
+    browser.set_column_widths(&[60]);
+    browser.set_column_char('\t');
+
+    let ui = BookmarksUi {
+        state: Rc::clone(state),
+        panel: Rc::new(RefCell::new(browser)),
+        editor: editor.clone(),
+        buf: Rc::clone(buf),
+        dock: Rc::clone(dock),
+        relayout: Rc::clone(relayout),
+        update_status: Rc::clone(update_status),
+    };
+
+    {
+        let ui_ref = ui.clone();
+        ui.panel.borrow_mut().set_callback(move |b| {
+            let row = b.value();
+            if row <= 0 {
+                return;
+            }
+            let line = ui_ref
+                .state
+                .borrow()
+                .document
+                .marks
+                .bookmarked_lines()
+                .nth((row - 1) as usize);
+            if let Some(line) = line {
+                jump_to_line(&ui_ref, line);
+            }
+        });
+    }
+
+    // Edits move bookmarks around; refresh the list once typing settles.
+    {
+        let pending = Rc::new(Cell::new(false));
+        let ui_ref = ui.clone();
+        buf.borrow_mut()
+            .add_modify_callback(move |_, inserted, deleted, _, _| {
+                if (inserted == 0 && deleted == 0) || pending.get() {
+                    return;
+                }
+
+                pending.set(true);
+                let pending = Rc::clone(&pending);
+                let ui = ui_ref.clone();
+                app::add_timeout3(PANEL_REFRESH_DELAY, move |_| {
+                    pending.set(false);
+                    refresh_bookmarks_panel(&ui);
+                });
+            });
+    }
+
+    ui
+}
@@ -0,0 +1,392 @@
+use fltk::{
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use super::highlight::{Highlighter, SharedHighlighter};
+use super::state::AppState;
+
+/// Bytes searched on each side of the cursor for a matching bracket.
+const SCAN_LIMIT: i32 = 200_000;
+const PAIRS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+const QUOTES: [u8; 3] = [b'"', b'\'', b'`'];
+
+/// Tells whether a position in the scanned text is outside strings and comments.
+type IsCode<'a> = Option<&'a dyn Fn(usize) -> bool>;
+/// Finds a bracket pair from a cursor offset in the scanned text.
+type FindPair = fn(&[u8], usize, IsCode) -> Option<(usize, usize)>;
+
+fn is_opener(c: u8) -> bool {
+    PAIRS.iter().any(|p| p.0 == c)
+}
+
+fn is_closer(c: u8) -> bool {
+    PAIRS.iter().any(|p| p.1 == c)
+}
+
+/// The character that pairs with `c`, for brackets and quotes.
+fn partner(c: u8) -> Option<u8> {
+    if QUOTES.contains(&c) {
+        return Some(c);
+    }
+    PAIRS.iter().find_map(|&(open, close)| match c {
+        _ if c == open => Some(close),
+        _ if c == close => Some(open),
+        _ => None,
+    })
+}
+
+/// Counts nesting from the bracket at `at` towards its partner. When the
+/// bracket is in code, brackets in strings and comments are skipped.
+fn match_bracket(text: &[u8], at: usize, is_code: IsCode) -> Option<usize> {
+    let c = text[at];
+    let other = partner(c)?;
+    let skip_text = is_code.is_some_and(|f| f(at));
+    let counts = |i: usize| !skip_text || is_code.is_some_and(|f| f(i));
+
+    let mut depth = 0;
+    let mut step = |i: usize| {
+        if !counts(i) {
+            return false;
+        }
+        if text[i] == c {
+            depth += 1;
+        } else if text[i] == other {
+            depth -= 1;
+        }
+        depth == 0
+    };
+    if is_opener(c) {
+        (at..text.len()).find(|i| step(*i))
+    } else {
+        (0..=at).rev().find(|i| step(*i))
+    }
+}
+
+fn is_escaped(text: &[u8], at: usize) -> bool {
+    at > 0 && text[at - 1] == b'\\'
+}
+
+/// Pairs quotes on the same line by counting the ones before `at`.
+fn match_quote(text: &[u8], at: usize, is_code: IsCode) -> Option<usize> {
+    let q = text[at];
+    // With a grammar, only quotes that delimit a string pair up.
+    if is_escaped(text, at) || is_code.is_some_and(|f| f(at)) {
+        return None;
+    }
+    let line_start = text[..at]
+        .iter()
+        .rposition(|c| *c == b'\n')
+        .map_or(0, |i| i + 1);
+    let line_end = text[at..]
+        .iter()
+        .position(|c| *c == b'\n')
+        .map_or(text.len(), |i| at + i);
+    let is_quote = |i: &usize| text[*i] == q && !is_escaped(text, *i);
+
+    let before = (line_start..at).filter(is_quote).count();
+    if before % 2 == 0 {
+        (at + 1..line_end).find(is_quote)
+    } else {
+        (line_start..at).rev().find(is_quote)
+    }
+}
+
+/// The bracket or quote just after, else just before, the cursor, and its match.
+fn find_pair(text: &[u8], cursor: usize, is_code: IsCode) -> Option<(usize, usize)> {
+    [Some(cursor), cursor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .filter(|at| *at < text.len())
+        .find_map(|at| {
+            let found = if QUOTES.contains(&text[at]) {
+                match_quote(text, at, is_code)
+            } else {
+                match_bracket(text, at, is_code)
+            };
+            found.map(|m| (at, m))
+        })
+}
+
+/// The innermost brackets around the cursor.
+fn enclosing(text: &[u8], cursor: usize, is_code: IsCode) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let open = (0..cursor.min(text.len())).rev().find(|i| {
+        if is_code.is_some_and(|f| !f(*i)) {
+            return false;
+        }
+        if is_closer(text[*i]) {
+            depth += 1;
+        } else if is_opener(text[*i]) {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    match_bracket(text, open, is_code).map(|close| (open, close))
+}
+
+/// Text around `pos`, cut at line boundaries, and where it starts.
+fn scan_window(buf: &TextBuffer, pos: i32) -> (Vec<u8>, i32) {
+    let start = buf.line_start((pos - SCAN_LIMIT).max(0));
+    let end = buf.line_end((pos + SCAN_LIMIT).min(buf.length()));
+    let text = buf.text_range(start, end).unwrap_or_default();
+    (text.into_bytes(), start)
+}
+
+/// Runs `find` over the text around the cursor, consulting the grammar unless a
+/// filtered view is on screen. Returns document positions.
+fn with_window(
+    buf: &TextBuffer,
+    pos: i32,
+    h: &Highlighter,
+    filtered: bool,
+    find: FindPair,
+) -> Option<(i32, i32)> {
+    let (text, offset) = scan_window(buf, pos);
+    let is_code = |i: usize| h.is_code(offset as usize + i);
+    let is_code: IsCode = if h.has_language() && !filtered {
+        Some(&is_code)
+    } else {
+        None
+    };
+    find(&text, (pos - offset) as usize, is_code)
+        .map(|(a, b)| (a as i32 + offset, b as i32 + offset))
+}
+
+/// Highlights the bracket or quote at the cursor together with its match.
+pub fn update_bracket_match(
+    editor: &TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+) {
+    let (Ok(mut h), Ok(s)) = (highlighter.try_borrow_mut(), state.try_borrow()) else {
+        return;
+    };
+    // The highlighter styles the document, which a filtered view doesn't show.
+    let pair = match (editor.buffer(), &s.document.filter.view) {
+        (Some(buf), None) => with_window(&buf, editor.insert_position(), &h, false, find_pair),
+        _ => None,
+    };
+    h.set_brackets(pair.map(|(a, b)| vec![a, b]).unwrap_or_default());
+}
+
+/// The bracket pair at the cursor, else the one around it, in document order.
+fn pair_or_enclosing(
+    editor: &TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+) -> Option<(i32, i32, bool)> {
+    let buf = editor.buffer()?;
+    let pos = editor.insert_position();
+    let h = highlighter.borrow();
+    let filtered = state.borrow().document.filter.view.is_some();
+
+    if let Some((at, m)) = with_window(&buf, pos, &h, filtered, find_pair) {
+        return Some((at.min(m), at.max(m), true));
+    }
+    with_window(&buf, pos, &h, filtered, enclosing).map(|(open, close)| (open, close, false))
+}
+
+/// Moves the cursor to the bracket matching the one next to it, or to the
+/// opening bracket around it.
+pub fn jump_to_match(
+    editor: &mut TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+) {
+    let pos = editor.insert_position();
+    let Some((open, close, at_bracket)) = pair_or_enclosing(editor, highlighter, state) else {
+        return;
+    };
+    let target = if !at_bracket {
+        open
+    } else if pos == open || pos == open + 1 {
+        // Keep the cursor on the same side of the bracket it was on.
+        close + pos - open
+    } else {
+        open + pos - close
+    };
+    editor.set_insert_position(target);
+    editor.show_insert_position();
+}
+
+/// Selects what the brackets at or around the cursor enclose; again, the
+/// brackets too.
+pub fn select_to_match(
+    editor: &mut TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+) {
+    let Some((open, close, _)) = pair_or_enclosing(editor, highlighter, state) else {
+        return;
+    };
+    let Some(mut buf) = editor.buffer() else {
+        return;
+    };
+    let (start, end) = if buf.selection_position() == Some((open + 1, close)) {
+        (open, close + 1)
+    } else {
+        (open + 1, close)
+    };
+    buf.select(start, end);
+    editor.set_insert_position(end);
+    editor.show_insert_position();
+}
+
+fn auto_close_enabled(state: &Rc<RefCell<AppState>>) -> bool {
+    state.borrow().settings.editor.auto_close_brackets
+}
+
+/// Typing an opening bracket or quote also inserts its partner, or wraps the
+/// selection; typing a closer just before the same closer steps over it.
+/// Returns true when the key was handled.
+pub fn handle_typed_char(
+    editor: &mut TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    typed: &str,
+) -> bool {
+    let &[c] = typed.as_bytes() else {
+        return false;
+    };
+    let Some(close) = partner(c) else {
+        return false;
+    };
+    if !auto_close_enabled(state) {
+        return false;
+    }
+    let Some(mut buf) = editor.buffer() else {
+        return false;
+    };
+
+    if let Some((start, end)) = buf.selection_position().filter(|_| !is_closer(c)) {
+        let selected = buf.selection_text();
+        buf.replace(
+            start,
+            end,
+            &format!("{}{}{}", c as char, selected, close as char),
+        );
+        buf.select(start + 1, end + 1);
+        editor.set_insert_position(end + 1);
+        return true;
+    }
+
+    let pos = editor.insert_position();
+    let next = (pos < buf.length()).then(|| buf.byte_at(pos));
+    let prev = (pos > 0).then(|| buf.byte_at(pos - 1));
+    if (is_closer(c) || QUOTES.contains(&c)) && next == Some(c) {
+        editor.set_insert_position(pos + 1);
+        return true;
+    }
+    if is_closer(c) {
+        return false;
+    }
+
+    let next_ok =
+        next.is_none_or(|n| n.is_ascii_whitespace() || is_closer(n) || b",;".contains(&n));
+    // Don't pair apostrophes in words or quotes right after other text.
+    let prev_ok = !QUOTES.contains(&c)
+        || prev.is_none_or(|p| p.is_ascii_whitespace() || is_opener(p) || b"=,:".contains(&p));
+    if !next_ok || !prev_ok {
+        return false;
+    }
+    buf.insert(pos, &format!("{}{}", c as char, close as char));
+    editor.set_insert_position(pos + 1);
+    true
+}
+
+/// Backspace between an empty pair deletes both halves.
+pub fn handle_backspace_key(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) -> bool {
+    let Some(mut buf) = editor.buffer() else {
+        return false;
+    };
+    let pos = editor.insert_position();
+    if !auto_close_enabled(state) || buf.selected() || pos == 0 || pos >= buf.length() {
+        return false;
+    }
+    let (prev, next) = (buf.byte_at(pos - 1), buf.byte_at(pos));
+    if is_closer(prev) || partner(prev) != Some(next) {
+        return false;
+    }
+    buf.remove(pos - 1, pos + 1);
+    editor.set_insert_position(pos - 1);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Treats the bytes in `strings` as string or comment text.
+    fn outside(strings: std::ops::RangeInclusive<usize>) -> impl Fn(usize) -> bool {
+        move |i| !strings.contains(&i)
+    }
+
+    #[test]
+    fn brackets_match_across_nesting() {
+        let text = b"a(b[c]d)e";
+        assert_eq!(match_bracket(text, 1, None), Some(7));
+        assert_eq!(match_bracket(text, 7, None), Some(1));
+        assert_eq!(match_bracket(text, 3, None), Some(5));
+        assert_eq!(match_bracket(b"((a)", 0, None), None);
+        assert_eq!(match_bracket(text, 0, None), None);
+    }
+
+    #[test]
+    fn brackets_in_strings_are_skipped_from_code() {
+        let text = br#"f("(", x)"#;
+        let code = outside(2..=4);
+        assert_eq!(match_bracket(text, 1, Some(&code)), Some(8));
+        assert_eq!(match_bracket(text, 1, None), None);
+    }
+
+    #[test]
+    fn quotes_pair_on_their_line() {
+        let text = br#"say "hi" and "yo""#;
+        assert_eq!(match_quote(text, 4, None), Some(7));
+        assert_eq!(match_quote(text, 7, None), Some(4));
+        assert_eq!(match_quote(text, 13, None), Some(16));
+        assert_eq!(match_quote(b"\"a\nb\"", 0, None), None);
+    }
+
+    #[test]
+    fn escaped_quotes_are_not_delimiters() {
+        let text = br#""a\"b""#;
+        assert_eq!(match_quote(text, 0, None), Some(5));
+        assert_eq!(match_quote(text, 5, None), Some(0));
+        assert_eq!(match_quote(text, 3, None), None);
+    }
+
+    #[test]
+    fn quotes_in_code_do_not_pair() {
+        let code = |_| true;
+        assert_eq!(match_quote(b"it's 'x'", 2, Some(&code)), None);
+    }
+
+    #[test]
+    fn pair_after_then_before_the_cursor() {
+        assert_eq!(find_pair(b"(a)", 0, None), Some((0, 2)));
+        assert_eq!(find_pair(b"(a)", 3, None), Some((2, 0)));
+        assert_eq!(find_pair(b"(a)", 1, None), Some((0, 2)));
+        assert_eq!(find_pair(b"abc", 1, None), None);
+    }
+
+    #[test]
+    fn innermost_enclosing_brackets() {
+        let text = b"f(a, [b, c])";
+        assert_eq!(enclosing(text, 6, None), Some((5, 10)));
+        assert_eq!(enclosing(text, 3, None), Some((1, 11)));
+        assert_eq!(enclosing(text, 11, None), Some((1, 11)));
+        assert_eq!(enclosing(text, 0, None), None);
+    }
+
+    #[test]
+    fn enclosing_skips_brackets_in_strings() {
+        let text = b"(a ')' b)";
+        let code = outside(3..=5);
+        assert_eq!(enclosing(text, 7, Some(&code)), Some((0, 8)));
+    }
+}
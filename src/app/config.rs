@@ -0,0 +1,31 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+pub fn config_dir() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        if let Ok(appdata) = env::var("APPDATA") {
+            return Path::new(&appdata).join("plain_text");
+        }
+    }
+
+    if let Ok(home) = env::var("HOME") {
+        return Path::new(&home).join(".config").join("plain_text");
+    }
+
+    PathBuf::from(".")
+}
+
+pub fn config_file(name: &str) -> PathBuf {
+    config_dir().join(name)
+}
+
+pub fn write_config_file(name: &str, content: &str) {
+    let path = config_file(name);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(path, content);
+}
@@ -0,0 +1,434 @@
+use fltk::{
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use quick_xml::{Reader, Writer, events::Event};
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+use std::{cell::RefCell, path::Path, rc::Rc};
+use toml_edit::{Decor, DocumentMut, Item, RawString, Table, Value};
+
+use super::edit::{editable_buffer, replace_changed_range};
+use super::indent::Indent;
+use super::state::AppState;
+use super::status::show_status_message;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Json,
+    Xml,
+    Toml,
+}
+
+/// What went wrong and the byte offset in the text it points at.
+struct SyntaxError {
+    offset: usize,
+    message: String,
+}
+
+impl SyntaxError {
+    fn new(text: &str, offset: usize, message: impl Into<String>) -> SyntaxError {
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        SyntaxError {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl DataFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Xml => "XML",
+            DataFormat::Toml => "TOML",
+        }
+    }
+
+    /// From the file extension, else from whichever format the text parses as.
+    pub fn detect(path: &str, text: &str) -> Option<DataFormat> {
+        let extension = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("json" | "geojson" | "webmanifest") => return Some(DataFormat::Json),
+            Some("xml" | "svg" | "xsd" | "xsl" | "xslt" | "plist" | "rss" | "atom" | "xaml") => {
+                return Some(DataFormat::Xml);
+            }
+            Some("toml") => return Some(DataFormat::Toml),
+            _ => {}
+        }
+
+        let content = text.trim_start();
+        if content.starts_with('<') {
+            Some(DataFormat::Xml)
+        } else if serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok() {
+            Some(DataFormat::Json)
+        } else if text.parse::<toml::Table>().is_ok() || looks_like_toml(content) {
+            Some(DataFormat::Toml)
+        } else if content.starts_with(['{', '[']) {
+            // Broken either way; report it as JSON.
+            Some(DataFormat::Json)
+        } else {
+            None
+        }
+    }
+
+    /// Re-lays out `text`, indented by `indent`, or minified without one.
+    fn reformat(self, text: &str, indent: Option<Indent>) -> Result<String, SyntaxError> {
+        match self {
+            DataFormat::Json => json(text, indent),
+            DataFormat::Xml => xml(text, indent),
+            DataFormat::Toml => toml(text, indent.is_none()),
+        }
+    }
+}
+
+/// Whether broken text starts with a `[table]` header or a `key = value` line.
+fn looks_like_toml(content: &str) -> bool {
+    let first = content.lines().next().unwrap_or_default().trim();
+    if first.starts_with('[') && first.ends_with(']') {
+        let name = first.trim_matches(['[', ']']);
+        return name.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '"');
+    }
+    !first.starts_with('{') && first.contains('=')
+}
+
+/// The byte offset of a 1-based line and column.
+fn line_col_offset(text: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    line_start + column.saturating_sub(1)
+}
+
+fn json(text: &str, indent: Option<Indent>) -> Result<String, SyntaxError> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| {
+        let message = e.to_string();
+        // The position is reported through the cursor instead.
+        let message = message.split(" at line ").next().unwrap_or_default();
+        SyntaxError::new(text, line_col_offset(text, e.line(), e.column()), message)
+    })?;
+    let Some(indent) = indent else {
+        return Ok(value.to_string());
+    };
+
+    let unit = indent.unit();
+    let mut out = vec![];
+    let mut serializer =
+        Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(unit.as_bytes()));
+    value
+        .serialize(&mut serializer)
+        .map_err(|e| SyntaxError::new(text, 0, e.to_string()))?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Formats through the reader's events, so attributes and elements keep their
+/// order. Whitespace around text is trimmed.
+fn xml(text: &str, indent: Option<Indent>) -> Result<String, SyntaxError> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+    let mut writer = match indent {
+        Some(Indent { tabs: true, .. }) => Writer::new_with_indent(vec![], b'\t', 1),
+        Some(indent) => Writer::new_with_indent(vec![], b' ', indent.width),
+        None => Writer::new(vec![]),
+    };
+    // Open elements and where they start; the reader doesn't report unclosed ones.
+    let mut open = vec![];
+
+    loop {
+        let start = reader.buffer_position() as usize;
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => {
+                return Err(SyntaxError::new(
+                    text,
+                    reader.error_position() as usize,
+                    e.to_string(),
+                ));
+            }
+        };
+        match &event {
+            Event::Start(e) => {
+                // Skip the whitespace the reader trimmed before the tag.
+                let rest = &text[start..];
+                let at = start + rest.len() - rest.trim_start().len();
+                open.push((String::from_utf8_lossy(e.name().as_ref()).into_owned(), at));
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        writer
+            .write_event(event)
+            .map_err(|e| SyntaxError::new(text, start, e.to_string()))?;
+    }
+
+    if let Some((name, start)) = open.pop() {
+        return Err(SyntaxError::new(
+            text,
+            start,
+            format!("<{}> is never closed", name),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&writer.into_inner()).into_owned())
+}
+
+/// Formatting keeps comments and key order and only tidies the whitespace
+/// around keys, values and table headers; arrays keep their layout.
+/// Minifying drops comments.
+fn toml(text: &str, minify: bool) -> Result<String, SyntaxError> {
+    if minify {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| {
+            let offset = e.span().map_or(0, |span| span.start);
+            SyntaxError::new(text, offset, e.message().trim())
+        })?;
+        return toml::to_string(&table).map_err(|e| SyntaxError::new(text, 0, e.to_string()));
+    }
+
+    let mut doc: DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| {
+        let offset = e.span().map_or(0, |span| span.start);
+        SyntaxError::new(text, offset, e.message().trim())
+    })?;
+    tidy_table(doc.as_table_mut());
+    // The text after the last item only holds comments and blank lines.
+    let trailing = comment_lines(&format!("{}\n", raw_text(Some(doc.trailing()))));
+    doc.set_trailing(trailing);
+    Ok(doc.to_string().trim().to_string())
+}
+
+fn raw_text(raw: Option<&RawString>) -> &str {
+    raw.and_then(RawString::as_str).unwrap_or_default()
+}
+
+/// The comment lines of a decor prefix, trimmed, with each run of blank lines
+/// cut to one. What follows the last newline indents the item itself.
+fn comment_lines(text: &str) -> String {
+    let Some((lines, _)) = text.rsplit_once('\n') else {
+        return String::new();
+    };
+    let mut out = String::new();
+    let mut blank = false;
+    for line in lines.split('\n').map(str::trim) {
+        if !line.is_empty() {
+            out.push_str(line);
+            out.push('\n');
+        } else if !blank {
+            out.push('\n');
+        }
+        blank = line.is_empty();
+    }
+    out
+}
+
+/// A comment after a value or header on the same line.
+fn trailing_comment(text: &str) -> String {
+    match text.trim() {
+        "" => String::new(),
+        comment => format!(" {}", comment),
+    }
+}
+
+/// Puts a blank line before a table header and keeps its comments.
+fn tidy_header(decor: &mut Decor) {
+    let comments = comment_lines(raw_text(decor.prefix()));
+    let suffix = trailing_comment(raw_text(decor.suffix()));
+    *decor = Decor::new(format!("\n{}", comments.trim_start_matches('\n')), suffix);
+}
+
+fn tidy_table(table: &mut Table) {
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            Item::Value(value) => {
+                let decor = key.leaf_decor_mut();
+                *decor = Decor::new(comment_lines(raw_text(decor.prefix())), " ");
+                *key.dotted_decor_mut() = Decor::new("", "");
+                // Inline tables can't hold comments, so they can be redone.
+                if let Value::InlineTable(inline) = value {
+                    inline.fmt();
+                }
+                let decor = value.decor_mut();
+                *decor = Decor::new(" ", trailing_comment(raw_text(decor.suffix())));
+            }
+            Item::Table(table) => {
+                if table.is_dotted() {
+                    *key.dotted_decor_mut() = Decor::new("", "");
+                } else if !table.is_implicit() {
+                    *key.leaf_decor_mut() = Decor::new("", "");
+                    tidy_header(table.decor_mut());
+                }
+                tidy_table(table);
+            }
+            Item::ArrayOfTables(array) => {
+                *key.leaf_decor_mut() = Decor::new("", "");
+                for table in array.iter_mut() {
+                    tidy_header(table.decor_mut());
+                    tidy_table(table);
+                }
+            }
+            Item::None => {}
+        }
+    }
+}
+
+/// The selection, else the whole document, its text and its format.
+fn target(
+    editor: &TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    buf: &TextBuffer,
+) -> Option<(i32, i32, String, DataFormat)> {
+    let (start, end) = buf.selection_position().unwrap_or((0, buf.length()));
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let path = state.borrow().document.filepath.clone();
+    match DataFormat::detect(&path, &text) {
+        Some(format) => Some((start, end, text, format)),
+        None => {
+            show_status_message(state, editor, "Not recognized as JSON, XML or TOML");
+            None
+        }
+    }
+}
+
+/// Puts the cursor on the error and describes it in the status bar.
+fn report(
+    editor: &mut TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    buf: &mut TextBuffer,
+    start: i32,
+    format: DataFormat,
+    err: SyntaxError,
+) {
+    buf.unselect();
+    editor.set_insert_position(start + err.offset as i32);
+    editor.show_insert_position();
+    let message = format!("Invalid {}: {}", format.name(), err.message);
+    show_status_message(state, editor, &message);
+}
+
+/// Pretty-prints or minifies the selection, or the whole document, as one undo
+/// step. A syntax error leaves the text alone and moves the cursor to it.
+pub fn format_data(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>, minify: bool) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let Some((start, end, text, format)) = target(editor, state, &buf) else {
+        return;
+    };
+    let indent = (!minify).then(|| state.borrow().document.indent);
+    let formatted = match format.reformat(&text, indent) {
+        Ok(formatted) => formatted,
+        Err(err) => return report(editor, state, &mut buf, start, format, err),
+    };
+
+    let ending = if text.ends_with('\n') { "\n" } else { "" };
+    let new_text = format!("{}{}", formatted.trim_end(), ending);
+    if buf.selected() {
+        if new_text != text {
+            buf.replace(start, end, &new_text);
+            buf.select(start, start + new_text.len() as i32);
+            editor.set_insert_position(start + new_text.len() as i32);
+        }
+    } else if replace_changed_range(&mut buf, &new_text) {
+        editor.set_insert_position(editor.insert_position().min(buf.length()));
+    }
+    editor.show_insert_position();
+}
+
+/// Checks the selection, or the whole document, and moves the cursor to the
+/// first syntax error.
+pub fn validate_data(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let Some((start, _, text, format)) = target(editor, state, &buf) else {
+        return;
+    };
+    match format.reformat(&text, None) {
+        Ok(_) => show_status_message(state, editor, &format!("Valid {}", format.name())),
+        Err(err) => report(editor, state, &mut buf, start, format, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_SPACES: Option<Indent> = Some(Indent {
+        tabs: false,
+        width: 2,
+    });
+
+    fn format(format: DataFormat, text: &str) -> String {
+        format.reformat(text, TWO_SPACES).ok().unwrap()
+    }
+
+    fn error_offset(format: DataFormat, text: &str) -> usize {
+        format.reformat(text, None).err().unwrap().offset
+    }
+
+    #[test]
+    fn detect_by_extension_then_content() {
+        let detect = DataFormat::detect;
+        assert!(detect("a.JSON", "not json") == Some(DataFormat::Json));
+        assert!(detect("icon.svg", "") == Some(DataFormat::Xml));
+        assert!(detect("Cargo.toml", "") == Some(DataFormat::Toml));
+        assert!(detect("", "  <root/>") == Some(DataFormat::Xml));
+        assert!(detect("", "[1, 2]") == Some(DataFormat::Json));
+        assert!(detect("", "[package]\nname = \"x\"") == Some(DataFormat::Toml));
+        assert!(detect("", "[server]\nport = ") == Some(DataFormat::Toml));
+        assert!(detect("", "{\"a\": ") == Some(DataFormat::Json));
+        assert!(detect("notes.txt", "just some words").is_none());
+    }
+
+    #[test]
+    fn line_and_column_to_offset() {
+        let text = "ab\ncd\r\nef";
+        assert_eq!(line_col_offset(text, 1, 1), 0);
+        assert_eq!(line_col_offset(text, 1, 2), 1);
+        assert_eq!(line_col_offset(text, 2, 1), 3);
+        assert_eq!(line_col_offset(text, 3, 2), 8);
+        assert_eq!(line_col_offset(text, 0, 0), 0);
+    }
+
+    #[test]
+    fn json_keeps_key_order() {
+        let text = r#"{"z": 1, "a": {"y": 2.50, "b": [true, null]}}"#;
+        assert_eq!(
+            format(DataFormat::Json, text),
+            "{\n  \"z\": 1,\n  \"a\": {\n    \"y\": 2.50,\n    \"b\": [\n      true,\n      null\n    ]\n  }\n}"
+        );
+        let minified = DataFormat::Json.reformat(text, None).ok().unwrap();
+        assert_eq!(minified, r#"{"z":1,"a":{"y":2.50,"b":[true,null]}}"#);
+    }
+
+    #[test]
+    fn json_error_points_at_the_problem() {
+        assert_eq!(error_offset(DataFormat::Json, "[1,\n 2 3]"), 7);
+    }
+
+    #[test]
+    fn xml_unclosed_element_points_at_its_tag() {
+        let text = "<root>\n  <item>x</item>";
+        let err = DataFormat::Xml.reformat(text, None).err().unwrap();
+        assert_eq!(err.message, "<root> is never closed");
+        assert_eq!(err.offset, 0);
+        let text = "<a>\n  <b>\n    <c/>";
+        assert_eq!(error_offset(DataFormat::Xml, text), 6);
+    }
+
+    #[test]
+    fn toml_keeps_comments() {
+        let text = "# top\n  title   =  \"x\"   # why\n\n\n# about\n[ server ]\nport=80\nhost.name = {a=1}\n";
+        assert_eq!(
+            format(DataFormat::Toml, text),
+            "# top\ntitle = \"x\" # why\n\n# about\n[server]\nport = 80\nhost.name = { a = 1 }"
+        );
+    }
+}
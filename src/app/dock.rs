@@ -0,0 +1,78 @@
+use fltk::{browser::HoldBrowser, group::Group, group::Tabs, prelude::*};
+use std::{cell::RefCell, rc::Rc};
+
+pub const DOCK_HEIGHT: i32 = 160;
+const TAB_HEIGHT: i32 = 25;
+
+pub struct Dock {
+    pub tabs: Tabs,
+    pub visible: bool,
+}
+
+pub type SharedDock = Rc<RefCell<Dock>>;
+pub type Relayout = Rc<dyn Fn()>;
+
+pub fn create_dock(x: i32, y: i32, w: i32) -> SharedDock {
+    let mut tabs = Tabs::new(x, y, w, DOCK_HEIGHT, "");
+    tabs.end();
+    tabs.hide();
+
+    Rc::new(RefCell::new(Dock {
+        tabs,
+        visible: false,
+    }))
+}
+
+pub fn add_dock_page(dock: &SharedDock, label: &str) -> HoldBrowser {
+    let d = dock.borrow();
+    let x = d.tabs.x();
+    let y = d.tabs.y() + TAB_HEIGHT;
+    let w = d.tabs.w();
+    let h = d.tabs.h() - TAB_HEIGHT;
+
+    d.tabs.begin();
+    let page = Group::new(x, y, w, h, None).with_label(label);
+    let mut browser = HoldBrowser::new(x, y, w, h, "");
+    browser.set_text_size(12);
+    page.end();
+    page.resizable(&browser);
+    d.tabs.end();
+
+    browser
+}
+
+fn page_of(browser: &HoldBrowser) -> Option<Group> {
+    browser.parent()
+}
+
+pub fn show_dock_page(dock: &SharedDock, browser: &HoldBrowser, relayout: &Relayout) {
+    {
+        let mut d = dock.borrow_mut();
+        if let Some(page) = page_of(browser) {
+            let _ = d.tabs.set_value(&page);
+        }
+        d.visible = true;
+        d.tabs.show();
+    }
+    relayout();
+}
+
+/// Shows the page if it is hidden or behind another tab, otherwise hides the dock.
+pub fn toggle_dock_page(dock: &SharedDock, browser: &HoldBrowser, relayout: &Relayout) {
+    let showing_page = {
+        let d = dock.borrow();
+        let current = d.tabs.value().map(|g| g.as_widget_ptr());
+        d.visible && current.is_some() && current == page_of(browser).map(|g| g.as_widget_ptr())
+    };
+
+    if showing_page {
+        {
+            let mut d = dock.borrow_mut();
+            d.visible = false;
+            d.tabs.hide();
+        }
+        relayout();
+    } else {
+        show_dock_page(dock, browser, relayout);
+    }
+}
@@ -0,0 +1,72 @@
+use fltk::{
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use super::state::AppState;
+
+/// Replaces the whole buffer content with `new_text`, touching only the span
+/// that actually differs so the change is a single undo step and the rest of
+/// the buffer (and the cursor around it) stays put.
+pub fn replace_changed_range(buf: &mut TextBuffer, new_text: &str) -> bool {
+    let old_text = buf.text();
+    if old_text == new_text {
+        return false;
+    }
+
+    let old = old_text.as_bytes();
+    let new = new_text.as_bytes();
+
+    let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    while !old_text.is_char_boundary(prefix) || !new_text.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old_text.is_char_boundary(old.len() - suffix)
+        || !new_text.is_char_boundary(new.len() - suffix)
+    {
+        suffix -= 1;
+    }
+
+    buf.replace(
+        prefix as i32,
+        (old.len() - suffix) as i32,
+        &new_text[prefix..new.len() - suffix],
+    );
+    true
+}
+
+/// The whole lines touched by the selection, or the cursor's line.
+pub fn selected_lines(editor: &TextEditor, buf: &TextBuffer) -> (i32, i32) {
+    let (start, end) = buf
+        .selection_position()
+        .unwrap_or((editor.insert_position(), editor.insert_position()));
+    // A selection ending at a line start doesn't include that line.
+    let last = if end > start && buf.line_start(end) == end {
+        end - 1
+    } else {
+        end
+    };
+    (buf.line_start(start), buf.line_end(last))
+}
+
+pub fn selection_spans_lines(buf: &TextBuffer) -> bool {
+    buf.selection_text().contains('\n')
+}
+
+/// The document buffer, unless a filtered view is on screen.
+pub fn editable_buffer(editor: &TextEditor, state: &Rc<RefCell<AppState>>) -> Option<TextBuffer> {
+    if state.borrow().document.filter.view.is_some() {
+        return None;
+    }
+    editor.buffer()
+}
@@ -0,0 +1,391 @@
+use encoding_rs::Encoding;
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::encoding::LineEnding;
+use super::indent::Indent;
+
+const EDITORCONFIG_FILE: &str = ".editorconfig";
+/// Larger `{a..b}` ranges match any integer rather than being spelled out.
+const MAX_RANGE_ALTERNATIVES: i64 = 1000;
+
+/// The `.editorconfig` properties that apply to one file. Unset ones leave
+/// the editor's own settings alone.
+#[derive(Clone, Default, PartialEq)]
+pub struct EditorConfig {
+    pub indent_tabs: Option<bool>,
+    pub indent_size: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    /// Encoding and whether to write a byte order mark.
+    pub charset: Option<(&'static Encoding, bool)>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub max_line_length: Option<i32>,
+}
+
+struct Section {
+    /// `None` when the pattern couldn't be compiled; such sections match nothing.
+    matcher: Option<Regex>,
+    properties: Vec<(String, String)>,
+}
+
+struct ConfigFile {
+    root: bool,
+    sections: Vec<Section>,
+}
+
+/// Finds the `}` closing the brace group whose contents start at `from`.
+fn closing_brace(chars: &[char], from: usize) -> Option<usize> {
+    let mut depth = 1;
+    let mut i = from;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Splits brace contents at commas outside nested braces.
+fn split_alternatives(chars: &[char]) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut depth = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                parts.last_mut().unwrap().extend([c, chars[i + 1]]);
+                i += 1;
+            }
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(String::new());
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if c != '\\' {
+            parts.last_mut().unwrap().push(c);
+        }
+        i += 1;
+    }
+    parts
+}
+
+fn numeric_range(content: &str) -> Option<String> {
+    let (a, b) = content.split_once("..")?;
+    let (a, b): (i64, i64) = (a.parse().ok()?, b.parse().ok()?);
+    let (lo, hi) = (a.min(b), a.max(b));
+    if hi - lo > MAX_RANGE_ALTERNATIVES {
+        return Some(r"[+-]?\d+".into());
+    }
+    let numbers: Vec<String> = (lo..=hi).map(|n| n.to_string()).collect();
+    Some(format!("(?:{})", numbers.join("|")))
+}
+
+/// Translates an EditorConfig glob into a regex fragment.
+fn translate(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut re = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                re.push_str(".*");
+                i += 1;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|c| *c == ']') {
+                Some(len) => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let (negate, class) = match class.strip_prefix('!') {
+                        Some(rest) => ("^", rest.to_string()),
+                        None => ("", class),
+                    };
+                    re.push_str(&format!("[{}{}]", negate, class.replace('\\', r"\\")));
+                    i += len + 1;
+                }
+                None => re.push_str(r"\["),
+            },
+            '{' => match closing_brace(&chars, i + 1) {
+                Some(end) => {
+                    let content: String = chars[i + 1..end].iter().collect();
+                    let alternatives = split_alternatives(&chars[i + 1..end]);
+                    if let Some(range) = numeric_range(&content) {
+                        re.push_str(&range);
+                    } else if alternatives.len() > 1 {
+                        let parts: Vec<String> =
+                            alternatives.iter().map(|a| translate(a)).collect();
+                        re.push_str(&format!("(?:{})", parts.join("|")));
+                    } else {
+                        re.push_str(&regex::escape(&format!("{{{}}}", content)));
+                    }
+                    i = end;
+                }
+                None => re.push_str(r"\{"),
+            },
+            '\\' if i + 1 < chars.len() => {
+                re.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 1;
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re
+}
+
+/// Patterns with a slash are relative to the config's directory; others match
+/// a file name at any depth below it.
+fn section_matcher(dir: &str, pattern: &str) -> Option<Regex> {
+    let dir = regex::escape(dir.trim_end_matches('/'));
+    let re = if pattern.contains('/') {
+        format!("^{}/{}$", dir, translate(pattern.trim_start_matches('/')))
+    } else {
+        format!("^{}/(?:.*/)?{}$", dir, translate(pattern))
+    };
+    Regex::new(&re).ok()
+}
+
+fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn parse_config(path: &Path) -> Option<ConfigFile> {
+    let content = fs::read_to_string(path).ok()?;
+    let dir = slash_path(path.parent()?);
+    let mut config = ConfigFile {
+        root: false,
+        sections: vec![],
+    };
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(pattern) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            config.sections.push(Section {
+                matcher: section_matcher(&dir, pattern),
+                properties: vec![],
+            });
+            continue;
+        }
+        let Some((key, value)) = line.split_once(['=', ':']) else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim().to_lowercase();
+        match config.sections.last_mut() {
+            Some(section) => section.properties.push((key, value)),
+            None if key == "root" => config.root = value == "true",
+            None => {}
+        }
+    }
+    Some(config)
+}
+
+/// `.editorconfig` files from the file's directory up to the nearest `root = true`.
+fn config_files(path: &Path) -> Vec<ConfigFile> {
+    let mut files = vec![];
+    let mut dir: Option<PathBuf> = path.parent().map(Path::to_path_buf);
+    while let Some(d) = dir {
+        if let Some(config) = parse_config(&d.join(EDITORCONFIG_FILE)) {
+            let root = config.root;
+            files.push(config);
+            if root {
+                break;
+            }
+        }
+        dir = d.parent().map(Path::to_path_buf);
+    }
+    files
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl EditorConfig {
+    /// Collects the properties for `path`; nearer files and later sections win.
+    pub fn for_path(path: &str) -> EditorConfig {
+        if path.is_empty() {
+            return EditorConfig::default();
+        }
+        let absolute = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let target = slash_path(&absolute);
+
+        let mut properties = BTreeMap::new();
+        for config in config_files(&absolute).iter().rev() {
+            for section in config
+                .sections
+                .iter()
+                .filter(|s| s.matcher.as_ref().is_some_and(|m| m.is_match(&target)))
+            {
+                for (key, value) in &section.properties {
+                    properties.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        EditorConfig::from_properties(&properties)
+    }
+
+    fn from_properties(properties: &BTreeMap<String, String>) -> EditorConfig {
+        let get = |key: &str| {
+            properties
+                .get(key)
+                .map(String::as_str)
+                .filter(|v| *v != "unset")
+        };
+        let number = |key: &str| get(key).and_then(|v| v.parse::<usize>().ok());
+
+        let tab_width = number("tab_width");
+        let indent_size = match get("indent_size") {
+            Some("tab") => tab_width,
+            _ => number("indent_size"),
+        };
+        EditorConfig {
+            indent_tabs: get("indent_style").and_then(|v| match v {
+                "tab" => Some(true),
+                "space" => Some(false),
+                _ => None,
+            }),
+            // Without a tab_width, tabs are as wide as an indent level.
+            tab_width: tab_width.or(indent_size),
+            indent_size,
+            end_of_line: get("end_of_line").and_then(|v| match v {
+                "lf" => Some(LineEnding::Lf),
+                "crlf" => Some(LineEnding::CrLf),
+                "cr" => Some(LineEnding::Cr),
+                _ => None,
+            }),
+            charset: get("charset").and_then(|v| match v {
+                "utf-8" => Some((encoding_rs::UTF_8, false)),
+                "utf-8-bom" => Some((encoding_rs::UTF_8, true)),
+                "latin1" => Some((encoding_rs::WINDOWS_1252, false)),
+                "utf-16le" => Some((encoding_rs::UTF_16LE, true)),
+                "utf-16be" => Some((encoding_rs::UTF_16BE, true)),
+                _ => None,
+            }),
+            trim_trailing_whitespace: get("trim_trailing_whitespace").and_then(parse_bool),
+            insert_final_newline: get("insert_final_newline").and_then(parse_bool),
+            max_line_length: number("max_line_length").map(|n| n as i32),
+        }
+    }
+
+    /// Overrides the detected or default indentation with the configured one.
+    pub fn apply_indent(&self, indent: &mut Indent) {
+        if let Some(tabs) = self.indent_tabs {
+            indent.tabs = tabs;
+        }
+        let width = if indent.tabs {
+            self.tab_width
+        } else {
+            self.indent_size
+        };
+        if let Some(width) = width.filter(|w| *w > 0) {
+            indent.width = width;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        section_matcher("/p", pattern).is_some_and(|re| re.is_match(path))
+    }
+
+    #[test]
+    fn star_stays_within_a_directory() {
+        assert!(matches("*", "/p/Makefile"));
+        assert!(matches("*.py", "/p/a.py"));
+        assert!(matches("*.py", "/p/src/deep/a.py"));
+        assert!(!matches("*.py", "/p/a.pyc"));
+        assert!(matches("src/*.js", "/p/src/a.js"));
+        assert!(!matches("src/*.js", "/p/src/lib/a.js"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("lib/**.js", "/p/lib/a.js"));
+        assert!(matches("lib/**.js", "/p/lib/x/y/a.js"));
+        assert!(!matches("lib/**.js", "/p/other/lib/a.js"));
+        assert!(matches("**/test/*.rs", "/p/a/b/test/t.rs"));
+    }
+
+    #[test]
+    fn slash_anchors_to_the_config_directory() {
+        assert!(matches("/Makefile", "/p/Makefile"));
+        assert!(!matches("/Makefile", "/p/sub/Makefile"));
+        assert!(matches("Makefile", "/p/sub/Makefile"));
+        assert!(!matches("*.py", "/q/a.py"));
+    }
+
+    #[test]
+    fn question_mark_and_classes() {
+        assert!(matches("?.c", "/p/a.c"));
+        assert!(!matches("?.c", "/p/ab.c"));
+        assert!(!matches("a?c", "/p/a/c"));
+        assert!(matches("[abc].txt", "/p/b.txt"));
+        assert!(!matches("[abc].txt", "/p/d.txt"));
+        assert!(matches("[a-c].txt", "/p/c.txt"));
+        assert!(matches("[!abc].txt", "/p/d.txt"));
+        assert!(!matches("[!abc].txt", "/p/a.txt"));
+        assert!(matches("[.txt", "/p/[.txt"));
+    }
+
+    #[test]
+    fn braces_list_alternatives() {
+        assert!(matches("*.{js,py}", "/p/a.js"));
+        assert!(matches("*.{js,py}", "/p/a.py"));
+        assert!(!matches("*.{js,py}", "/p/a.rs"));
+        assert!(matches("{package.json,.travis.yml}", "/p/.travis.yml"));
+        assert!(matches("a{b,{c,d}}e", "/p/ade"));
+        assert!(matches("a{,b}c", "/p/ac"));
+        // A single word in braces is literal.
+        assert!(matches("{single}.txt", "/p/{single}.txt"));
+        assert!(matches("{unclosed", "/p/{unclosed"));
+    }
+
+    #[test]
+    fn braces_with_numeric_ranges() {
+        assert!(matches("file{1..3}.txt", "/p/file1.txt"));
+        assert!(matches("file{1..3}.txt", "/p/file3.txt"));
+        assert!(!matches("file{1..3}.txt", "/p/file4.txt"));
+        assert!(matches("v{-2..2}", "/p/v-1"));
+        assert!(matches("n{3..1}", "/p/n2"));
+        assert!(matches("n{0..5000}", "/p/n4321"));
+    }
+
+    #[test]
+    fn escapes_are_literal() {
+        assert!(matches(r"\*.txt", "/p/*.txt"));
+        assert!(!matches(r"\*.txt", "/p/a.txt"));
+        assert!(matches(r"a\{b,c\}", "/p/a{b,c}"));
+        assert!(matches("a+b(c).txt", "/p/a+b(c).txt"));
+    }
+}
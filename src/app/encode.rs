@@ -0,0 +1,585 @@
+use fltk::{prelude::*, text::TextEditor};
+use std::{cell::RefCell, rc::Rc};
+
+use super::edit::editable_buffer;
+use super::state::AppState;
+use super::status::show_status_message;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+/// Longest quoted-printable line before a soft break, not counting the `=`.
+const QP_LINE: usize = 75;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Codec {
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HtmlEncode,
+    HtmlDecode,
+    JsonEscape,
+    JsonUnescape,
+    HexEncode,
+    HexDecode,
+    QuotedPrintableEncode,
+    QuotedPrintableDecode,
+    JwtPayload,
+}
+
+impl Codec {
+    /// In menu order; `true` ends a group.
+    pub const ALL: [(Codec, bool); 13] = [
+        (Codec::Base64Encode, false),
+        (Codec::Base64Decode, true),
+        (Codec::UrlEncode, false),
+        (Codec::UrlDecode, true),
+        (Codec::HtmlEncode, false),
+        (Codec::HtmlDecode, true),
+        (Codec::JsonEscape, false),
+        (Codec::JsonUnescape, true),
+        (Codec::HexEncode, false),
+        (Codec::HexDecode, true),
+        (Codec::QuotedPrintableEncode, false),
+        (Codec::QuotedPrintableDecode, true),
+        (Codec::JwtPayload, false),
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Codec::Base64Encode => "Base64 Encode",
+            Codec::Base64Decode => "Base64 Decode",
+            Codec::UrlEncode => "URL Encode",
+            Codec::UrlDecode => "URL Decode",
+            Codec::HtmlEncode => "HTML Entities Encode",
+            Codec::HtmlDecode => "HTML Entities Decode",
+            Codec::JsonEscape => "JSON String Escape",
+            Codec::JsonUnescape => "JSON String Unescape",
+            Codec::HexEncode => "Hex Encode",
+            Codec::HexDecode => "Hex Decode",
+            Codec::QuotedPrintableEncode => "Quoted-Printable Encode",
+            Codec::QuotedPrintableDecode => "Quoted-Printable Decode",
+            Codec::JwtPayload => "JWT Payload Decode",
+        }
+    }
+
+    pub fn apply(self, text: &str) -> Result<String, String> {
+        match self {
+            Codec::Base64Encode => Ok(base64_encode(text.as_bytes())),
+            Codec::Base64Decode => utf8(base64_decode(text)?),
+            Codec::UrlEncode => Ok(url_encode(text)),
+            Codec::UrlDecode => utf8(url_decode(text)?),
+            Codec::HtmlEncode => Ok(html_encode(text)),
+            Codec::HtmlDecode => html_decode(text),
+            Codec::JsonEscape => Ok(json_escape(text)),
+            Codec::JsonUnescape => json_unescape(text),
+            Codec::HexEncode => Ok(hex_encode(text.as_bytes())),
+            Codec::HexDecode => utf8(hex_decode(text)?),
+            Codec::QuotedPrintableEncode => Ok(qp_encode(text)),
+            Codec::QuotedPrintableDecode => utf8(qp_decode(text)?),
+            Codec::JwtPayload => jwt_payload(text),
+        }
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|e| {
+        format!(
+            "result isn't UTF-8 text (invalid byte at {})",
+            e.utf8_error().valid_up_to()
+        )
+    })
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (idx, b)| n | (*b as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * idx) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Accepts the standard and URL-safe alphabets, with or without padding, and
+/// ignores whitespace.
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut acc, mut bits, mut count) = (0u32, 0, 0);
+    let mut padded = false;
+    for (idx, c) in text.char_indices() {
+        let value = match c {
+            _ if c.is_whitespace() => continue,
+            '=' => {
+                padded = true;
+                continue;
+            }
+            _ if padded => return Err(format!("unexpected '{}' after padding at {}", c, idx)),
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            _ => return Err(format!("invalid character '{}' at {}", c, idx)),
+        };
+        acc = acc << 6 | value;
+        bits += 6;
+        count += 1;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if count % 4 == 1 {
+        return Err("input is truncated".into());
+    }
+    Ok(out)
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn url_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[(b >> 4) as usize] as char);
+            out.push(HEX[(b & 15) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Decodes `%XX` escapes; `+` is left alone since only form data uses it for spaces.
+fn url_decode(text: &str) -> Result<Vec<u8>, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] != b'%' {
+            out.push(bytes[idx]);
+            idx += 1;
+            continue;
+        }
+        let hi = bytes.get(idx + 1).copied().and_then(hex_digit);
+        let lo = bytes.get(idx + 2).copied().and_then(hex_digit);
+        let (Some(hi), Some(lo)) = (hi, lo) else {
+            return Err(format!("invalid escape at {}", idx));
+        };
+        out.push(hi << 4 | lo);
+        idx += 3;
+    }
+    Ok(out)
+}
+
+fn html_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '\u{a9}',
+        "reg" => '\u{ae}',
+        "trade" => '\u{2122}',
+        "hellip" => '\u{2026}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "euro" => '\u{20ac}',
+        _ => return None,
+    })
+}
+
+/// The character of a `&#...;` reference, given what follows the `#`.
+fn numeric_reference(number: &str) -> Option<char> {
+    let code = match number.strip_prefix(['x', 'X']) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => number.parse(),
+    };
+    code.ok().and_then(char::from_u32)
+}
+
+/// Decodes numeric references and common named entities. Unknown names and
+/// references without digits are kept as they are, as a browser would.
+fn html_decode(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let at = text.len() - rest.len() + amp;
+        rest = &rest[amp..];
+        let Some(end) = rest[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+            .map(|i| i + 1)
+            .filter(|i| rest[*i..].starts_with(';'))
+        else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let name = &rest[1..end];
+        let decoded = match name.strip_prefix('#') {
+            // `&#;` and `&#x;` have no digits, so they aren't references at all.
+            Some("" | "x" | "X") => None,
+            Some(number) => Some(
+                numeric_reference(number)
+                    .ok_or_else(|| format!("invalid character reference &{}; at {}", name, at))?,
+            ),
+            None => named_entity(name),
+        };
+        match decoded {
+            Some(c) => out.push(c),
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Escapes text for use inside a JSON string, without the surrounding quotes.
+fn json_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            _ if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reads the four hex digits of a `\u` escape that starts at `at`.
+fn json_hex4(chars: &[char], at: usize) -> Result<u32, String> {
+    let digits: String = chars.iter().skip(at + 2).take(4).collect();
+    if digits.len() != 4 {
+        return Err(format!("truncated \\u escape at {}", at));
+    }
+    u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid \\u escape at {}", at))
+}
+
+/// Undoes JSON string escapes; surrounding quotes, if selected, are dropped.
+fn json_unescape(text: &str) -> Result<String, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .filter(|_| text.len() >= 2)
+        .unwrap_or(text);
+    let chars: Vec<char> = inner.chars().collect();
+    let mut out = String::with_capacity(inner.len());
+    let mut idx = 0;
+    while idx < chars.len() {
+        if chars[idx] != '\\' {
+            out.push(chars[idx]);
+            idx += 1;
+            continue;
+        }
+        let Some(&escape) = chars.get(idx + 1) else {
+            return Err(format!("unfinished escape at {}", idx));
+        };
+        let c = match escape {
+            '"' | '\\' | '/' => escape,
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'u' => {
+                let mut code = json_hex4(&chars, idx)?;
+                if (0xd800..0xdc00).contains(&code)
+                    && chars.get(idx + 6) == Some(&'\\')
+                    && chars.get(idx + 7) == Some(&'u')
+                {
+                    let low = json_hex4(&chars, idx + 6)?;
+                    if (0xdc00..0xe000).contains(&low) {
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        idx += 6;
+                    }
+                }
+                idx += 4;
+                char::from_u32(code).ok_or_else(|| format!("unpaired surrogate at {}", idx - 4))?
+            }
+            _ => return Err(format!("invalid escape \\{} at {}", escape, idx)),
+        };
+        out.push(c);
+        idx += 2;
+    }
+    Ok(out)
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Ignores whitespace and `0x` prefixes, so dumps like `0x48 0x69` decode too.
+fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut digits = vec![];
+    for word in text.split_whitespace() {
+        let word = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        for c in word.chars() {
+            let d = c
+                .to_digit(16)
+                .ok_or_else(|| format!("invalid hex digit '{}'", c))?;
+            digits.push(d as u8);
+        }
+    }
+    if digits.len() % 2 != 0 {
+        return Err("odd number of hex digits".into());
+    }
+    Ok(digits.chunks(2).map(|p| p[0] << 4 | p[1]).collect())
+}
+
+/// Encodes each line per RFC 2045, breaking long lines with soft line breaks.
+fn qp_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (idx, line) in text.split('\n').enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        let bytes = line.as_bytes();
+        let mut width = 0;
+        for (at, &b) in bytes.iter().enumerate() {
+            let blank = b == b' ' || b == b'\t';
+            let printable = (b'!'..=b'~').contains(&b) && b != b'=';
+            // Whitespace at the end of a line would be lost in transit.
+            let piece = if printable || (blank && at + 1 < bytes.len()) {
+                (b as char).to_string()
+            } else {
+                format!(
+                    "={}{}",
+                    HEX[(b >> 4) as usize] as char,
+                    HEX[(b & 15) as usize] as char
+                )
+            };
+            if width + piece.len() > QP_LINE {
+                out.push_str("=\n");
+                width = 0;
+            }
+            width += piece.len();
+            out.push_str(&piece);
+        }
+    }
+    out
+}
+
+fn qp_decode(text: &str) -> Result<Vec<u8>, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] != b'=' {
+            out.push(bytes[idx]);
+            idx += 1;
+            continue;
+        }
+        // A soft line break, possibly after trailing whitespace.
+        let rest = &bytes[idx + 1..];
+        let blank = rest
+            .iter()
+            .take_while(|b| matches!(b, b' ' | b'\t'))
+            .count();
+        let skip = match &rest[blank..] {
+            [] => Some(0),
+            [b'\n', ..] => Some(1),
+            [b'\r', b'\n', ..] => Some(2),
+            _ => None,
+        };
+        if let Some(skip) = skip {
+            idx += 1 + blank + skip;
+            continue;
+        }
+        let hi = bytes.get(idx + 1).copied().and_then(hex_digit);
+        let lo = bytes.get(idx + 2).copied().and_then(hex_digit);
+        let (Some(hi), Some(lo)) = (hi, lo) else {
+            return Err(format!("invalid escape at {}", idx));
+        };
+        out.push(hi << 4 | lo);
+        idx += 3;
+    }
+    Ok(out)
+}
+
+/// The decoded payload, the middle part of `header.payload.signature`.
+fn jwt_payload(text: &str) -> Result<String, String> {
+    let token = text.trim().trim_start_matches("Bearer ").trim();
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err("expected header.payload.signature".into());
+    }
+    utf8(base64_decode(parts[1]).map_err(|e| format!("payload: {}", e))?)
+}
+
+/// Encodes or decodes the selection as one undo step. Errors go to the status
+/// bar and leave the text alone.
+pub fn convert_selection(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>, codec: Codec) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let Some((start, end)) = buf.selection_position() else {
+        show_status_message(
+            state,
+            editor,
+            &format!("{}: select some text first", codec.label()),
+        );
+        return;
+    };
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let new_text = match codec.apply(&text) {
+        Ok(new_text) => new_text,
+        Err(err) => {
+            show_status_message(state, editor, &format!("{} failed: {}", codec.label(), err));
+            return;
+        }
+    };
+    if new_text == text {
+        return;
+    }
+
+    buf.replace(start, end, &new_text);
+    let new_end = start + new_text.len() as i32;
+    buf.select(start, new_end);
+    editor.set_insert_position(new_end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(codec: Codec, text: &str) -> String {
+        codec.apply(text).unwrap()
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(apply(Codec::Base64Encode, "f"), "Zg==");
+        assert_eq!(apply(Codec::Base64Encode, "fo"), "Zm8=");
+        assert_eq!(apply(Codec::Base64Encode, "foo"), "Zm9v");
+        assert_eq!(apply(Codec::Base64Decode, "Zg=="), "f");
+        assert_eq!(apply(Codec::Base64Decode, "Zg"), "f");
+        assert_eq!(apply(Codec::Base64Decode, "Zm8\n="), "fo");
+        assert_eq!(apply(Codec::Base64Decode, "PDw_Pz4-"), "<<??>>");
+    }
+
+    #[test]
+    fn base64_rejects_truncated_input() {
+        assert_eq!(
+            Codec::Base64Decode.apply("Zm9vY"),
+            Err("input is truncated".into())
+        );
+        assert!(Codec::Base64Decode.apply("Zg==Zg").is_err());
+        assert!(Codec::Base64Decode.apply("Z*g=").is_err());
+    }
+
+    #[test]
+    fn html_references() {
+        assert_eq!(
+            apply(Codec::HtmlDecode, "&lt;a&gt; &#65;&#x42; &amp;c"),
+            "<a> AB &c"
+        );
+        assert_eq!(apply(Codec::HtmlDecode, "&bogus; & x"), "&bogus; & x");
+        assert!(Codec::HtmlDecode.apply("&#xD800;").is_err());
+    }
+
+    #[test]
+    fn html_references_without_digits_are_kept() {
+        assert_eq!(apply(Codec::HtmlDecode, "a&#;b"), "a&#;b");
+        assert_eq!(apply(Codec::HtmlDecode, "a&#x;b&#X;"), "a&#x;b&#X;");
+    }
+
+    #[test]
+    fn json_surrogate_pairs() {
+        assert_eq!(
+            apply(Codec::JsonUnescape, "\"\\ud83d\\ude00\""),
+            "\u{1f600}"
+        );
+        assert_eq!(apply(Codec::JsonUnescape, "a\\u00e9\\n"), "a\u{e9}\n");
+        assert!(Codec::JsonUnescape.apply("\\ud83d").is_err());
+        assert!(Codec::JsonUnescape.apply("\\ude00x").is_err());
+        assert!(Codec::JsonUnescape.apply("\\u12").is_err());
+    }
+
+    #[test]
+    fn json_escape_round_trips() {
+        let text = "say \"hi\"\t\\ \u{1}";
+        let escaped = apply(Codec::JsonEscape, text);
+        assert_eq!(apply(Codec::JsonUnescape, &escaped), text);
+    }
+
+    #[test]
+    fn quoted_printable_soft_breaks() {
+        let long = "a".repeat(100);
+        let encoded = apply(Codec::QuotedPrintableEncode, &long);
+        assert!(encoded.lines().all(|line| line.len() <= QP_LINE + 1));
+        assert!(encoded.contains("=\n"));
+        assert_eq!(apply(Codec::QuotedPrintableDecode, &encoded), long);
+        assert_eq!(apply(Codec::QuotedPrintableDecode, "ab= \t\r\ncd="), "abcd");
+    }
+
+    #[test]
+    fn quoted_printable_trailing_whitespace() {
+        assert_eq!(
+            apply(Codec::QuotedPrintableEncode, "a b \nc\t"),
+            "a b=20\nc=09"
+        );
+        assert_eq!(
+            apply(Codec::QuotedPrintableEncode, "1=2 \u{e9}"),
+            "1=3D2 =C3=A9"
+        );
+        assert_eq!(
+            apply(Codec::QuotedPrintableDecode, "a b=20\nc=09"),
+            "a b \nc\t"
+        );
+        assert!(Codec::QuotedPrintableDecode.apply("=G1").is_err());
+    }
+
+    #[test]
+    fn jwt_with_bearer_prefix() {
+        let token = "Bearer eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxMjM0In0.c2ln";
+        assert_eq!(apply(Codec::JwtPayload, token), "{\"sub\":\"1234\"}");
+        assert!(Codec::JwtPayload.apply("Bearer abc").is_err());
+    }
+}
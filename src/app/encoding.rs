@@ -1,13 +1,110 @@
 use encoding_rs::Encoding;
 use std::fs;
 
-pub fn load_as_utf8(path: &str) -> Option<String> {
-    let bytes = fs::read(path).ok()?;
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
 
-    let enc = match Encoding::for_bom(&bytes) {
-        Some((encoding, _)) => encoding,
-        None => encoding_rs::UTF_8,
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+pub fn detect_encoding(bytes: &[u8]) -> (&'static Encoding, bool) {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return (encoding, true);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        (encoding_rs::UTF_8, false)
+    } else {
+        (encoding_rs::WINDOWS_1252, false)
+    }
+}
+
+/// Returns the most common line ending in `text`, defaulting to LF.
+pub fn detect_line_ending(text: &str) -> LineEnding {
+    let bytes = text.as_bytes();
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                crlf += 1;
+                i += 1;
+            }
+            b'\r' => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if crlf > lf && crlf >= cr {
+        LineEnding::CrLf
+    } else if cr > lf && cr > crlf {
+        LineEnding::Cr
+    } else {
+        LineEnding::Lf
+    }
+}
+
+pub fn decode_with_encoding(bytes: &[u8]) -> DecodedText {
+    let (encoding, bom) = detect_encoding(bytes);
+    let text = if bom {
+        encoding.decode_with_bom_removal(bytes).0.into_owned()
+    } else {
+        encoding.decode_without_bom_handling(bytes).0.into_owned()
     };
 
-    Some(enc.decode(&bytes).0.into_owned())
+    DecodedText {
+        text,
+        encoding,
+        bom,
+    }
+}
+
+pub fn decode_bytes(bytes: &[u8]) -> String {
+    decode_with_encoding(bytes).text
+}
+
+pub fn encode_text(text: &str, encoding: &'static Encoding, bom: bool) -> Vec<u8> {
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        let le = encoding == encoding_rs::UTF_16LE;
+        let mut out = Vec::with_capacity(text.len() * 2 + 2);
+        let units = bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16());
+        for unit in units {
+            if le {
+                out.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                out.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        return out;
+    }
+
+    let mut out = Vec::with_capacity(text.len() + 3);
+    if bom && encoding == encoding_rs::UTF_8 {
+        out.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+    }
+    out.extend_from_slice(&encoding.encode(text).0);
+    out
+}
+
+pub fn load_as_utf8(path: &str) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(decode_bytes(&bytes))
 }
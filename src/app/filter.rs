@@ -0,0 +1,435 @@
+use fltk::{
+    app,
+    enums::{Event, EventState, Key},
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use regex::Regex;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use super::highlight::SharedHighlighter;
+use super::languages::TokenKind;
+use super::search::{SearchOptions, build_regex};
+use super::state::AppState;
+use super::status::UpdateStatus;
+
+const REBUILD_DELAY: f64 = 0.15;
+
+#[derive(Clone)]
+pub struct LineFilter {
+    pub pattern: String,
+    pub options: SearchOptions,
+    pub hide_matching: bool,
+}
+
+/// The read-only buffer shown in place of the document while filters apply.
+pub struct FilterView {
+    pub buf: TextBuffer,
+    pub stylebuf: TextBuffer,
+    /// Source line for each view line; `None` marks a collapsed-lines marker.
+    pub line_map: Vec<Option<usize>>,
+    pub line_starts: Vec<i32>,
+    pub total_lines: usize,
+}
+
+#[derive(Default)]
+pub struct FilterState {
+    pub filters: Vec<LineFilter>,
+    pub collapsed: bool,
+    pub view: Option<FilterView>,
+}
+
+#[derive(Clone)]
+pub struct FilterUi {
+    pub state: Rc<RefCell<AppState>>,
+    pub editor: TextEditor,
+    pub buf: Rc<RefCell<TextBuffer>>,
+    pub highlighter: SharedHighlighter,
+    pub update_status: UpdateStatus,
+}
+
+impl FilterView {
+    pub fn visible_lines(&self) -> usize {
+        self.line_map.iter().filter(|l| l.is_some()).count()
+    }
+
+    pub fn source_line(&self, view_line: usize) -> Option<usize> {
+        self.line_map.get(view_line).copied().flatten()
+    }
+
+    fn view_line(&self, source_line: usize) -> Option<usize> {
+        self.line_map.iter().position(|l| *l == Some(source_line))
+    }
+
+    /// Maps a position in the document to the view, if its line is shown.
+    pub fn to_view_pos(&self, source: &TextBuffer, pos: i32) -> Option<i32> {
+        let line = source.count_lines(0, pos) as usize;
+        let col = pos - source.line_start(pos);
+        let start = self.line_starts[self.view_line(line)?];
+        Some((start + col).min(self.buf.line_end(start)))
+    }
+}
+
+fn compile(filters: &[LineFilter]) -> Vec<(Regex, bool)> {
+    filters
+        .iter()
+        .filter_map(|f| {
+            build_regex(&f.pattern, &f.options)
+                .ok()
+                .map(|re| (re, f.hide_matching))
+        })
+        .collect()
+}
+
+fn collapsed_marker(count: usize) -> String {
+    if count == 1 {
+        "\u{22ef} 1 hidden line".to_string()
+    } else {
+        format!("\u{22ef} {} hidden lines", count)
+    }
+}
+
+/// A line is shown when it matches every "show" filter and no "hide" filter.
+/// Shown lines keep their styles from `styles`, the document's style text.
+pub fn build_view(text: &str, styles: &str, filters: &[LineFilter], collapsed: bool) -> FilterView {
+    let compiled = compile(filters);
+    let mut out = String::new();
+    let mut out_styles = String::new();
+    let mut line_map = vec![];
+    let mut line_starts = vec![];
+    let mut hidden = 0;
+    let mut total_lines = 0;
+
+    let mut push_line = |out: &mut String, line: &str, style: &str, source: Option<usize>| {
+        if !line_map.is_empty() {
+            out.push('\n');
+            out_styles.push(TokenKind::Plain.style_char() as char);
+        }
+        line_starts.push(out.len() as i32);
+        out.push_str(line);
+        out_styles.push_str(style);
+        line_map.push(source);
+    };
+    let marker_style = |marker: &str| {
+        let c = TokenKind::Comment.style_char() as char;
+        std::iter::repeat_n(c, marker.len()).collect::<String>()
+    };
+
+    let mut line_start = 0;
+    for (idx, line) in text.split('\n').enumerate() {
+        let style = styles
+            .get(line_start..line_start + line.len())
+            .unwrap_or_default();
+        line_start += line.len() + 1;
+        total_lines += 1;
+        let content = line.trim_end_matches('\r');
+        let visible = compiled
+            .iter()
+            .all(|(re, hide_matching)| re.is_match(content) != *hide_matching);
+
+        if visible {
+            if collapsed && hidden > 0 {
+                let marker = collapsed_marker(hidden);
+                push_line(&mut out, &marker, &marker_style(&marker), None);
+            }
+            hidden = 0;
+            push_line(&mut out, line, style, Some(idx));
+        } else {
+            hidden += 1;
+        }
+    }
+    if collapsed && hidden > 0 {
+        let marker = collapsed_marker(hidden);
+        push_line(&mut out, &marker, &marker_style(&marker), None);
+    }
+
+    let mut buf = TextBuffer::default();
+    buf.set_text(&out);
+    let mut stylebuf = TextBuffer::default();
+    stylebuf.set_text(&out_styles);
+
+    FilterView {
+        buf,
+        stylebuf,
+        line_map,
+        line_starts,
+        total_lines,
+    }
+}
+
+/// Rebuilds the filtered view, or puts the document back once no filters remain.
+pub fn apply_filters(ui: &FilterUi) {
+    let mut ed = ui.editor.clone();
+    let mut s = ui.state.borrow_mut();
+
+    if s.document.filter.filters.is_empty() {
+        if s.document.filter.view.take().is_some() {
+            let h = ui.highlighter.borrow();
+            ed.set_buffer(Some(ui.buf.borrow().clone()));
+            ed.set_highlight_data_ext(h.stylebuf(), h.style_table(&s.theme));
+            ed.set_insert_position(0);
+            ed.redraw();
+        }
+        return;
+    }
+
+    let text = ui.buf.borrow().text();
+    let h = ui.highlighter.borrow();
+    let mut view = build_view(
+        &text,
+        &h.stylebuf().text(),
+        &s.document.filter.filters,
+        s.document.filter.collapsed,
+    );
+    view.buf.set_tab_distance(ui.buf.borrow().tab_distance());
+    ed.set_buffer(Some(view.buf.clone()));
+    ed.set_highlight_data_ext(view.stylebuf.clone(), h.style_table(&s.theme));
+    ed.set_insert_position(0);
+    ed.redraw();
+    s.document.filter.view = Some(view);
+}
+
+/// Keeps the view in step when the document changes underneath it,
+/// e.g. after opening another file or a Replace in Files.
+fn attach_filter_refresh(ui: &FilterUi) {
+    let pending = Rc::new(Cell::new(false));
+    let ui_ref = ui.clone();
+
+    ui.buf
+        .borrow_mut()
+        .add_modify_callback(move |_, inserted, deleted, _, _| {
+            if (inserted == 0 && deleted == 0) || pending.get() {
+                return;
+            }
+
+            pending.set(true);
+            let pending = Rc::clone(&pending);
+            let ui = ui_ref.clone();
+
+            app::add_timeout3(REBUILD_DELAY, move |_| {
+                pending.set(false);
+                if ui.state.borrow().document.filter.view.is_none() {
+                    return;
+                }
+                apply_filters(&ui);
+                (ui.update_status)();
+            });
+        });
+}
+
+pub fn create_filter(
+    state: &Rc<RefCell<AppState>>,
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    highlighter: &SharedHighlighter,
+    update_status: &UpdateStatus,
+) -> FilterUi {
+    let ui = FilterUi {
+        state: Rc::clone(state),
+        editor: editor.clone(),
+        buf: Rc::clone(buf),
+        highlighter: Rc::clone(highlighter),
+        update_status: Rc::clone(update_status),
+    };
+    attach_filter_refresh(&ui);
+    ui
+}
+
+/// Selects a document range in whichever buffer the editor shows.
+/// Returns false when the range sits on a line the filters hide.
+pub fn select_source_range(
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    state: &Rc<RefCell<AppState>>,
+    start: i32,
+    end: i32,
+) -> bool {
+    let mut ed = editor.clone();
+    let s = state.borrow();
+
+    let (mut target, start, end) = match &s.document.filter.view {
+        Some(view) => {
+            let source = buf.borrow();
+            let Some(view_start) = view.to_view_pos(&source, start) else {
+                return false;
+            };
+            let view_end = view
+                .to_view_pos(&source, end)
+                .unwrap_or_else(|| view.buf.line_end(view_start));
+            (view.buf.clone(), view_start, view_end)
+        }
+        None => (buf.borrow().clone(), start, end),
+    };
+
+    ed.set_insert_position(start);
+    ed.show_insert_position();
+    target.select(start, end);
+    true
+}
+
+fn is_editing_key(ev: Event) -> bool {
+    if ev != Event::KeyDown {
+        return ev == Event::Paste;
+    }
+
+    let key = app::event_key();
+    let st = app::event_state();
+    let command = st.contains(EventState::Ctrl) || st.contains(EventState::Meta);
+    let edit_key = matches!(
+        key,
+        Key::BackSpace | Key::Delete | Key::Enter | Key::KPEnter | Key::Tab
+    ) || (key == Key::Insert && st.contains(EventState::Shift));
+
+    if command {
+        edit_key
+            || ['v', 'x', 'y', 'z']
+                .iter()
+                .any(|c| key == Key::from_char(*c))
+    } else {
+        edit_key || (key != Key::Escape && !app::event_text().is_empty())
+    }
+}
+
+fn is_copy_key(ev: Event) -> bool {
+    let st = app::event_state();
+    let command = st.contains(EventState::Ctrl) || st.contains(EventState::Meta);
+    let key = app::event_key();
+    ev == Event::KeyDown && command && (key == Key::from_char('c') || key == Key::Insert)
+}
+
+/// Copies the selected view lines, leaving out collapsed-lines markers.
+fn copy_visible_selection(view: &FilterView) -> bool {
+    let Some((start, end)) = view.buf.selection_position() else {
+        return false;
+    };
+
+    let first = view.buf.count_lines(0, start) as usize;
+    let last = view.buf.count_lines(0, end) as usize;
+    let mut parts = vec![];
+    for line in first..=last.min(view.line_starts.len() - 1) {
+        if view.source_line(line).is_none() {
+            continue;
+        }
+        let line_start = view.line_starts[line];
+        let from = start.max(line_start);
+        let to = end.min(view.buf.line_end(line_start));
+        if from <= to {
+            parts.push(view.buf.text_range(from, to).unwrap_or_default());
+        }
+    }
+
+    app::copy(&parts.join("\n"));
+    true
+}
+
+/// Keeps the filtered view read-only; returns true when the event was consumed.
+pub fn handle_view_event(state: &Rc<RefCell<AppState>>, ev: Event) -> bool {
+    let s = state.borrow();
+    let Some(view) = &s.document.filter.view else {
+        return false;
+    };
+
+    if is_copy_key(ev) {
+        return copy_visible_selection(view);
+    }
+    is_editing_key(ev)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(pattern: &str) -> LineFilter {
+        LineFilter {
+            pattern: pattern.into(),
+            options: SearchOptions::default(),
+            hide_matching: false,
+        }
+    }
+
+    fn hide(pattern: &str) -> LineFilter {
+        LineFilter {
+            hide_matching: true,
+            ..show(pattern)
+        }
+    }
+
+    fn view(text: &str, filters: &[LineFilter], collapsed: bool) -> FilterView {
+        build_view(text, &"A".repeat(text.len()), filters, collapsed)
+    }
+
+    #[test]
+    fn stacked_filters_must_all_agree() {
+        let text = "error: disk\nwarn: cpu\nERROR: net (known)\nerror: fan\n";
+        let v = view(text, &[show("error"), hide("known")], false);
+        assert_eq!(v.buf.text(), "error: disk\nerror: fan");
+        assert_eq!(v.line_map, [Some(0), Some(3)]);
+        assert_eq!(v.line_starts, [0, 12]);
+        assert_eq!(v.visible_lines(), 2);
+        assert_eq!(v.total_lines, 5);
+    }
+
+    #[test]
+    fn collapsed_view_marks_hidden_runs() {
+        let v = view("a1\nx\nx\na2\nx", &[show("a")], true);
+        assert_eq!(
+            v.buf.text(),
+            "a1\n\u{22ef} 2 hidden lines\na2\n\u{22ef} 1 hidden line"
+        );
+        assert_eq!(v.line_map, [Some(0), None, Some(3), None]);
+        assert_eq!(v.visible_lines(), 2);
+        assert_eq!(v.source_line(1), None);
+        assert_eq!(v.source_line(2), Some(3));
+        assert_eq!(v.source_line(9), None);
+    }
+
+    #[test]
+    fn shown_lines_keep_their_styles() {
+        let v = build_view("ab\ncd\nef", "BC\nDE\nFG", &[hide("cd")], false);
+        assert_eq!(v.buf.text(), "ab\nef");
+        assert_eq!(v.stylebuf.text(), "BCAFG");
+    }
+
+    #[test]
+    fn line_endings_are_ignored_when_matching() {
+        let filter = LineFilter {
+            options: SearchOptions {
+                case_sensitive: true,
+                regex: true,
+            },
+            ..show("b$")
+        };
+        let v = view("ab\r\ncd\r\n", &[filter], false);
+        assert_eq!(v.line_map, [Some(0)]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_skipped() {
+        let filter = LineFilter {
+            options: SearchOptions {
+                case_sensitive: false,
+                regex: true,
+            },
+            ..show("(")
+        };
+        let v = view("a\nb", &[filter], false);
+        assert_eq!(v.line_map, [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn document_positions_map_into_the_view() {
+        let text = "one\ntwo\nthree\nfour";
+        let mut source = TextBuffer::default();
+        source.set_text(text);
+        let v = view(text, &[hide("t")], false);
+        assert_eq!(v.buf.text(), "one\nfour");
+        assert_eq!(v.to_view_pos(&source, 0), Some(0));
+        assert_eq!(v.to_view_pos(&source, 2), Some(2));
+        assert_eq!(v.to_view_pos(&source, 16), Some(6));
+        // Hidden lines have no place in the view.
+        assert_eq!(v.to_view_pos(&source, 5), None);
+    }
+}
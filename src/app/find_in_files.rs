@@ -0,0 +1,559 @@
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    button::{Button, CheckButton, ReturnButton},
+    enums::{Align, Color, Event, Key},
+    frame::Frame,
+    group::Group,
+    input::Input,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+    window::Window,
+};
+use ignore::{WalkBuilder, overrides::OverrideBuilder};
+use regex::Regex;
+use rfd::FileDialog;
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+    },
+    thread,
+};
+
+use super::dock::{Relayout, SharedDock, add_dock_page, show_dock_page};
+use super::encoding::decode_bytes;
+use super::filter::select_source_range;
+use super::history::{HistoryKind, attach_history_keys};
+use super::menu::OpenPath;
+use super::replace_in_files::show_replace_preview;
+use super::results::context_text;
+use super::search::{SearchOptions, build_regex};
+use super::state::AppState;
+use super::status::UpdateStatus;
+
+const POLL_INTERVAL: f64 = 0.05;
+const MAX_MESSAGES_PER_POLL: usize = 2000;
+const BINARY_SNIFF_LEN: usize = 8000;
+
+#[derive(Clone)]
+pub struct FileQuery {
+    pub root: PathBuf,
+    pub pattern: String,
+    pub options: SearchOptions,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct FileHit {
+    pub path: PathBuf,
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+enum WorkerMessage {
+    Hit(FileHit),
+    FileScanned,
+    Finished,
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct FindInFilesState {
+    pub query: Option<FileQuery>,
+    pub hits: Vec<FileHit>,
+    pub files_scanned: usize,
+    pub running: bool,
+    generation: u64,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+pub struct FindInFilesDialog {
+    pub win: Window,
+    pub pattern: Input,
+    pub root: Input,
+    pub include: Input,
+    pub exclude: Input,
+    pub case_sensitive: CheckButton,
+    pub regex: CheckButton,
+    pub status: Frame,
+}
+
+pub type SharedFindInFiles = Rc<RefCell<FindInFilesState>>;
+pub type FilesPanel = Rc<RefCell<HoldBrowser>>;
+
+#[derive(Clone)]
+pub struct FindInFilesUi {
+    pub state: SharedFindInFiles,
+    pub panel: FilesPanel,
+    pub dialog: Rc<RefCell<Option<FindInFilesDialog>>>,
+    pub dock: SharedDock,
+    pub relayout: Relayout,
+    pub app_state: Rc<RefCell<AppState>>,
+    pub buf: Rc<RefCell<TextBuffer>>,
+}
+
+/// Splits a glob list such as `*.rs, *.toml; src/**` into its patterns.
+pub fn split_globs(text: &str) -> Vec<String> {
+    text.split([',', ';'])
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn build_walker(query: &FileQuery) -> Result<ignore::Walk, String> {
+    let mut overrides = OverrideBuilder::new(&query.root);
+    for glob in &query.include {
+        overrides.add(glob).map_err(|e| e.to_string())?;
+    }
+    for glob in &query.exclude {
+        overrides
+            .add(&format!("!{}", glob))
+            .map_err(|e| e.to_string())?;
+    }
+    let overrides = overrides.build().map_err(|e| e.to_string())?;
+
+    Ok(WalkBuilder::new(&query.root)
+        .standard_filters(true)
+        .require_git(false)
+        .overrides(overrides)
+        .build())
+}
+
+/// Reads a text file, returning `None` for unreadable or binary files.
+pub fn read_text_file(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let sniff = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    let utf16 = sniff.starts_with(&[0xFF, 0xFE]) || sniff.starts_with(&[0xFE, 0xFF]);
+    if !utf16 && sniff.contains(&0) {
+        return None;
+    }
+    Some(decode_bytes(&bytes))
+}
+
+pub fn find_in_text(path: &Path, text: &str, re: &Regex) -> Vec<FileHit> {
+    let mut hits = vec![];
+    let mut line = 1;
+    let mut last = 0;
+
+    for m in re.find_iter(text).filter(|m| !m.is_empty()) {
+        line += text[last..m.start()].matches('\n').count();
+        last = m.start();
+
+        let line_start = text[..m.start()].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = text[m.start()..]
+            .find('\n')
+            .map(|i| m.start() + i)
+            .unwrap_or(text.len());
+
+        hits.push(FileHit {
+            path: path.to_path_buf(),
+            line,
+            start: m.start(),
+            end: m.end(),
+            text: text[line_start..line_end].to_string(),
+        });
+    }
+    hits
+}
+
+fn run_worker(query: FileQuery, cancel: Arc<AtomicBool>, tx: Sender<WorkerMessage>) {
+    let re = match build_regex(&query.pattern, &query.options) {
+        Ok(re) => re,
+        Err(e) => {
+            let _ = tx.send(WorkerMessage::Failed(e.to_string()));
+            return;
+        }
+    };
+
+    let walker = match build_walker(&query) {
+        Ok(walker) => walker,
+        Err(e) => {
+            let _ = tx.send(WorkerMessage::Failed(e));
+            return;
+        }
+    };
+
+    for entry in walker.flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+
+        if let Some(text) = read_text_file(entry.path()) {
+            for hit in find_in_text(entry.path(), &text, &re) {
+                if tx.send(WorkerMessage::Hit(hit)).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = tx.send(WorkerMessage::FileScanned);
+    }
+
+    let _ = tx.send(WorkerMessage::Finished);
+}
+
+fn hit_label(root: &Path, hit: &FileHit) -> String {
+    let shown = hit.path.strip_prefix(root).unwrap_or(&hit.path);
+    format!(
+        "{}:{}\t{}",
+        shown.to_string_lossy(),
+        hit.line,
+        context_text(&hit.text)
+    )
+}
+
+pub fn set_status(ui: &FindInFilesUi, label: &str) {
+    if let Some(dialog) = ui.dialog.borrow_mut().as_mut() {
+        dialog.status.set_label(label);
+    }
+}
+
+fn summary(state: &FindInFilesState) -> String {
+    format!(
+        "{} matches in {} files scanned",
+        state.hits.len(),
+        state.files_scanned
+    )
+}
+
+fn poll_worker(ui: FindInFilesUi, rx: Receiver<WorkerMessage>, generation: u64) {
+    app::add_timeout3(POLL_INTERVAL, move |handle| {
+        if ui.state.borrow().generation != generation {
+            return;
+        }
+
+        let root = match &ui.state.borrow().query {
+            Some(query) => query.root.clone(),
+            None => return,
+        };
+
+        for _ in 0..MAX_MESSAGES_PER_POLL {
+            match rx.try_recv() {
+                Ok(WorkerMessage::Hit(hit)) => {
+                    ui.panel.borrow_mut().add(&hit_label(&root, &hit));
+                    ui.state.borrow_mut().hits.push(hit);
+                }
+                Ok(WorkerMessage::FileScanned) => ui.state.borrow_mut().files_scanned += 1,
+                Ok(WorkerMessage::Finished) | Err(TryRecvError::Disconnected) => {
+                    ui.state.borrow_mut().running = false;
+                    set_status(&ui, &summary(&ui.state.borrow()));
+                    return;
+                }
+                Ok(WorkerMessage::Failed(e)) => {
+                    ui.state.borrow_mut().running = false;
+                    set_status(&ui, &e);
+                    return;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        set_status(
+            &ui,
+            &format!("Searching... {}", summary(&ui.state.borrow())),
+        );
+        app::repeat_timeout3(POLL_INTERVAL, handle);
+    });
+}
+
+pub fn stop_find_in_files(ui: &FindInFilesUi) {
+    let mut st = ui.state.borrow_mut();
+    if let Some(cancel) = st.cancel.take() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+    if st.running {
+        st.running = false;
+        st.generation += 1;
+        drop(st);
+        set_status(ui, &format!("Stopped: {}", summary(&ui.state.borrow())));
+    }
+}
+
+pub fn clear_find_in_files(ui: &FindInFilesUi) {
+    stop_find_in_files(ui);
+    let mut st = ui.state.borrow_mut();
+    st.hits.clear();
+    st.files_scanned = 0;
+    drop(st);
+    ui.panel.borrow_mut().clear();
+}
+
+pub fn start_find_in_files(ui: &FindInFilesUi, query: FileQuery) {
+    stop_find_in_files(ui);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let generation = {
+        let mut st = ui.state.borrow_mut();
+        st.generation += 1;
+        st.hits.clear();
+        st.files_scanned = 0;
+        st.running = true;
+        st.query = Some(query.clone());
+        st.cancel = Some(Arc::clone(&cancel));
+        st.generation
+    };
+
+    ui.panel.borrow_mut().clear();
+    let browser = ui.panel.borrow().clone();
+    show_dock_page(&ui.dock, &browser, &ui.relayout);
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || run_worker(query, cancel, tx));
+    poll_worker(ui.clone(), rx, generation);
+}
+
+fn query_from_dialog(dialog: &FindInFilesDialog) -> Option<FileQuery> {
+    let pattern = dialog.pattern.value();
+    let root = dialog.root.value();
+    if pattern.is_empty() || root.trim().is_empty() {
+        return None;
+    }
+
+    Some(FileQuery {
+        root: PathBuf::from(root.trim()),
+        pattern,
+        options: SearchOptions {
+            case_sensitive: dialog.case_sensitive.value(),
+            regex: dialog.regex.value(),
+        },
+        include: split_globs(&dialog.include.value()),
+        exclude: split_globs(&dialog.exclude.value()),
+    })
+}
+
+fn build_dialog(ui: &FindInFilesUi) -> FindInFilesDialog {
+    Group::set_current(None::<&Group>);
+
+    let mut win = Window::new(0, 0, 480, 265, "Find in Files");
+    let mut pattern = Input::new(90, 10, 380, 25, "Find:");
+    let mut replacement = Input::new(90, 40, 380, 25, "Replace with:");
+    let root = Input::new(90, 70, 295, 25, "Directory:");
+    let mut browse = Button::new(390, 70, 80, 25, "Browse...");
+    let mut include = Input::new(90, 100, 380, 25, "Include:");
+    include.set_tooltip("Globs to search, e.g. *.rs, *.toml");
+    let mut exclude = Input::new(90, 130, 380, 25, "Exclude:");
+    exclude.set_tooltip("Globs to skip, e.g. target/**, *.min.js");
+    let case_sensitive = CheckButton::new(90, 160, 120, 25, "Match case");
+    let regex = CheckButton::new(220, 160, 160, 25, "Regular expression");
+    pattern.set_tooltip("Up/Down recalls earlier searches");
+    replacement.set_tooltip("Up/Down recalls earlier replacements");
+    let mut status = Frame::new(10, 190, 460, 25, "");
+    status.set_align(Align::Left | Align::Inside);
+    status.set_label_color(Color::from_rgb(100, 100, 100));
+    let mut replace = Button::new(135, 230, 80, 25, "Replace...");
+    let mut find = ReturnButton::new(220, 230, 80, 25, "Find");
+    let mut stop = Button::new(305, 230, 80, 25, "Stop");
+    let mut close = Button::new(390, 230, 80, 25, "Close");
+    win.end();
+
+    {
+        let mut case_sensitive = case_sensitive.clone();
+        let mut regex = regex.clone();
+        attach_history_keys(
+            &mut pattern,
+            &ui.app_state,
+            HistoryKind::Search,
+            move |entry| {
+                case_sensitive.set_value(entry.options.case_sensitive);
+                regex.set_value(entry.options.regex);
+            },
+        );
+        attach_history_keys(
+            &mut replacement,
+            &ui.app_state,
+            HistoryKind::Replace,
+            |_| {},
+        );
+    }
+
+    {
+        let mut root = root.clone();
+        browse.set_callback(move |_| {
+            let mut dialog = FileDialog::new();
+            if !root.value().is_empty() {
+                dialog = dialog.set_directory(root.value());
+            }
+            if let Some(dir) = dialog.pick_folder() {
+                root.set_value(&dir.to_string_lossy());
+            }
+        });
+    }
+
+    {
+        let ui = ui.clone();
+        find.set_callback(move |_| {
+            let query = ui.dialog.borrow().as_ref().and_then(query_from_dialog);
+            match query {
+                Some(query) => {
+                    ui.app_state.borrow_mut().search.history.record(
+                        HistoryKind::Search,
+                        &query.pattern,
+                        query.options,
+                    );
+                    start_find_in_files(&ui, query);
+                }
+                None => set_status(&ui, "Enter a pattern and a directory"),
+            }
+        });
+    }
+
+    {
+        let ui = ui.clone();
+        stop.set_callback(move |_| stop_find_in_files(&ui));
+    }
+
+    {
+        let ui = ui.clone();
+        replace.set_callback(move |_| {
+            let value = replacement.value();
+            let options = ui
+                .state
+                .borrow()
+                .query
+                .as_ref()
+                .map(|q| q.options)
+                .unwrap_or_default();
+            ui.app_state
+                .borrow_mut()
+                .search
+                .history
+                .record(HistoryKind::Replace, &value, options);
+            show_replace_preview(&ui, &value);
+        });
+    }
+
+    {
+        let mut win = win.clone();
+        close.set_callback(move |_| win.hide());
+    }
+
+    win.handle(|w, ev| {
+        if ev == Event::KeyDown && app::event_key() == Key::Escape {
+            w.hide();
+            return true;
+        }
+        false
+    });
+
+    FindInFilesDialog {
+        win,
+        pattern,
+        root,
+        include,
+        exclude,
+        case_sensitive,
+        regex,
+        status,
+    }
+}
+
+pub fn show_find_in_files_dialog(
+    ui: &FindInFilesUi,
+    root: &str,
+    pattern: &str,
+    options: SearchOptions,
+) {
+    if ui.dialog.borrow().is_none() {
+        let dialog = build_dialog(ui);
+        *ui.dialog.borrow_mut() = Some(dialog);
+    }
+
+    let mut slot = ui.dialog.borrow_mut();
+    let Some(dialog) = slot.as_mut() else {
+        return;
+    };
+
+    if !pattern.is_empty() {
+        dialog.pattern.set_value(pattern);
+    }
+    if dialog.root.value().is_empty() {
+        dialog.root.set_value(root);
+    }
+    dialog.case_sensitive.set_value(options.case_sensitive);
+    dialog.regex.set_value(options.regex);
+
+    if !dialog.win.shown() {
+        let (x, y) = app::event_coords();
+        dialog.win.set_pos(x.max(0), y.max(0));
+    }
+    dialog.win.show();
+    dialog.pattern.take_focus().ok();
+}
+
+/// Places the cursor on a hit, opening its file first unless it is the current one.
+fn open_hit(
+    hit: &FileHit,
+    app_state: &Rc<RefCell<AppState>>,
+    open_path: &OpenPath,
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    update_status: &UpdateStatus,
+) {
+    let path = hit.path.to_string_lossy().to_string();
+    let current_path = app_state.borrow().document.filepath.clone();
+    if path != current_path && !open_path(&path) {
+        return;
+    }
+
+    select_source_range(editor, buf, app_state, hit.start as i32, hit.end as i32);
+    let _ = editor.clone().take_focus();
+    update_status();
+}
+
+pub fn create_find_in_files(
+    dock: &SharedDock,
+    relayout: &Relayout,
+    open_path: &OpenPath,
+    app_state: &Rc<RefCell<AppState>>,
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    update_status: &UpdateStatus,
+) -> FindInFilesUi {
+    let mut browser = add_dock_page(dock, "Find in Files");
+    browser.set_column_widths(&[260]);
+    browser.set_column_char('\t');
+
+    let ui = FindInFilesUi {
+        state: Rc::new(RefCell::new(FindInFilesState::default())),
+        panel: Rc::new(RefCell::new(browser)),
+        dialog: Rc::new(RefCell::new(None)),
+        dock: Rc::clone(dock),
+        relayout: Rc::clone(relayout),
+        app_state: Rc::clone(app_state),
+        buf: Rc::clone(buf),
+    };
+
+    {
+        let state = Rc::clone(&ui.state);
+        let app_state = Rc::clone(app_state);
+        let open_path = Rc::clone(open_path);
+        let editor = editor.clone();
+        let buf = Rc::clone(buf);
+        let update_status = Rc::clone(update_status);
+
+        ui.panel.borrow_mut().set_callback(move |b| {
+            let row = b.value();
+            if row <= 0 {
+                return;
+            }
+            let hit = state.borrow().hits.get((row - 1) as usize).cloned();
+            if let Some(hit) = hit {
+                open_hit(&hit, &app_state, &open_path, &editor, &buf, &update_status);
+            }
+        });
+    }
+
+    ui
+}
@@ -0,0 +1,228 @@
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    button::{Button, ReturnButton},
+    draw,
+    enums::{Align, Font},
+    frame::Frame,
+    group::Group,
+    misc::Spinner,
+    prelude::*,
+    text::TextEditor,
+    window::Window,
+};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use super::gutter::{SharedGutter, update_gutter_width};
+use super::highlight::{SharedHighlighter, refresh_style_table};
+use super::state::AppState;
+
+const MEASURE_SIZE: i32 = 14;
+pub const MIN_FONT_SIZE: i32 = 6;
+pub const MAX_FONT_SIZE: i32 = 72;
+const PREVIEW_TEXT: &str = "fn main() { let O0 = l1I | {}; }";
+
+/// FLTK's own fonts, in index order; system fonts come after them.
+const BUILTIN_FONTS: [&str; 16] = [
+    "Helvetica",
+    "HelveticaBold",
+    "HelveticaItalic",
+    "HelveticaBoldItalic",
+    "Courier",
+    "CourierBold",
+    "CourierItalic",
+    "CourierBoldItalic",
+    "Times",
+    "TimesBold",
+    "TimesItalic",
+    "TimesBoldItalic",
+    "Symbol",
+    "Screen",
+    "ScreenBold",
+    "Zapfdingbats",
+];
+
+#[derive(Clone)]
+pub struct FontUi {
+    /// Steps added by zooming; not saved, and reset with Ctrl+0.
+    pub zoom: Rc<Cell<i32>>,
+    pub editor: TextEditor,
+    pub highlighter: SharedHighlighter,
+    pub state: Rc<RefCell<AppState>>,
+    pub gutter: SharedGutter,
+}
+
+fn load_system_fonts() {
+    if app::font_count() <= BUILTIN_FONTS.len() {
+        app::App::default().load_system_fonts();
+    }
+}
+
+/// Finds a font by the name shown in the chooser, loading system fonts if needed.
+pub fn resolve_font(name: &str) -> Font {
+    if let Some(idx) = BUILTIN_FONTS.iter().position(|f| *f == name) {
+        return Font::by_index(idx);
+    }
+    load_system_fonts();
+    app::fonts()
+        .iter()
+        .position(|f| f.trim() == name)
+        .map(Font::by_index)
+        .unwrap_or(Font::Courier)
+}
+
+fn is_monospace(font: Font) -> bool {
+    draw::set_font(font, MEASURE_SIZE);
+    (draw::width("iiii") - draw::width("MMMM")).abs() < 0.5
+}
+
+/// Regular (not bold or italic) monospace fonts as (name, font) pairs.
+///
+/// System font names start with a style character; a space marks the regular face.
+pub fn monospace_fonts() -> Vec<(String, Font)> {
+    load_system_fonts();
+    let mut fonts = vec![
+        (BUILTIN_FONTS[4].to_string(), Font::Courier),
+        (BUILTIN_FONTS[13].to_string(), Font::Screen),
+    ];
+    let mut system: Vec<(String, Font)> = app::fonts()
+        .iter()
+        .enumerate()
+        .skip(BUILTIN_FONTS.len())
+        .filter(|(_, name)| name.starts_with(' '))
+        .map(|(idx, name)| (name.trim().to_string(), Font::by_index(idx)))
+        .filter(|(_, font)| is_monospace(*font))
+        .collect();
+    system.sort_by_key(|(name, _)| name.to_lowercase());
+    system.dedup_by(|a, b| a.0 == b.0);
+    fonts.extend(system);
+    fonts
+}
+
+pub fn apply_font(ui: &FontUi) {
+    let (font, size) = {
+        let s = ui.state.borrow();
+        let appearance = &s.settings.appearance;
+        let size = (appearance.font_size + ui.zoom.get()).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        (resolve_font(&appearance.font), size)
+    };
+
+    let mut ed = ui.editor.clone();
+    ed.set_text_font(font);
+    ed.set_text_size(size);
+    refresh_style_table(&ui.editor, &ui.highlighter, &ui.state.borrow());
+    update_gutter_width(&ui.gutter, &ui.editor);
+    ed.redraw();
+}
+
+/// Zooms by `steps` points; zero resets to the chosen size.
+pub fn zoom(ui: &FontUi, steps: i32) {
+    let base = ui.state.borrow().settings.appearance.font_size;
+    let zoom = if steps == 0 { 0 } else { ui.zoom.get() + steps };
+    ui.zoom
+        .set((base + zoom).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE) - base);
+    apply_font(ui);
+}
+
+pub fn show_font_dialog(ui: &FontUi) {
+    Group::set_current(None::<&Group>);
+
+    let mut win = Window::new(0, 0, 400, 330, "Font");
+    let mut list = HoldBrowser::new(10, 10, 260, 245, "");
+    let mut size = Spinner::new(310, 10, 80, 25, "Size:");
+    let mut preview = Frame::new(10, 262, 380, 25, PREVIEW_TEXT);
+    preview.set_align(Align::Left | Align::Inside | Align::Clip);
+    let mut ok = ReturnButton::new(220, 295, 80, 25, "OK");
+    let mut cancel = Button::new(310, 295, 80, 25, "Cancel");
+    win.end();
+    win.make_modal(true);
+
+    let fonts = Rc::new(monospace_fonts());
+    let (current, current_size) = {
+        let s = ui.state.borrow();
+        (
+            s.settings.appearance.font.clone(),
+            s.settings.appearance.font_size,
+        )
+    };
+    for (idx, (name, _)) in fonts.iter().enumerate() {
+        list.add(name);
+        if *name == current {
+            list.select(idx as i32 + 1);
+        }
+    }
+    if list.value() <= 0 {
+        list.select(1);
+    }
+    size.set_range(MIN_FONT_SIZE as f64, MAX_FONT_SIZE as f64);
+    size.set_step(1.0);
+    size.set_value(current_size as f64);
+
+    let update_preview = {
+        let fonts = Rc::clone(&fonts);
+        let list = list.clone();
+        let size = size.clone();
+        let preview = preview.clone();
+        move || {
+            let Some((_, font)) = fonts.get((list.value() - 1).max(0) as usize) else {
+                return;
+            };
+            let mut preview = preview.clone();
+            preview.set_label_font(*font);
+            preview.set_label_size(size.value() as i32);
+            preview.redraw();
+        }
+    };
+    update_preview();
+
+    {
+        let update_preview = update_preview.clone();
+        list.set_callback(move |_| update_preview());
+    }
+    size.set_callback(move |_| update_preview());
+
+    {
+        let ui = ui.clone();
+        let mut win = win.clone();
+        ok.set_callback(move |_| {
+            if let Some((name, _)) = fonts.get((list.value() - 1).max(0) as usize) {
+                let mut s = ui.state.borrow_mut();
+                s.settings.appearance.font = name.clone();
+                s.settings.appearance.font_size = size.value() as i32;
+                s.settings.save();
+            }
+            ui.zoom.set(0);
+            apply_font(&ui);
+            win.hide();
+        });
+    }
+
+    {
+        let mut win = win.clone();
+        cancel.set_callback(move |_| win.hide());
+    }
+
+    let (x, y) = app::event_coords();
+    win.set_pos(x.max(0), y.max(0));
+    win.show();
+}
+
+pub fn create_fonts(
+    editor: &TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+    gutter: &SharedGutter,
+) -> FontUi {
+    let ui = FontUi {
+        zoom: Rc::new(Cell::new(0)),
+        editor: editor.clone(),
+        highlighter: Rc::clone(highlighter),
+        state: Rc::clone(state),
+        gutter: Rc::clone(gutter),
+    };
+    apply_font(&ui);
+    ui
+}
@@ -0,0 +1,222 @@
+use fltk::{
+    dialog,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use super::filter::select_source_range;
+use super::state::AppState;
+use super::status::UpdateStatus;
+
+pub type GotoLine = Rc<dyn Fn()>;
+
+const PROMPT: &str = "Go to line (line, line:col, +N, -N, N%, #offset or 0x offset):";
+
+pub enum GotoTarget {
+    /// 1-based line, optional 1-based column.
+    Line(usize, Option<usize>),
+    Relative(i64),
+    Percent(f64),
+    Offset(usize),
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    text.trim()
+        .parse::<usize>()
+        .map_err(|_| format!("\"{}\" is not a number.", text.trim()))
+}
+
+pub fn parse_goto(input: &str) -> Result<GotoTarget, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Enter a line number.".into());
+    }
+
+    if let Some(hex) = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        return usize::from_str_radix(hex, 16)
+            .map(GotoTarget::Offset)
+            .map_err(|_| format!("\"{}\" is not a hexadecimal offset.", input));
+    }
+    if let Some(offset) = input.strip_prefix('#') {
+        return parse_number(offset).map(GotoTarget::Offset);
+    }
+    if let Some(percent) = input.strip_suffix('%') {
+        return match percent.trim().parse::<f64>() {
+            Ok(p) if (0.0..=100.0).contains(&p) => Ok(GotoTarget::Percent(p)),
+            _ => Err(format!(
+                "\"{}\" is not a percentage between 0 and 100.",
+                input
+            )),
+        };
+    }
+    if let Some(rest) = input.strip_prefix('+') {
+        return parse_number(rest).map(|n| GotoTarget::Relative(n as i64));
+    }
+    if let Some(rest) = input.strip_prefix('-') {
+        return parse_number(rest).map(|n| GotoTarget::Relative(-(n as i64)));
+    }
+
+    match input.split_once(':') {
+        Some((line, col)) => Ok(GotoTarget::Line(
+            parse_number(line)?,
+            Some(parse_number(col)?),
+        )),
+        None => Ok(GotoTarget::Line(parse_number(input)?, None)),
+    }
+}
+
+fn floor_char_boundary(text: &str, mut pos: usize) -> usize {
+    pos = pos.min(text.len());
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    pos
+}
+
+/// Resolves a target to a byte position in `text`; `current_line` is 0-based.
+pub fn resolve_goto(text: &str, current_line: usize, target: &GotoTarget) -> usize {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let last = line_starts.len() - 1;
+
+    let (line, col) = match *target {
+        GotoTarget::Offset(offset) => return floor_char_boundary(text, offset),
+        GotoTarget::Line(line, col) => (line.saturating_sub(1), col),
+        GotoTarget::Relative(delta) => ((current_line as i64 + delta).max(0) as usize, None),
+        GotoTarget::Percent(p) => (((last as f64) * p / 100.0).round() as usize, None),
+    };
+
+    let line = line.min(last);
+    let start = line_starts[line];
+    let end = line_starts
+        .get(line + 1)
+        .map(|next| next - 1)
+        .unwrap_or(text.len());
+    let end = if text[start..end].ends_with('\r') {
+        end - 1
+    } else {
+        end
+    };
+
+    let pos = start + col.unwrap_or(1).saturating_sub(1);
+    floor_char_boundary(text, pos.min(end))
+}
+
+/// The cursor line in the document, looking through the filtered view if one is shown.
+pub fn current_source_line(editor: &TextEditor, state: &AppState) -> usize {
+    let pos = editor.insert_position();
+    // Count in the buffer rather than the display, which would count wrapped rows.
+    let line = editor
+        .buffer()
+        .map(|b| b.count_lines(0, pos) as usize)
+        .unwrap_or(0);
+    match &state.document.filter.view {
+        Some(view) => view.source_line(line).unwrap_or(0),
+        None => line,
+    }
+}
+
+pub fn make_goto_line(
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) -> GotoLine {
+    let editor = editor.clone();
+    let buf = Rc::clone(buf);
+    let state = Rc::clone(state);
+    let update_status = Rc::clone(update_status);
+
+    Rc::new(move || {
+        let mut answer = String::new();
+        loop {
+            let Some(input) = dialog::input_default(PROMPT, &answer) else {
+                return;
+            };
+            answer = input;
+
+            match parse_goto(&answer) {
+                Ok(target) => {
+                    let text = buf.borrow().text();
+                    let current = current_source_line(&editor, &state.borrow());
+                    let pos = resolve_goto(&text, current, &target) as i32;
+
+                    if !select_source_range(&editor, &buf, &state, pos, pos) {
+                        dialog::alert_default("That line is hidden by the active filters.");
+                        continue;
+                    }
+                    let _ = editor.clone().take_focus();
+                    update_status();
+                    return;
+                }
+                Err(message) => dialog::alert_default(&message),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "one\r\ntwo\r\nthree\r\nfour\r\n";
+
+    fn goto(current_line: usize, input: &str) -> usize {
+        resolve_goto(TEXT, current_line, &parse_goto(input).ok().unwrap())
+    }
+
+    #[test]
+    fn line_and_column() {
+        assert_eq!(goto(0, "1"), 0);
+        assert_eq!(goto(0, "2"), 5);
+        assert_eq!(goto(0, " 3:2 "), 11);
+        assert_eq!(goto(0, "3:0"), 10);
+    }
+
+    #[test]
+    fn out_of_range_targets_are_clamped() {
+        assert_eq!(goto(0, "0"), 0);
+        assert_eq!(goto(0, "99"), TEXT.len());
+        // Past the end of the line, before its `\r`.
+        assert_eq!(goto(0, "2:50"), 8);
+        assert_eq!(goto(0, "-10"), 0);
+        assert_eq!(goto(0, "#999"), TEXT.len());
+    }
+
+    #[test]
+    fn relative_lines() {
+        assert_eq!(goto(1, "+1"), 10);
+        assert_eq!(goto(3, "-2"), 5);
+        assert_eq!(goto(2, "+0"), 10);
+    }
+
+    #[test]
+    fn percent_of_the_document() {
+        assert_eq!(goto(0, "0%"), 0);
+        assert_eq!(goto(0, "50%"), 10);
+        assert_eq!(goto(0, "100%"), TEXT.len());
+        assert!(parse_goto("101%").is_err());
+        assert!(parse_goto("-5%").is_err());
+    }
+
+    #[test]
+    fn byte_offsets() {
+        assert_eq!(goto(0, "#7"), 7);
+        assert_eq!(goto(0, "0x0A"), 10);
+        assert_eq!(goto(0, "0X0a"), 10);
+        // Inside a multi-byte character, back to its start.
+        assert_eq!(resolve_goto("a\u{e9}b", 0, &GotoTarget::Offset(2)), 1);
+    }
+
+    #[test]
+    fn bad_input_is_rejected() {
+        for input in ["", "  ", "abc", "3:x", "0xZZ", "#", "+-1", "1.5"] {
+            assert!(parse_goto(input).is_err(), "{:?}", input);
+        }
+    }
+}
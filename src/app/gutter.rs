@@ -0,0 +1,264 @@
+use fltk::{
+    app, draw,
+    enums::{Align, Color, Event},
+    prelude::*,
+    text::{PositionType, TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use super::goto::current_source_line;
+use super::state::AppState;
+use super::whitespace::draw_whitespace;
+
+const GUTTER_PADDING: i32 = 12;
+
+pub struct Gutter {
+    pub visible: bool,
+    pub relative: bool,
+    /// Column the ruler line is drawn at, if shown.
+    pub ruler: Option<i32>,
+    /// Draw glyphs for spaces, tabs and line endings.
+    pub whitespace: bool,
+    /// Lines in the document, kept up to date from buffer edits.
+    line_count: usize,
+    /// Line start where a click-and-drag selection began.
+    anchor: Option<i32>,
+}
+
+pub type SharedGutter = Rc<RefCell<Gutter>>;
+
+/// Resizes the editor's line-number area to fit the largest line number.
+pub fn update_gutter_width(gutter: &SharedGutter, editor: &TextEditor) {
+    let g = gutter.borrow();
+    let mut ed = editor.clone();
+    if !g.visible {
+        ed.set_linenumber_width(0);
+        ed.redraw();
+        return;
+    }
+
+    // Estimate digit width from the font size; measuring needs an open display,
+    // which isn't there yet when the editor is first set up.
+    let digits = g.line_count.max(1).to_string().len().max(2) as i32;
+    let w = digits * (ed.text_size() * 3 + 4) / 5;
+    if ed.linenumber_width() != w + GUTTER_PADDING {
+        ed.set_linenumber_width(w + GUTTER_PADDING);
+        ed.redraw();
+    }
+}
+
+fn gutter_left(editor: &TextEditor) -> i32 {
+    editor.x() + editor.frame().dx()
+}
+
+/// The editor's text area, right of the line numbers, as (x, y, w, h).
+pub fn text_area(editor: &TextEditor) -> (i32, i32, i32, i32) {
+    let x = gutter_left(editor) + editor.linenumber_width();
+    let y = editor.y() + editor.frame().dy();
+    let w =
+        editor.w() - editor.linenumber_width() - 2 * editor.frame().dx() - editor.scrollbar_size();
+    let h = editor.h() - 2 * editor.frame().dy();
+    (x, y, w, h)
+}
+
+/// Draws a vertical line at `column`, following horizontal scrolling.
+fn draw_ruler(e: &TextEditor, column: i32, color: Color) {
+    let (text_x, top_y, area_w, area_h) = text_area(e);
+    let offset = e.col_to_x(column as f64 - e.x_to_col(text_x as f64));
+    let x = text_x + offset.round() as i32;
+    if x > text_x && x < text_x + area_w {
+        draw::set_draw_color(color);
+        draw::draw_line(x, top_y, x, top_y + area_h - 1);
+    }
+}
+
+/// Paints line numbers over the editor's own number area, plus bookmark bars,
+/// the ruler and whitespace glyphs.
+///
+/// Drawing from the editor keeps the gutter in step with every scroll and edit.
+fn attach_overlay(editor: &mut TextEditor, gutter: &SharedGutter, state: &Rc<RefCell<AppState>>) {
+    let gutter = Rc::clone(gutter);
+    let state = Rc::clone(state);
+
+    editor.draw(move |e| {
+        let (Some(display), Ok(g), Ok(s)) = (e.buffer(), gutter.try_borrow(), state.try_borrow())
+        else {
+            return;
+        };
+
+        let left = gutter_left(e);
+        let top_y = e.y() + e.frame().dy();
+        let area_h = e.h() - 2 * e.frame().dy();
+        let width = e.linenumber_width();
+        if g.whitespace {
+            draw_whitespace(e, &display, s.theme.whitespace);
+        }
+        if let Some(column) = g.ruler {
+            draw_ruler(e, column, s.theme.ruler);
+        }
+        if g.visible {
+            draw::push_clip(left, top_y, width, area_h);
+            draw::draw_rect_fill(left, top_y, width, area_h, s.theme.gutter_background);
+        } else if !s.document.marks.has_bookmarks() {
+            return;
+        }
+
+        draw::set_font(e.text_font(), e.text_size());
+        let line_h = draw::height();
+        let text_x = left + width + 5;
+        let first = e.xy_to_position(text_x, top_y + 1, PositionType::Cursor);
+        let last = e.xy_to_position(text_x, top_y + area_h - 1, PositionType::Cursor);
+        let current = current_source_line(e, &s);
+
+        let mut pos = display.line_start(first);
+        let mut row = display.count_lines(0, pos) as usize;
+        while pos <= last {
+            let line = match &s.document.filter.view {
+                Some(view) => view.source_line(row),
+                None => Some(row),
+            };
+            let (_, y) = e.position_to_xy(pos);
+            // The first line may start above the view when it wraps.
+            let on_screen = y >= top_y && y < top_y + area_h;
+
+            if let Some(line) = line.filter(|_| on_screen) {
+                let bookmarked = s.document.marks.is_bookmarked(line);
+                if g.visible {
+                    let number = if g.relative && line != current {
+                        line.abs_diff(current)
+                    } else {
+                        line + 1
+                    };
+                    if line == current {
+                        draw::draw_rect_fill(left, y, width, line_h, s.theme.current_line);
+                        draw::set_draw_color(s.theme.current_line_number);
+                    } else {
+                        draw::set_draw_color(s.theme.line_number);
+                    }
+                    draw::draw_text2(
+                        &number.to_string(),
+                        left,
+                        y,
+                        width - GUTTER_PADDING / 2,
+                        line_h,
+                        Align::Right,
+                    );
+                }
+                if bookmarked {
+                    let x = if g.visible { left + 1 } else { left + 2 };
+                    draw::draw_rect_fill(x, y, 3, line_h, s.theme.bookmark);
+                }
+            }
+
+            let next = display.line_end(pos) + 1;
+            if next > display.length() {
+                break;
+            }
+            pos = next;
+            row += 1;
+        }
+
+        if g.visible {
+            draw::pop_clip();
+        }
+    });
+}
+
+/// Keeps the line count, and with it the gutter width, in step with edits.
+fn attach_line_count_tracking(
+    buf: &Rc<RefCell<TextBuffer>>,
+    gutter: &SharedGutter,
+    editor: &TextEditor,
+) {
+    let gutter = Rc::clone(gutter);
+    let editor = editor.clone();
+
+    buf.borrow_mut()
+        .add_modify_callback2(move |b, pos, inserted, _, _, deleted_text| {
+            let added = b.count_lines(pos, pos + inserted) as usize;
+            let removed = deleted_text.matches('\n').count();
+            if added == 0 && removed == 0 {
+                return;
+            }
+            if let Ok(mut g) = gutter.try_borrow_mut() {
+                g.line_count = (g.line_count + added).saturating_sub(removed).max(1);
+            }
+            update_gutter_width(&gutter, &editor);
+        });
+}
+
+pub fn create_gutter(
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    state: &Rc<RefCell<AppState>>,
+) -> SharedGutter {
+    let gutter = Rc::new(RefCell::new(Gutter {
+        visible: true,
+        relative: false,
+        ruler: None,
+        whitespace: false,
+        line_count: 1,
+        anchor: None,
+    }));
+
+    let mut ed = editor.clone();
+    let background = state.borrow().theme.gutter_background;
+    ed.set_linenumber_bgcolor(background);
+    ed.set_linenumber_fgcolor(background);
+    attach_overlay(&mut ed, &gutter, state);
+    attach_line_count_tracking(buf, &gutter, editor);
+    update_gutter_width(&gutter, editor);
+    gutter
+}
+
+fn line_start_at(editor: &TextEditor, y: i32) -> Option<(TextBuffer, i32)> {
+    let display = editor.buffer()?;
+    let x = gutter_left(editor) + editor.linenumber_width() + 5;
+    let pos = editor.xy_to_position(x, y, PositionType::Cursor);
+    let start = display.line_start(pos);
+    Some((display, start))
+}
+
+/// Selects whole lines between two line starts, cursor after the last one.
+fn select_lines(editor: &mut TextEditor, display: &mut TextBuffer, anchor: i32, pos: i32) {
+    let start = anchor.min(pos);
+    let end = (display.line_end(anchor.max(pos)) + 1).min(display.length());
+    display.select(start, end);
+    editor.set_insert_position(end);
+}
+
+/// Click selects a line, dragging extends the selection over whole lines.
+/// Returns true when the event belonged to the gutter.
+pub fn handle_gutter_event(gutter: &SharedGutter, editor: &mut TextEditor, ev: Event) -> bool {
+    let mut g = gutter.borrow_mut();
+    if !g.visible {
+        return false;
+    }
+
+    match ev {
+        Event::Push => {
+            let over_gutter = app::event_x() < gutter_left(editor) + editor.linenumber_width();
+            if !over_gutter || app::event_mouse_button() != app::MouseButton::Left {
+                return false;
+            }
+            let Some((mut display, start)) = line_start_at(editor, app::event_y()) else {
+                return false;
+            };
+            g.anchor = Some(start);
+            select_lines(editor, &mut display, start, start);
+            let _ = editor.take_focus();
+            true
+        }
+        Event::Drag => {
+            let Some(anchor) = g.anchor else {
+                return false;
+            };
+            if let Some((mut display, start)) = line_start_at(editor, app::event_y()) {
+                select_lines(editor, &mut display, anchor, start);
+            }
+            true
+        }
+        Event::Released => g.anchor.take().is_some(),
+        _ => false,
+    }
+}
@@ -0,0 +1,454 @@
+use fltk::{
+    app, dialog,
+    enums::Font,
+    prelude::*,
+    text::{StyleTableEntryExt, TextAttr, TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, fs, path::Path, rc::Rc, time::SystemTime};
+
+use super::config::config_file;
+use super::languages::{
+    LANGUAGES_FILE, Language, LanguageDef, LineState, TOKEN_KINDS, TokenKind, builtin_languages,
+    load_user_languages,
+};
+use super::state::AppState;
+use super::theme::Theme;
+use super::whitespace::mark_whitespace;
+
+/// State of a line inserted by an edit that hasn't been tokenized yet.
+const UNKNOWN_STATE: LineState = LineState::MAX;
+/// How often (in seconds) `languages.toml` is checked for changes.
+const LANGUAGES_POLL_INTERVAL: f64 = 1.0;
+
+/// Style for trailing whitespace and invisible characters, after the token and hit styles.
+const WHITESPACE_STYLE: u8 = b'A' + 2 * TOKEN_KINDS as u8;
+/// Style for the bracket pair at the cursor.
+const BRACKET_STYLE: u8 = WHITESPACE_STYLE + 1;
+
+/// Keeps the style buffer in step with the document: one style char per byte,
+/// `A..` for token kinds, and the same kinds shifted by `TOKEN_KINDS` under search hits.
+pub struct Highlighter {
+    stylebuf: TextBuffer,
+    document: TextBuffer,
+    editor: TextEditor,
+    /// User languages first, so they win over built-in ones for the same file.
+    languages: Vec<Language>,
+    language: Option<usize>,
+    /// Path the language was picked for.
+    path: String,
+    /// Token style per byte, without search hits.
+    syntax: Vec<u8>,
+    /// Tokenizer state at the start of each line.
+    line_states: Vec<LineState>,
+    hits: Vec<(i32, i32)>,
+    /// Mark trailing whitespace and invisible characters.
+    show_whitespace: bool,
+    /// Positions of the bracket pair at the cursor.
+    brackets: Vec<i32>,
+}
+
+pub type SharedHighlighter = Rc<RefCell<Highlighter>>;
+
+fn bold(font: Font) -> Font {
+    match font {
+        Font::Courier => Font::CourierBold,
+        Font::Helvetica => Font::HelveticaBold,
+        Font::Times => Font::TimesBold,
+        Font::Screen => Font::ScreenBold,
+        other => other,
+    }
+}
+
+fn hit_style(style: u8) -> u8 {
+    let kind = style.saturating_sub(b'A') as usize % TOKEN_KINDS;
+    b'A' + (TOKEN_KINDS + kind) as u8
+}
+
+impl Highlighter {
+    pub fn stylebuf(&self) -> TextBuffer {
+        self.stylebuf.clone()
+    }
+
+    /// Style table entries in style char order, using the editor's font.
+    pub fn style_table(&self, theme: &Theme) -> Vec<StyleTableEntryExt> {
+        let font = self.editor.text_font();
+        let size = self.editor.text_size();
+        let entry = |kind: TokenKind, hit: bool| StyleTableEntryExt {
+            color: theme.token(kind),
+            font: match kind {
+                TokenKind::Keyword | TokenKind::Heading => bold(font),
+                _ => font,
+            },
+            size,
+            attr: if hit {
+                TextAttr::BgColor
+            } else {
+                TextAttr::None
+            },
+            bgcolor: theme.search_hit,
+        };
+
+        let plain = TokenKind::ALL.iter().map(|k| entry(*k, false));
+        let hits = TokenKind::ALL.iter().map(|k| entry(*k, true));
+        let whitespace = StyleTableEntryExt {
+            color: theme.foreground,
+            font,
+            size,
+            attr: TextAttr::BgColor,
+            bgcolor: theme.trailing_whitespace,
+        };
+        let bracket = StyleTableEntryExt {
+            color: theme.foreground,
+            font: bold(font),
+            size,
+            attr: TextAttr::BgColor,
+            bgcolor: theme.bracket_match,
+        };
+        plain.chain(hits).chain([whitespace, bracket]).collect()
+    }
+
+    /// Picks a language by file name or extension, falling back to a `#!` line.
+    /// Returns true when the language changed.
+    pub fn select_language(&mut self, path: &str, text: &str) -> bool {
+        let p = Path::new(path);
+        let file_name = p.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let extension = p.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let first_line = text.lines().next().unwrap_or_default();
+
+        let language = self
+            .languages
+            .iter()
+            .position(|l| l.matches_path(file_name, extension))
+            .or_else(|| {
+                self.languages
+                    .iter()
+                    .position(|l| l.matches_shebang(first_line))
+            });
+        let changed = language != self.language;
+        self.language = language;
+        self.path = path.to_string();
+        changed
+    }
+
+    pub fn language_name(&self) -> Option<&str> {
+        self.language.map(|l| self.languages[l].def.name.as_str())
+    }
+
+    /// Tokenizes lines from `line` (starting at byte `pos`) until the state carried
+    /// into a line past `settle_line` matches what it had before. Returns the end
+    /// of the restyled range.
+    fn tokenize_from(
+        &mut self,
+        b: &TextBuffer,
+        mut line: usize,
+        mut pos: i32,
+        settle_line: usize,
+    ) -> i32 {
+        let Some(language) = self.language.map(|idx| &self.languages[idx]) else {
+            return pos;
+        };
+        let len = b.length();
+
+        loop {
+            let end = b.line_end(pos);
+            let text = b.text_range(pos, end).unwrap_or_default();
+            let state = match self.line_states.get(line) {
+                Some(s) if *s != UNKNOWN_STATE => *s,
+                _ => 0,
+            };
+            let out = &mut self.syntax[pos as usize..end as usize];
+            let next = language.tokenize_line(&text, state, out);
+
+            if end >= len {
+                return end;
+            }
+            line += 1;
+            pos = end + 1;
+            if line > settle_line && self.line_states.get(line) == Some(&next) {
+                return end;
+            }
+            if let Some(s) = self.line_states.get_mut(line) {
+                *s = next;
+            }
+        }
+    }
+
+    fn composed(&self, start: usize, end: usize) -> String {
+        let mut out = self.syntax[start..end].to_vec();
+        if self.show_whitespace {
+            let text = self
+                .document
+                .text_range(start as i32, end as i32)
+                .unwrap_or_default();
+            mark_whitespace(&text, &mut out, WHITESPACE_STYLE);
+        }
+        for (s, e) in &self.hits {
+            let s = (*s as usize).max(start);
+            let e = (*e as usize).min(end);
+            for c in out
+                .iter_mut()
+                .take(e.saturating_sub(start))
+                .skip(s.saturating_sub(start))
+            {
+                *c = hit_style(*c);
+            }
+        }
+        for pos in &self.brackets {
+            if let Some(c) = (*pos as usize)
+                .checked_sub(start)
+                .and_then(|at| out.get_mut(at))
+            {
+                *c = BRACKET_STYLE;
+            }
+        }
+        String::from_utf8(out).unwrap_or_default()
+    }
+
+    pub fn has_language(&self) -> bool {
+        self.language.is_some()
+    }
+
+    /// Whether `pos` is outside strings and comments.
+    pub fn is_code(&self, pos: usize) -> bool {
+        let style = self.syntax.get(pos).copied();
+        style != Some(TokenKind::Comment.style_char())
+            && style != Some(TokenKind::String.style_char())
+    }
+
+    /// Marks the bracket pair at the cursor, restyling only the old and new positions.
+    pub fn set_brackets(&mut self, brackets: Vec<i32>) {
+        if brackets == self.brackets {
+            return;
+        }
+        let changed: Vec<i32> = self.brackets.iter().chain(&brackets).copied().collect();
+        self.brackets = brackets;
+        for pos in changed {
+            if pos < 0 || pos as usize >= self.syntax.len() {
+                continue;
+            }
+            let styles = self.composed(pos as usize, pos as usize + 1);
+            self.stylebuf.replace(pos, pos + 1, &styles);
+            self.editor.redisplay_range(pos, pos + 1);
+        }
+    }
+
+    /// Retokenizes the whole document, e.g. after the language changed.
+    pub fn rehighlight(&mut self, b: &TextBuffer) {
+        let len = b.length();
+        self.syntax = vec![TokenKind::Plain.style_char(); len as usize];
+        self.line_states = vec![0; b.count_lines(0, len) as usize + 1];
+        if len > 0 {
+            let last = self.line_states.len();
+            self.tokenize_from(b, 0, 0, last);
+        }
+        let styles = self.composed(0, len as usize);
+        self.stylebuf.set_text(&styles);
+        self.editor.redraw();
+    }
+
+    fn recompose(&mut self) {
+        let styles = self.composed(0, self.syntax.len());
+        self.stylebuf.set_text(&styles);
+        self.editor.redraw();
+    }
+
+    /// Layers search hits over the token styles.
+    pub fn set_hits(&mut self, hits: &[(i32, i32)]) {
+        self.hits = hits.to_vec();
+        self.recompose();
+    }
+
+    pub fn set_show_whitespace(&mut self, show: bool) {
+        if show != self.show_whitespace {
+            self.show_whitespace = show;
+            self.recompose();
+        }
+    }
+
+    fn shift_brackets(&mut self, pos: i32, inserted: i32, deleted: i32) {
+        self.brackets.retain(|b| *b < pos || *b >= pos + deleted);
+        for b in self.brackets.iter_mut().filter(|b| **b >= pos) {
+            *b += inserted - deleted;
+        }
+    }
+
+    fn shift_hits(&mut self, pos: i32, inserted: i32, deleted: i32) {
+        // Hits touched by the edit are dropped; the next search refresh finds them again.
+        self.hits
+            .retain(|(s, e)| *e <= pos || *s >= pos + deleted.max(1));
+        for (s, e) in self.hits.iter_mut().filter(|(s, _)| *s >= pos) {
+            *s += inserted - deleted;
+            *e += inserted - deleted;
+        }
+    }
+
+    fn on_modify(
+        &mut self,
+        b: &TextBuffer,
+        pos: i32,
+        inserted: i32,
+        deleted: i32,
+        deleted_text: &str,
+    ) {
+        let (p, ins, del) = (pos as usize, inserted as usize, deleted as usize);
+        if self.syntax.len() < p + del {
+            // Out of step with the document; start over.
+            self.rehighlight(b);
+            return;
+        }
+
+        let plain = TokenKind::Plain.style_char();
+        self.syntax
+            .splice(p..p + del, std::iter::repeat_n(plain, ins));
+
+        let line = b.count_lines(0, pos) as usize;
+        let added = b.count_lines(pos, pos + inserted) as usize;
+        let removed = deleted_text.matches('\n').count();
+        if self.line_states.len() <= line + removed {
+            self.line_states.resize(line + removed + 1, 0);
+        }
+        self.line_states.splice(
+            line + 1..line + 1 + removed,
+            std::iter::repeat_n(UNKNOWN_STATE, added),
+        );
+        self.shift_hits(pos, inserted, deleted);
+        self.shift_brackets(pos, inserted, deleted);
+
+        let start = b.line_start(pos);
+        let mut end = self
+            .tokenize_from(b, line, start, line + added)
+            .max(pos + inserted);
+        if self.show_whitespace {
+            // Whether whitespace is trailing depends on the rest of the line.
+            end = b.line_end(end);
+        }
+        let styles = self.composed(start as usize, end as usize);
+        self.stylebuf
+            .replace(start, end - inserted + deleted, &styles);
+        // The editor may be showing a filtered view rather than this document.
+        let shown = self.editor.buffer().map(|d| d.length()).unwrap_or(0);
+        self.editor
+            .redisplay_range(start.min(shown), end.min(shown));
+    }
+}
+
+/// Re-applies the style table after the theme or the editor's font changed,
+/// pointing it at whichever style buffer is on screen.
+pub fn refresh_style_table(editor: &TextEditor, highlighter: &SharedHighlighter, state: &AppState) {
+    let h = highlighter.borrow();
+    let stylebuf = match &state.document.filter.view {
+        Some(view) => view.stylebuf.clone(),
+        None => h.stylebuf(),
+    };
+    editor
+        .clone()
+        .set_highlight_data_ext(stylebuf, h.style_table(&state.theme));
+}
+
+/// Compiles user languages ahead of the built-in ones, collecting any errors.
+fn compile_languages(user: Vec<LanguageDef>) -> (Vec<Language>, Vec<String>) {
+    let mut languages = vec![];
+    let mut errors = vec![];
+    for def in user.into_iter().chain(builtin_languages()) {
+        match Language::compile(def) {
+            Ok(language) => languages.push(language),
+            Err(e) => errors.push(e),
+        }
+    }
+    (languages, errors)
+}
+
+fn load_languages() -> (Vec<Language>, Vec<String>) {
+    match load_user_languages() {
+        Ok(user) => compile_languages(user),
+        Err(e) => {
+            let (languages, mut errors) = compile_languages(vec![]);
+            errors.insert(0, e);
+            (languages, errors)
+        }
+    }
+}
+
+fn report_language_errors(errors: &[String]) {
+    if !errors.is_empty() {
+        dialog::alert_default(&format!(
+            "Some user languages could not be loaded:\n\n{}",
+            errors.join("\n")
+        ));
+    }
+}
+
+fn languages_modified() -> Option<SystemTime> {
+    fs::metadata(config_file(LANGUAGES_FILE))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Reloads `languages.toml` whenever it changes and rehighlights the document.
+fn attach_language_reload(highlighter: &SharedHighlighter, buf: &Rc<RefCell<TextBuffer>>) {
+    let highlighter = Rc::clone(highlighter);
+    let buf = Rc::clone(buf);
+    let mut last_modified = languages_modified();
+
+    app::add_timeout3(LANGUAGES_POLL_INTERVAL, move |handle| {
+        let modified = languages_modified();
+        if modified != last_modified {
+            last_modified = modified;
+            let (languages, errors) = load_languages();
+            let b = buf.borrow().clone();
+            let mut h = highlighter.borrow_mut();
+            h.languages = languages;
+            h.language = None;
+            let path = h.path.clone();
+            h.select_language(&path, &b.text_range(0, b.line_end(0)).unwrap_or_default());
+            h.rehighlight(&b);
+            drop(h);
+            report_language_errors(&errors);
+        }
+        app::repeat_timeout3(LANGUAGES_POLL_INTERVAL, handle);
+    });
+}
+
+pub fn create_highlighter(
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    theme: &Theme,
+) -> SharedHighlighter {
+    let (languages, errors) = load_languages();
+    // Wait for the main loop so the alert doesn't pop up before the window.
+    app::add_timeout3(0.0, move |_| report_language_errors(&errors));
+
+    let highlighter = Rc::new(RefCell::new(Highlighter {
+        stylebuf: TextBuffer::default(),
+        document: buf.borrow().clone(),
+        editor: editor.clone(),
+        languages,
+        language: None,
+        path: String::new(),
+        syntax: vec![],
+        line_states: vec![0],
+        hits: vec![],
+        show_whitespace: false,
+        brackets: vec![],
+    }));
+
+    {
+        let h = highlighter.borrow();
+        let mut ed = editor.clone();
+        ed.set_highlight_data_ext(h.stylebuf(), h.style_table(theme));
+    }
+
+    let highlighter_ref = Rc::clone(&highlighter);
+    buf.borrow_mut()
+        .add_modify_callback2(move |b, pos, inserted, deleted, _, deleted_text| {
+            if inserted == 0 && deleted == 0 {
+                return;
+            }
+            if let Ok(mut h) = highlighter_ref.try_borrow_mut() {
+                h.on_modify(b, pos, inserted, deleted, deleted_text);
+            }
+        });
+    attach_language_reload(&highlighter, buf);
+
+    highlighter
+}
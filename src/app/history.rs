@@ -0,0 +1,277 @@
+use fltk::{
+    enums::{Event, Key},
+    input::Input,
+    prelude::*,
+};
+use std::{cell::RefCell, fs, rc::Rc};
+
+use super::config::{config_file, write_config_file};
+use super::search::SearchOptions;
+use super::state::AppState;
+
+const HISTORY_FILE: &str = "search_history.txt";
+const SAVED_FILE: &str = "saved_searches.txt";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HistoryKind {
+    Search,
+    Replace,
+}
+
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub kind: HistoryKind,
+    pub pattern: String,
+    pub options: SearchOptions,
+}
+
+#[derive(Clone)]
+pub struct SavedSearch {
+    pub name: String,
+    pub pattern: String,
+    pub options: SearchOptions,
+}
+
+#[derive(Default)]
+pub struct SearchHistory {
+    /// Most recent first.
+    pub entries: Vec<HistoryEntry>,
+    pub saved: Vec<SavedSearch>,
+    /// Entries kept per kind.
+    pub limit: usize,
+    cursor: Option<usize>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn flags(options: &SearchOptions) -> String {
+    let mut out = String::new();
+    if options.case_sensitive {
+        out.push('c');
+    }
+    if options.regex {
+        out.push('r');
+    }
+    if out.is_empty() {
+        out.push('-');
+    }
+    out
+}
+
+fn parse_flags(flags: &str) -> SearchOptions {
+    SearchOptions {
+        case_sensitive: flags.contains('c'),
+        regex: flags.contains('r'),
+    }
+}
+
+impl SearchHistory {
+    pub fn load(limit: usize) -> Self {
+        let mut history = SearchHistory {
+            limit,
+            ..Default::default()
+        };
+
+        if let Ok(content) = fs::read_to_string(config_file(HISTORY_FILE)) {
+            for line in content.lines() {
+                let mut parts = line.splitn(3, '\t');
+                let (Some(kind), Some(fl), Some(pattern)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                let kind = match kind {
+                    "r" => HistoryKind::Replace,
+                    _ => HistoryKind::Search,
+                };
+                history.entries.push(HistoryEntry {
+                    kind,
+                    pattern: unescape(pattern),
+                    options: parse_flags(fl),
+                });
+            }
+        }
+
+        if let Ok(content) = fs::read_to_string(config_file(SAVED_FILE)) {
+            for line in content.lines() {
+                let mut parts = line.splitn(3, '\t');
+                let (Some(name), Some(fl), Some(pattern)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                history.saved.push(SavedSearch {
+                    name: unescape(name),
+                    pattern: unescape(pattern),
+                    options: parse_flags(fl),
+                });
+            }
+        }
+
+        history
+    }
+
+    fn save(&self) {
+        let content: Vec<String> = self
+            .entries
+            .iter()
+            .map(|e| {
+                let kind = match e.kind {
+                    HistoryKind::Search => "s",
+                    HistoryKind::Replace => "r",
+                };
+                format!("{}\t{}\t{}", kind, flags(&e.options), escape(&e.pattern))
+            })
+            .collect();
+        write_config_file(HISTORY_FILE, &content.join("\n"));
+    }
+
+    fn save_saved(&self) {
+        let content: Vec<String> = self
+            .saved
+            .iter()
+            .map(|s| {
+                format!(
+                    "{}\t{}\t{}",
+                    escape(&s.name),
+                    flags(&s.options),
+                    escape(&s.pattern)
+                )
+            })
+            .collect();
+        write_config_file(SAVED_FILE, &content.join("\n"));
+    }
+
+    pub fn record(&mut self, kind: HistoryKind, pattern: &str, options: SearchOptions) {
+        self.cursor = None;
+        if pattern.is_empty() {
+            return;
+        }
+
+        self.entries
+            .retain(|e| !(e.kind == kind && e.pattern == pattern));
+        self.entries.insert(
+            0,
+            HistoryEntry {
+                kind,
+                pattern: pattern.to_string(),
+                options,
+            },
+        );
+
+        let mut kept = 0;
+        self.entries.retain(|e| {
+            if e.kind != kind {
+                return true;
+            }
+            kept += 1;
+            kept <= self.limit
+        });
+        self.save();
+    }
+
+    pub fn is_browsing(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    pub fn reset_cursor(&mut self) {
+        self.cursor = None;
+    }
+
+    /// Moves through entries of `kind`; `older` walks back in time.
+    pub fn step(&mut self, kind: HistoryKind, older: bool) -> Option<HistoryEntry> {
+        let matching: Vec<&HistoryEntry> = self.entries.iter().filter(|e| e.kind == kind).collect();
+        if matching.is_empty() {
+            return None;
+        }
+
+        let next = match (self.cursor, older) {
+            (None, true) => 0,
+            (None, false) => return None,
+            (Some(i), true) => (i + 1).min(matching.len() - 1),
+            (Some(0), false) => {
+                self.cursor = None;
+                return Some(HistoryEntry {
+                    kind,
+                    pattern: String::new(),
+                    options: matching[0].options,
+                });
+            }
+            (Some(i), false) => i - 1,
+        };
+
+        let entry = matching[next].clone();
+        self.cursor = Some(next);
+        Some(entry)
+    }
+
+    pub fn save_search(&mut self, name: &str, pattern: &str, options: SearchOptions) {
+        self.saved.retain(|s| s.name != name);
+        self.saved.push(SavedSearch {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            options,
+        });
+        self.save_saved();
+    }
+
+    pub fn remove_saved(&mut self, name: &str) {
+        self.saved.retain(|s| s.name != name);
+        self.save_saved();
+    }
+}
+
+/// Lets Up/Down recall history in an input that has no other use for them.
+pub fn attach_history_keys<F>(
+    input: &mut Input,
+    state: &Rc<RefCell<AppState>>,
+    kind: HistoryKind,
+    mut on_recall: F,
+) where
+    F: FnMut(&HistoryEntry) + 'static,
+{
+    let state = Rc::clone(state);
+    input.handle(move |inp, ev| {
+        if ev != Event::KeyDown {
+            return false;
+        }
+
+        let key = fltk::app::event_key();
+        if key != Key::Up && key != Key::Down {
+            state.borrow_mut().search.history.reset_cursor();
+            return false;
+        }
+
+        let entry = state.borrow_mut().search.history.step(kind, key == Key::Up);
+        if let Some(entry) = entry {
+            inp.set_value(&entry.pattern);
+            inp.set_position(inp.value().len() as i32).ok();
+            on_recall(&entry);
+        }
+        true
+    });
+}
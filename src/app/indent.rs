@@ -0,0 +1,323 @@
+use fltk::{
+    menu::MenuBar,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use super::edit::{replace_changed_range, selected_lines, selection_spans_lines};
+use super::menu::sync_indent_menu;
+use super::settings::EditorSettings;
+use super::state::AppState;
+
+/// Lines looked at when guessing a document's indentation.
+const DETECT_LINES: usize = 10_000;
+pub const TAB_WIDTHS: [usize; 4] = [2, 3, 4, 8];
+
+/// How the current document is indented.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Indent {
+    pub tabs: bool,
+    pub width: usize,
+}
+
+impl Indent {
+    pub fn from_settings(settings: &EditorSettings) -> Indent {
+        Indent {
+            tabs: !settings.insert_spaces,
+            width: settings.tab_width.clamp(1, 16) as usize,
+        }
+    }
+
+    /// What one level of indentation inserts.
+    pub fn unit(&self) -> String {
+        if self.tabs {
+            "\t".into()
+        } else {
+            " ".repeat(self.width)
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let kind = if self.tabs { "Tabs" } else { "Spaces" };
+        format!("{}: {}", kind, self.width)
+    }
+}
+
+/// Guesses tabs vs spaces from which leads more lines, and the space width from
+/// the most common step between the indents of neighbouring lines.
+pub fn detect_indent(text: &str, fallback: Indent) -> Indent {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    let mut steps = [0usize; 9];
+    let mut previous = 0;
+
+    for line in text.lines().take(DETECT_LINES) {
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            tab_lines += 1;
+            previous = 0;
+            continue;
+        }
+
+        let spaces = line.len() - content.len();
+        // Skip the ` * ` continuation lines of block comments.
+        if spaces > 0 && !content.starts_with('*') {
+            space_lines += 1;
+            let step = spaces.abs_diff(previous);
+            if (2..steps.len()).contains(&step) {
+                steps[step] += 1;
+            }
+        }
+        previous = spaces;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return fallback;
+    }
+    if tab_lines > space_lines {
+        return Indent {
+            tabs: true,
+            ..fallback
+        };
+    }
+    let width = (2..steps.len())
+        .filter(|w| steps[*w] > 0)
+        .max_by_key(|w| steps[*w])
+        .unwrap_or(fallback.width);
+    Indent { tabs: false, width }
+}
+
+/// The indentation a newly loaded document should use.
+pub fn document_indent(settings: &EditorSettings, text: &str) -> Indent {
+    let fallback = Indent::from_settings(settings);
+    if settings.detect_indentation {
+        detect_indent(text, fallback)
+    } else {
+        fallback
+    }
+}
+
+/// Width of `whitespace` on screen, with tab stops every `width` columns.
+fn visual_width(whitespace: &str, width: usize) -> usize {
+    whitespace.chars().fold(0, |col, c| match c {
+        '\t' => (col / width + 1) * width,
+        _ => col + 1,
+    })
+}
+
+/// Rewrites the leading whitespace of every line in `text` with tabs or spaces.
+pub fn convert_indentation(text: &str, width: usize, tabs: bool) -> String {
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            let content = line.trim_start_matches([' ', '\t']);
+            let columns = visual_width(&line[..line.len() - content.len()], width);
+            let lead = if tabs {
+                "\t".repeat(columns / width) + &" ".repeat(columns % width)
+            } else {
+                " ".repeat(columns)
+            };
+            lead + content
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Adds or removes one indentation level on the selected lines, as one undo step.
+fn shift_lines(editor: &mut TextEditor, buf: &mut TextBuffer, indent: Indent, outdent: bool) {
+    let (start, end) = selected_lines(editor, buf);
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let unit = indent.unit();
+
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            if outdent {
+                let removed = if line.starts_with('\t') {
+                    1
+                } else {
+                    line.len() - line.trim_start_matches(' ').len()
+                };
+                line[removed.min(indent.width)..].to_string()
+            } else if line.is_empty() {
+                String::new()
+            } else {
+                unit.clone() + line
+            }
+        })
+        .collect();
+    let new_text = lines.join("\n");
+    if new_text == text {
+        return;
+    }
+
+    buf.replace(start, end, &new_text);
+    let new_end = start + new_text.len() as i32;
+    buf.select(start, new_end);
+    editor.set_insert_position(new_end);
+}
+
+/// Inserts spaces up to the next tab stop in place of a tab character.
+fn insert_soft_tab(editor: &mut TextEditor, buf: &mut TextBuffer, indent: Indent) {
+    if let Some((start, _)) = buf.selection_position() {
+        buf.remove_selection();
+        editor.set_insert_position(start);
+    }
+    let pos = editor.insert_position();
+    let before = buf.text_range(buf.line_start(pos), pos).unwrap_or_default();
+    let spaces = indent.width - visual_width(&before, indent.width) % indent.width;
+    buf.insert(pos, &" ".repeat(spaces));
+    editor.set_insert_position(pos + spaces as i32);
+}
+
+/// Tab indents and Shift+Tab unindents selected lines; a plain Tab inserts
+/// spaces when the document uses them. Returns true when the key was handled.
+pub fn handle_tab_key(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>, shift: bool) -> bool {
+    let Some(mut buf) = editor.buffer() else {
+        return false;
+    };
+    let indent = state.borrow().document.indent;
+
+    if shift || selection_spans_lines(&buf) {
+        shift_lines(editor, &mut buf, indent, shift);
+        return true;
+    }
+    if indent.tabs {
+        return false;
+    }
+    insert_soft_tab(editor, &mut buf, indent);
+    true
+}
+
+/// In leading spaces, Backspace deletes back to the previous tab stop when the
+/// document indents with spaces. Returns true when the key was handled.
+pub fn handle_backspace_key(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) -> bool {
+    let Some(mut buf) = editor.buffer() else {
+        return false;
+    };
+    let indent = state.borrow().document.indent;
+    if indent.tabs || buf.selected() {
+        return false;
+    }
+
+    let pos = editor.insert_position();
+    let before = buf.text_range(buf.line_start(pos), pos).unwrap_or_default();
+    if before.is_empty() || !before.bytes().all(|b| b == b' ') {
+        return false;
+    }
+    let remove = match before.len() % indent.width {
+        0 => indent.width,
+        partial => partial,
+    } as i32;
+    buf.remove(pos - remove, pos);
+    editor.set_insert_position(pos - remove);
+    true
+}
+
+/// Converts the selected lines, or the whole document, to tabs or spaces.
+pub fn convert_document(
+    editor: &mut TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    state: &Rc<RefCell<AppState>>,
+    tabs: bool,
+) {
+    if state.borrow().document.filter.view.is_some() {
+        return;
+    }
+    let width = state.borrow().document.indent.width;
+    let mut b = buf.borrow_mut();
+
+    if selection_spans_lines(&b) {
+        let (start, end) = selected_lines(editor, &b);
+        let text = b.text_range(start, end).unwrap_or_default();
+        let new_text = convert_indentation(&text, width, tabs);
+        if new_text != text {
+            b.replace(start, end, &new_text);
+            b.select(start, start + new_text.len() as i32);
+        }
+    } else {
+        let new_text = convert_indentation(&b.text(), width, tabs);
+        replace_changed_range(&mut b, &new_text);
+    }
+    drop(b);
+
+    state.borrow_mut().document.indent.tabs = tabs;
+}
+
+/// Pushes the document's indentation to the buffers' tab stops and the menu.
+pub fn apply_indent(
+    state: &Rc<RefCell<AppState>>,
+    buf: &Rc<RefCell<TextBuffer>>,
+    editor: &TextEditor,
+    menu: &MenuBar,
+) {
+    let indent = state.borrow().document.indent;
+    buf.borrow_mut().set_tab_distance(indent.width as i32);
+    if let Some(mut shown) = editor.buffer() {
+        shown.set_tab_distance(indent.width as i32);
+    }
+    sync_indent_menu(menu, indent);
+    editor.clone().redraw();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FALLBACK: Indent = Indent {
+        tabs: false,
+        width: 3,
+    };
+
+    fn spaces(width: usize) -> Indent {
+        Indent { tabs: false, width }
+    }
+
+    #[test]
+    fn detects_the_common_indent_step() {
+        let two = "fn a() {\n  b();\n  if x {\n    c();\n  }\n}\n";
+        assert!(detect_indent(two, FALLBACK) == spaces(2));
+        let four = "a:\n    b\n        c\n    d\n";
+        assert!(detect_indent(four, FALLBACK) == spaces(4));
+    }
+
+    #[test]
+    fn detects_tabs_by_majority() {
+        let text = "x\n\ty\n\t\tz\n  w\n";
+        let indent = detect_indent(text, FALLBACK);
+        assert!(indent.tabs);
+        assert_eq!(indent.width, FALLBACK.width);
+    }
+
+    #[test]
+    fn block_comment_stars_are_not_indentation() {
+        let text = "/**\n * doc\n */\nfn a() {\n    b();\n}\n";
+        assert!(detect_indent(text, FALLBACK) == spaces(4));
+    }
+
+    #[test]
+    fn unindented_text_keeps_the_fallback() {
+        assert!(detect_indent("a\nb\n\n", FALLBACK) == FALLBACK);
+        assert!(detect_indent("", FALLBACK) == FALLBACK);
+    }
+
+    #[test]
+    fn converts_tabs_to_spaces() {
+        assert_eq!(
+            convert_indentation("\tfoo\n\t\tbar\r\nbaz", 4, false),
+            "    foo\n        bar\r\nbaz"
+        );
+        // A space before a tab still reaches the same tab stop.
+        assert_eq!(convert_indentation(" \tx", 4, false), "    x");
+    }
+
+    #[test]
+    fn converts_spaces_to_tabs() {
+        assert_eq!(convert_indentation("      x\n    y", 4, true), "\t  x\n\ty");
+        assert_eq!(convert_indentation("  a\tb", 2, true), "\ta\tb");
+    }
+}
@@ -0,0 +1,570 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, io};
+
+use super::config::config_file;
+
+pub const LANGUAGES_FILE: &str = "languages.toml";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+    Plain,
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Function,
+    Attribute,
+    Key,
+    Heading,
+}
+
+pub const TOKEN_KINDS: usize = 10;
+
+impl TokenKind {
+    pub const ALL: [TokenKind; TOKEN_KINDS] = [
+        TokenKind::Plain,
+        TokenKind::Comment,
+        TokenKind::String,
+        TokenKind::Number,
+        TokenKind::Keyword,
+        TokenKind::Type,
+        TokenKind::Function,
+        TokenKind::Attribute,
+        TokenKind::Key,
+        TokenKind::Heading,
+    ];
+
+    pub fn style_char(self) -> u8 {
+        b'A' + self as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::Plain => "plain",
+            TokenKind::Comment => "comment",
+            TokenKind::String => "string",
+            TokenKind::Number => "number",
+            TokenKind::Keyword => "keyword",
+            TokenKind::Type => "type",
+            TokenKind::Function => "function",
+            TokenKind::Attribute => "attribute",
+            TokenKind::Key => "key",
+            TokenKind::Heading => "heading",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TokenKind> {
+        TokenKind::ALL
+            .into_iter()
+            .find(|k| k.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// A language as written down: regex rules applied one line at a time.
+///
+/// A rule with a capture group styles only the first group. Blocks span lines
+/// (comments, multi-line strings); their `end` is searched from where the start
+/// matched, so an end pattern may anchor with `^`.
+#[derive(Clone)]
+pub struct LanguageDef {
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub file_names: Vec<String>,
+    pub shebangs: Vec<String>,
+    pub blocks: Vec<(TokenKind, String, String)>,
+    pub rules: Vec<(TokenKind, String)>,
+}
+
+struct Block {
+    kind: TokenKind,
+    start: Regex,
+    end: Regex,
+}
+
+struct Rule {
+    kind: TokenKind,
+    regex: Regex,
+    grouped: bool,
+}
+
+pub struct Language {
+    pub def: LanguageDef,
+    blocks: Vec<Block>,
+    rules: Vec<Rule>,
+}
+
+/// Line state between lines: 0 outside any block, otherwise block index + 1.
+pub type LineState = u16;
+
+#[derive(Clone, Copy)]
+struct Found {
+    start: usize,
+    style_start: usize,
+    style_end: usize,
+}
+
+impl Language {
+    pub fn compile(def: LanguageDef) -> Result<Language, String> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern)
+                .map_err(|e| format!("{}: bad pattern {:?}: {}", def.name, pattern, e))
+        };
+
+        let mut blocks = vec![];
+        for (kind, start, end) in &def.blocks {
+            blocks.push(Block {
+                kind: *kind,
+                start: compile(start)?,
+                end: compile(end)?,
+            });
+        }
+
+        let mut rules = vec![];
+        for (kind, pattern) in &def.rules {
+            let regex = compile(pattern)?;
+            rules.push(Rule {
+                kind: *kind,
+                grouped: regex.captures_len() > 1,
+                regex,
+            });
+        }
+
+        Ok(Language { def, blocks, rules })
+    }
+
+    pub fn matches_path(&self, file_name: &str, extension: &str) -> bool {
+        self.def.file_names.iter().any(|n| n == file_name)
+            || self
+                .def
+                .extensions
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(extension))
+    }
+
+    pub fn matches_shebang(&self, first_line: &str) -> bool {
+        let Some(command) = first_line.strip_prefix("#!") else {
+            return false;
+        };
+        command
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .any(|word| self.def.shebangs.iter().any(|s| s == word))
+    }
+
+    fn find(&self, candidate: usize, line: &str, at: usize) -> Option<Found> {
+        if candidate < self.blocks.len() {
+            let m = self.blocks[candidate].start.find_at(line, at)?;
+            return Some(Found {
+                start: m.start(),
+                style_start: m.start(),
+                style_end: m.end(),
+            });
+        }
+
+        let rule = &self.rules[candidate - self.blocks.len()];
+        if rule.grouped {
+            let caps = rule.regex.captures_at(line, at)?;
+            let whole = caps.get(0)?;
+            let styled = caps.get(1).unwrap_or(whole);
+            Some(Found {
+                start: whole.start(),
+                style_start: styled.start(),
+                style_end: styled.end(),
+            })
+        } else {
+            let m = rule.regex.find_at(line, at)?;
+            Some(Found {
+                start: m.start(),
+                style_start: m.start(),
+                style_end: m.end(),
+            })
+        }
+    }
+
+    /// Writes one style char per byte of `line` into `out` and returns the state
+    /// the next line starts in.
+    pub fn tokenize_line(&self, line: &str, state: LineState, out: &mut [u8]) -> LineState {
+        out.fill(TokenKind::Plain.style_char());
+        let mut pos = 0;
+
+        if let Some(block) = (state as usize)
+            .checked_sub(1)
+            .and_then(|i| self.blocks.get(i))
+        {
+            match block.end.find(line) {
+                Some(m) => {
+                    out[..m.end()].fill(block.kind.style_char());
+                    pos = m.end();
+                }
+                None => {
+                    out.fill(block.kind.style_char());
+                    return state;
+                }
+            }
+        }
+
+        // Blocks come first so that `"""` wins over an empty `""` string rule.
+        let candidates = self.blocks.len() + self.rules.len();
+        let mut cache: Vec<Option<Option<Found>>> = vec![None; candidates];
+
+        while pos < line.len() {
+            let mut best: Option<(usize, Found)> = None;
+            for (c, slot) in cache.iter_mut().enumerate() {
+                let stale = match slot {
+                    None => true,
+                    Some(Some(found)) => found.start < pos,
+                    Some(None) => false,
+                };
+                if stale {
+                    *slot = Some(self.find(c, line, pos));
+                }
+                if let Some(Some(found)) = slot
+                    && best.is_none_or(|(_, b)| found.start < b.start)
+                {
+                    best = Some((c, *found));
+                }
+            }
+
+            let Some((candidate, found)) = best else {
+                break;
+            };
+
+            if candidate < self.blocks.len() {
+                let block = &self.blocks[candidate];
+                match block.end.find(&line[found.style_end..]) {
+                    Some(m) => {
+                        let end = found.style_end + m.end();
+                        out[found.start..end].fill(block.kind.style_char());
+                        pos = end;
+                    }
+                    None => {
+                        out[found.start..].fill(block.kind.style_char());
+                        return candidate as LineState + 1;
+                    }
+                }
+            } else {
+                let kind = self.rules[candidate - self.blocks.len()].kind;
+                out[found.style_start..found.style_end].fill(kind.style_char());
+                pos = found.style_end;
+            }
+
+            if pos <= found.start {
+                // Never stall on an empty match.
+                pos = found.start
+                    + line[found.start..]
+                        .chars()
+                        .next()
+                        .map_or(1, |c| c.len_utf8());
+            }
+        }
+
+        0
+    }
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn rules(items: &[(TokenKind, &str)]) -> Vec<(TokenKind, String)> {
+    items.iter().map(|(k, p)| (*k, p.to_string())).collect()
+}
+
+fn blocks(items: &[(TokenKind, &str, &str)]) -> Vec<(TokenKind, String, String)> {
+    items
+        .iter()
+        .map(|(k, s, e)| (*k, s.to_string(), e.to_string()))
+        .collect()
+}
+
+const DOUBLE_QUOTED: &str = r#""(?:[^"\\]|\\.)*""#;
+const NUMBER: &str =
+    r"\b(?:0[xX][0-9a-fA-F_]+|0[oO][0-7_]+|0[bB][01_]+|\d[\d_]*(?:\.\d[\d_]*)?(?:[eE][+-]?\d+)?)";
+const CALL: &str = r"\b([A-Za-z_]\w*)\s*\(";
+
+fn rust() -> LanguageDef {
+    LanguageDef {
+        name: "Rust".into(),
+        extensions: strings(&["rs"]),
+        file_names: vec![],
+        shebangs: vec![],
+        blocks: blocks(&[
+            (TokenKind::Comment, r"/\*", r"\*/"),
+            (TokenKind::String, r#"b?r#*""#, r##""#*"##),
+            (TokenKind::String, r#"b?""#, r#"^(?:[^"\\]|\\.)*""#),
+        ]),
+        rules: rules(&[
+            (TokenKind::Comment, r"//.*"),
+            (TokenKind::Attribute, r"#!?\[[^\]]*\]"),
+            (TokenKind::String, r"b?'(?:[^'\\]|\\.)'"),
+            (TokenKind::Attribute, r"'[A-Za-z_]\w*"),
+            (
+                TokenKind::Keyword,
+                r"\b(?:as|async|await|break|const|continue|crate|dyn|else|enum|extern|false|fn|for|if|impl|in|let|loop|match|mod|move|mut|pub|ref|return|self|Self|static|struct|super|trait|true|type|unsafe|use|where|while)\b",
+            ),
+            (
+                TokenKind::Type,
+                r"\b(?:[A-Z]\w*|i8|i16|i32|i64|i128|isize|u8|u16|u32|u64|u128|usize|f32|f64|bool|char|str)\b",
+            ),
+            (TokenKind::Function, r"\b([a-z_]\w*!)"),
+            (TokenKind::Number, NUMBER),
+            (TokenKind::Function, CALL),
+        ]),
+    }
+}
+
+fn toml() -> LanguageDef {
+    LanguageDef {
+        name: "TOML".into(),
+        extensions: strings(&["toml"]),
+        file_names: strings(&["Cargo.lock", "Pipfile"]),
+        shebangs: vec![],
+        blocks: blocks(&[
+            (TokenKind::String, r#"""""#, r#"""""#),
+            (TokenKind::String, r"'''", r"'''"),
+        ]),
+        rules: rules(&[
+            (TokenKind::Comment, r"#.*"),
+            (TokenKind::Heading, r"^\s*\[\[?[^\]]*\]\]?"),
+            (TokenKind::Key, r#"^\s*([A-Za-z0-9_.\-"' ]+?)\s*="#),
+            (TokenKind::String, DOUBLE_QUOTED),
+            (TokenKind::String, r"'[^']*'"),
+            (TokenKind::Keyword, r"\b(?:true|false|inf|nan)\b"),
+            (
+                TokenKind::Number,
+                r"\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:\d{2})?)?",
+            ),
+            (
+                TokenKind::Number,
+                r"[+-]?\b\d[\d_]*(?:\.\d+)?(?:[eE][+-]?\d+)?\b|\b0[xob][0-9a-fA-F_]+\b",
+            ),
+        ]),
+    }
+}
+
+fn json() -> LanguageDef {
+    LanguageDef {
+        name: "JSON".into(),
+        extensions: strings(&["json", "jsonc", "geojson"]),
+        file_names: strings(&[".prettierrc", ".eslintrc"]),
+        shebangs: vec![],
+        blocks: vec![],
+        rules: rules(&[
+            (TokenKind::Key, r#"("(?:[^"\\]|\\.)*")\s*:"#),
+            (TokenKind::String, DOUBLE_QUOTED),
+            (TokenKind::Keyword, r"\b(?:true|false|null)\b"),
+            (TokenKind::Number, r"-?\b\d+(?:\.\d+)?(?:[eE][+-]?\d+)?\b"),
+        ]),
+    }
+}
+
+fn markdown() -> LanguageDef {
+    LanguageDef {
+        name: "Markdown".into(),
+        extensions: strings(&["md", "markdown", "mdown"]),
+        file_names: vec![],
+        shebangs: vec![],
+        blocks: blocks(&[
+            (
+                TokenKind::String,
+                r"^\s*(?:```|~~~).*",
+                r"^\s*(?:```|~~~)\s*$",
+            ),
+            (TokenKind::Comment, r"<!--", r"-->"),
+        ]),
+        rules: rules(&[
+            (TokenKind::Heading, r"^#{1,6}\s.*"),
+            (TokenKind::Comment, r"^\s*>.*"),
+            (TokenKind::Attribute, r"^\s*(?:[-*+]|\d+[.)])\s"),
+            (TokenKind::String, r"`[^`]+`"),
+            (TokenKind::Keyword, r"\*\*[^*]+\*\*|__[^_]+__"),
+            (TokenKind::Type, r"\*[^*\s][^*]*\*|\b_[^_\s][^_]*_\b"),
+            (TokenKind::Function, r"!?\[[^\]]*\]\([^)]*\)"),
+        ]),
+    }
+}
+
+fn shell() -> LanguageDef {
+    LanguageDef {
+        name: "Shell".into(),
+        extensions: strings(&["sh", "bash", "zsh", "ksh"]),
+        file_names: strings(&[".bashrc", ".bash_profile", ".profile", ".zshrc"]),
+        shebangs: strings(&["sh", "bash", "zsh", "ksh", "dash"]),
+        blocks: blocks(&[
+            (TokenKind::String, r#"""#, r#"^(?:[^"\\]|\\.)*""#),
+            (TokenKind::String, r"'", r"'"),
+        ]),
+        rules: rules(&[
+            (TokenKind::Comment, r"(?:^|[ \t])(#.*)"),
+            (
+                TokenKind::Attribute,
+                r"\$\{[^}]*\}|\$[A-Za-z_]\w*|\$[0-9@#?$!*-]",
+            ),
+            (
+                TokenKind::Keyword,
+                r"\b(?:if|then|else|elif|fi|for|while|until|do|done|case|esac|in|function|return|local|export|readonly|select|break|continue|exit)\b",
+            ),
+            (TokenKind::Function, r"^\s*([A-Za-z_][\w-]*)\s*\(\)"),
+            (TokenKind::Number, r"\b\d+\b"),
+        ]),
+    }
+}
+
+fn python() -> LanguageDef {
+    LanguageDef {
+        name: "Python".into(),
+        extensions: strings(&["py", "pyw", "pyi"]),
+        file_names: strings(&["SConstruct", "SConscript"]),
+        shebangs: strings(&["python", "python2", "python3"]),
+        blocks: blocks(&[
+            (TokenKind::String, r#"[rRbBuUfF]{0,2}""""#, r#"""""#),
+            (TokenKind::String, r"[rRbBuUfF]{0,2}'''", r"'''"),
+        ]),
+        rules: rules(&[
+            (TokenKind::Comment, r"#.*"),
+            (TokenKind::Attribute, r"^\s*@[\w.]+"),
+            (TokenKind::String, r#"[rRbBuUfF]{0,2}"(?:[^"\\]|\\.)*""#),
+            (TokenKind::String, r"[rRbBuUfF]{0,2}'(?:[^'\\]|\\.)*'"),
+            (
+                TokenKind::Keyword,
+                r"\b(?:False|None|True|and|as|assert|async|await|break|class|continue|def|del|elif|else|except|finally|for|from|global|if|import|in|is|lambda|nonlocal|not|or|pass|raise|return|try|while|with|yield|match|case)\b",
+            ),
+            (TokenKind::Type, r"\b[A-Z]\w*\b"),
+            (TokenKind::Number, NUMBER),
+            (TokenKind::Function, CALL),
+        ]),
+    }
+}
+
+fn ini() -> LanguageDef {
+    LanguageDef {
+        name: "INI".into(),
+        extensions: strings(&["ini", "cfg", "conf", "properties", "desktop"]),
+        file_names: strings(&[".editorconfig", ".gitconfig", ".npmrc"]),
+        shebangs: vec![],
+        blocks: vec![],
+        rules: rules(&[
+            (TokenKind::Comment, r"^\s*[;#].*"),
+            (TokenKind::Heading, r"^\s*\[[^\]]*\]"),
+            (TokenKind::Key, r"^\s*([^=:\s\[][^=:]*?)\s*[=:]"),
+            (TokenKind::String, DOUBLE_QUOTED),
+            (TokenKind::Keyword, r"(?i)\b(?:true|false|yes|no|on|off)\b"),
+            (TokenKind::Number, r"\b\d+(?:\.\d+)?\b"),
+        ]),
+    }
+}
+
+pub fn builtin_languages() -> Vec<LanguageDef> {
+    vec![rust(), toml(), json(), markdown(), shell(), python(), ini()]
+}
+
+#[derive(Deserialize)]
+struct UserLanguagesFile {
+    #[serde(default)]
+    language: Vec<UserLanguage>,
+}
+
+/// One `[[language]]` table in `languages.toml`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserLanguage {
+    name: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    file_names: Vec<String>,
+    #[serde(default)]
+    shebangs: Vec<String>,
+    /// Style name to the words shown in that style, e.g. `keyword = ["if", "else"]`.
+    #[serde(default)]
+    keywords: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    rules: Vec<UserRule>,
+    #[serde(default)]
+    blocks: Vec<UserBlock>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserRule {
+    style: String,
+    pattern: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserBlock {
+    style: String,
+    start: String,
+    end: String,
+}
+
+fn style_kind(language: &str, style: &str) -> Result<TokenKind, String> {
+    TokenKind::from_name(style).ok_or_else(|| {
+        let names: Vec<&str> = TokenKind::ALL.iter().map(|k| k.name()).collect();
+        format!(
+            "{}: unknown style \"{}\" (expected one of {})",
+            language,
+            style,
+            names.join(", ")
+        )
+    })
+}
+
+impl UserLanguage {
+    /// Rules keep their order from the file and come before keyword lists.
+    fn into_def(self) -> Result<LanguageDef, String> {
+        let mut rules = vec![];
+        for rule in self.rules {
+            rules.push((style_kind(&self.name, &rule.style)?, rule.pattern));
+        }
+        for (style, words) in &self.keywords {
+            if words.is_empty() {
+                continue;
+            }
+            let words: Vec<String> = words.iter().map(|w| regex::escape(w)).collect();
+            let pattern = format!(r"\b(?:{})\b", words.join("|"));
+            rules.push((style_kind(&self.name, style)?, pattern));
+        }
+
+        let mut blocks = vec![];
+        for block in self.blocks {
+            blocks.push((
+                style_kind(&self.name, &block.style)?,
+                block.start,
+                block.end,
+            ));
+        }
+
+        Ok(LanguageDef {
+            name: self.name,
+            extensions: self
+                .extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_string())
+                .collect(),
+            file_names: self.file_names,
+            shebangs: self.shebangs,
+            blocks,
+            rules,
+        })
+    }
+}
+
+/// Reads the user's languages from the config directory; a missing file means none.
+pub fn load_user_languages() -> Result<Vec<LanguageDef>, String> {
+    let path = config_file(LANGUAGES_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+
+    let file: UserLanguagesFile =
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    file.language
+        .into_iter()
+        .map(UserLanguage::into_def)
+        .collect()
+}
@@ -0,0 +1,357 @@
+use fltk::{
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashSet,
+    hash::{BuildHasher, Hasher, RandomState},
+    rc::Rc,
+};
+
+use super::edit::{editable_buffer, selected_lines, selection_spans_lines};
+use super::state::AppState;
+
+pub type LineCommand = fn(&mut TextEditor, &Rc<RefCell<AppState>>);
+
+#[derive(Clone, Copy)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+    /// Runs of digits compare by value, so `file2` comes before `file10`.
+    Natural,
+    CaseInsensitive,
+    /// By the number each line starts with; lines without one go last.
+    Numeric,
+}
+
+/// Moves the cursor and any selection by `delta` after an edit.
+fn shift_selection(
+    editor: &mut TextEditor,
+    buf: &mut TextBuffer,
+    selection: Option<(i32, i32)>,
+    cursor: i32,
+    delta: i32,
+) {
+    match selection {
+        Some((start, end)) => buf.select(start + delta, end + delta),
+        None => buf.unselect(),
+    }
+    editor.set_insert_position(cursor + delta);
+    editor.show_insert_position();
+}
+
+/// Replaces the selected lines, or the cursor's line, with `rewrite` of them
+/// as one undo step.
+fn rewrite_lines(
+    editor: &mut TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    rewrite: &dyn Fn(Vec<String>) -> Vec<String>,
+) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let (start, end) = selected_lines(editor, &buf);
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let lines = text.split('\n').map(str::to_string).collect();
+    let new_text = rewrite(lines).join("\n");
+    if new_text != text {
+        buf.replace(start, end, &new_text);
+        buf.select(start, start + new_text.len() as i32);
+    }
+}
+
+/// Splits `s` into runs of digits and runs of everything else.
+fn chunks(s: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let mut start = 0;
+    for (idx, c) in s.char_indices().skip(1) {
+        let prev = s[..idx].chars().next_back().unwrap_or_default();
+        if c.is_ascii_digit() != prev.is_ascii_digit() {
+            out.push(&s[start..idx]);
+            start = idx;
+        }
+    }
+    if start < s.len() {
+        out.push(&s[start..]);
+    }
+    out
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    for (x, y) in chunks(a).into_iter().zip(chunks(b)) {
+        let order = if x.starts_with(|c: char| c.is_ascii_digit())
+            && y.starts_with(|c: char| c.is_ascii_digit())
+        {
+            let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+            x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+        } else {
+            x.to_lowercase().cmp(&y.to_lowercase())
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// The number a line starts with, after any indentation.
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let end = line
+        .char_indices()
+        .take_while(|(idx, c)| c.is_ascii_digit() || *c == '.' || (*idx == 0 && "+-".contains(*c)))
+        .last()
+        .map_or(0, |(idx, c)| idx + c.len_utf8());
+    line[..end].parse().ok()
+}
+
+fn sort(lines: &mut [String], order: SortOrder) {
+    match order {
+        SortOrder::Ascending => lines.sort(),
+        SortOrder::Descending => lines.sort_by(|a, b| b.cmp(a)),
+        SortOrder::Natural => lines.sort_by(|a, b| natural_cmp(a, b)),
+        SortOrder::CaseInsensitive => lines.sort_by_cached_key(|l| l.to_lowercase()),
+        SortOrder::Numeric => lines.sort_by(|a, b| match (leading_number(a), leading_number(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }),
+    }
+}
+
+/// Fisher-Yates with a xorshift generator seeded from the std hasher's random keys.
+fn shuffle(lines: &mut [String]) {
+    let mut seed = RandomState::new().build_hasher().finish() | 1;
+    for i in (1..lines.len()).rev() {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        lines.swap(i, (seed % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Sorts the selected lines.
+pub fn sort_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>, order: SortOrder) {
+    rewrite_lines(editor, state, &|mut lines| {
+        sort(&mut lines, order);
+        lines
+    });
+}
+
+pub fn reverse_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    rewrite_lines(editor, state, &|lines| lines.into_iter().rev().collect());
+}
+
+/// Keeps the first of each set of identical lines.
+pub fn remove_duplicate_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    rewrite_lines(editor, state, &|lines| {
+        let mut seen = HashSet::new();
+        lines
+            .into_iter()
+            .filter(|l| seen.insert(l.clone()))
+            .collect()
+    });
+}
+
+pub fn shuffle_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    rewrite_lines(editor, state, &|mut lines| {
+        shuffle(&mut lines);
+        lines
+    });
+}
+
+/// Copies the selected lines below themselves and moves the selection onto the copy.
+pub fn duplicate_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let (start, end) = selected_lines(editor, &buf);
+    let block = buf.text_range(start, end).unwrap_or_default();
+    let (selection, cursor) = (buf.selection_position(), editor.insert_position());
+
+    buf.insert(end, &format!("\n{}", block));
+    shift_selection(editor, &mut buf, selection, cursor, block.len() as i32 + 1);
+}
+
+pub fn delete_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let (start, end) = selected_lines(editor, &buf);
+    let (from, to) = if end < buf.length() {
+        (start, end + 1)
+    } else {
+        ((start - 1).max(0), end)
+    };
+    buf.remove(from, to);
+    buf.unselect();
+    editor.set_insert_position(buf.line_start(from));
+    editor.show_insert_position();
+}
+
+/// Swaps the selected lines with the line above or below them.
+pub fn move_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>, up: bool) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let (start, end) = selected_lines(editor, &buf);
+    let block = buf.text_range(start, end).unwrap_or_default();
+    let (selection, cursor) = (buf.selection_position(), editor.insert_position());
+
+    let delta = if up {
+        if start == 0 {
+            return;
+        }
+        let above = buf.line_start(start - 1);
+        let line = buf.text_range(above, start - 1).unwrap_or_default();
+        buf.replace(above, end, &format!("{}\n{}", block, line));
+        -(line.len() as i32 + 1)
+    } else {
+        if end >= buf.length() {
+            return;
+        }
+        let below = buf.line_end(end + 1);
+        let line = buf.text_range(end + 1, below).unwrap_or_default();
+        buf.replace(start, below, &format!("{}\n{}", line, block));
+        line.len() as i32 + 1
+    };
+    shift_selection(editor, &mut buf, selection, cursor, delta);
+}
+
+/// Joins the selected lines, or the cursor's line and the next, with single spaces.
+pub fn join_lines(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let spans = selection_spans_lines(&buf);
+    let (start, mut end) = selected_lines(editor, &buf);
+    if !spans {
+        if end >= buf.length() {
+            return;
+        }
+        end = buf.line_end(end + 1);
+    }
+
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let mut lines = text.split('\n');
+    let mut joined = lines.next().unwrap_or_default().trim_end().to_string();
+    let join_point = joined.len() as i32;
+    for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+        joined.push(' ');
+        joined.push_str(line);
+    }
+
+    buf.replace(start, end, &joined);
+    if spans {
+        buf.select(start, start + joined.len() as i32);
+        editor.set_insert_position(start + joined.len() as i32);
+    } else {
+        editor.set_insert_position(start + join_point);
+    }
+    editor.show_insert_position();
+}
+
+/// Puts each `separator`-delimited piece of the selection, or of the cursor's
+/// line, on a line of its own with the line's indentation.
+pub fn split_selection(editor: &mut TextEditor, state: &Rc<RefCell<AppState>>, separator: &str) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    if separator.is_empty() {
+        return;
+    }
+    let (start, end) = match buf.selection_position() {
+        Some(range) => range,
+        None => selected_lines(editor, &buf),
+    };
+    let line_start = buf.line_start(start);
+    let line = buf
+        .text_range(line_start, buf.line_end(start))
+        .unwrap_or_default();
+    let lead = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+    // Splitting the whole line keeps its indentation on the first piece.
+    let start = start.max(line_start + lead.len() as i32);
+
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let pieces: Vec<&str> = text
+        .split(separator)
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    let new_text = pieces.join(&format!("\n{}", lead));
+    if new_text == text {
+        return;
+    }
+    buf.replace(start, end, &new_text);
+    buf.select(start, start + new_text.len() as i32);
+    editor.set_insert_position(start + new_text.len() as i32);
+    editor.show_insert_position();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(lines: &[&str], order: SortOrder) -> Vec<String> {
+        let mut lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        sort(&mut lines, order);
+        lines
+    }
+
+    #[test]
+    fn chunks_split_digit_runs() {
+        assert_eq!(chunks("file10b2"), ["file", "10", "b", "2"]);
+        assert_eq!(chunks("12"), ["12"]);
+        assert!(chunks("").is_empty());
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        assert_eq!(
+            sorted(&["a10", "b", "a2", "A3", "a1"], SortOrder::Natural),
+            ["a1", "a2", "A3", "a10", "b"]
+        );
+        assert_eq!(natural_cmp("file2", "file02"), Ordering::Less);
+        assert_eq!(natural_cmp("x9", "x10"), Ordering::Less);
+        assert_eq!(natural_cmp("same", "same"), Ordering::Equal);
+    }
+
+    #[test]
+    fn leading_numbers() {
+        assert_eq!(leading_number("  42 apples"), Some(42.0));
+        assert_eq!(leading_number("-3.5x"), Some(-3.5));
+        assert_eq!(leading_number("+7"), Some(7.0));
+        assert_eq!(leading_number("x1"), None);
+        assert_eq!(leading_number("-"), None);
+    }
+
+    #[test]
+    fn numeric_order_puts_other_lines_last() {
+        assert_eq!(
+            sorted(&["10 b", "x", "2 a", "-1 c", "y"], SortOrder::Numeric),
+            ["-1 c", "2 a", "10 b", "x", "y"]
+        );
+    }
+
+    #[test]
+    fn plain_orders() {
+        let lines = ["b", "A", "a", "B"];
+        assert_eq!(sorted(&lines, SortOrder::Ascending), ["A", "B", "a", "b"]);
+        assert_eq!(sorted(&lines, SortOrder::Descending), ["b", "a", "B", "A"]);
+        assert_eq!(
+            sorted(&lines, SortOrder::CaseInsensitive),
+            ["A", "a", "b", "B"]
+        );
+    }
+
+    #[test]
+    fn shuffle_keeps_every_line() {
+        let mut lines: Vec<String> = (0..50).map(|n| n.to_string()).collect();
+        shuffle(&mut lines);
+        lines.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(lines, (0..50).map(|n| n.to_string()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,320 @@
+use fltk::{
+    app, draw,
+    enums::{Color, Event},
+    frame::Frame,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use super::filter::FilterView;
+use super::goto::{GotoTarget, resolve_goto};
+use super::highlight::SharedHighlighter;
+use super::search::GotoMatch;
+use super::state::AppState;
+use super::status::UpdateStatus;
+use super::theme::Theme;
+
+pub const MARKER_STRIP_WIDTH: i32 = 10;
+/// How close (in pixels) a click must land to a tick to jump to it.
+const CLICK_SLOP: i32 = 4;
+
+pub type MarkerStrip = Rc<RefCell<Frame>>;
+
+/// Kinds of ticks drawn beside the scrollbar, in drawing order.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Modified,
+    Bookmark,
+    Match,
+    CurrentMatch,
+}
+
+impl MarkerKind {
+    fn color(self, theme: &Theme) -> Color {
+        match self {
+            MarkerKind::Modified => theme.modified_marker,
+            MarkerKind::Bookmark => theme.bookmark,
+            MarkerKind::Match => theme.match_marker,
+            MarkerKind::CurrentMatch => theme.current_match_marker,
+        }
+    }
+}
+
+struct Marker {
+    row: usize,
+    kind: MarkerKind,
+    /// Index into `SearchState::results` for match ticks.
+    result: Option<usize>,
+}
+
+/// Per-line marks on the document that follow the text as lines are added or removed.
+#[derive(Default)]
+pub struct LineMarks {
+    modified: Vec<bool>,
+    bookmarks: Vec<bool>,
+}
+
+fn splice_lines(marks: &mut Vec<bool>, line: usize, added: usize, removed: usize, fill: bool) {
+    if marks.len() <= line + removed {
+        marks.resize(line + removed + 1, false);
+    }
+    marks.splice(
+        line + 1..line + 1 + removed,
+        std::iter::repeat_n(fill, added),
+    );
+}
+
+fn marked_lines(marks: &[bool]) -> impl Iterator<Item = usize> + '_ {
+    marks
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| **m)
+        .map(|(line, _)| line)
+}
+
+impl LineMarks {
+    pub fn reset(&mut self, line_count: usize) {
+        self.modified = vec![false; line_count.max(1)];
+        self.bookmarks = vec![false; line_count.max(1)];
+    }
+
+    /// Records an edit on `line` that removed `removed` and added `added` line breaks.
+    pub fn edit(&mut self, line: usize, added: usize, removed: usize) {
+        splice_lines(&mut self.modified, line, added, removed, true);
+        splice_lines(&mut self.bookmarks, line, added, removed, false);
+        self.modified[line] = true;
+    }
+
+    pub fn modified_lines(&self) -> impl Iterator<Item = usize> + '_ {
+        marked_lines(&self.modified)
+    }
+
+    pub fn bookmarked_lines(&self) -> impl Iterator<Item = usize> + '_ {
+        marked_lines(&self.bookmarks)
+    }
+
+    pub fn has_bookmarks(&self) -> bool {
+        self.bookmarks.contains(&true)
+    }
+
+    pub fn is_bookmarked(&self, line: usize) -> bool {
+        self.bookmarks.get(line).copied().unwrap_or(false)
+    }
+
+    pub fn toggle_bookmark(&mut self, line: usize) {
+        if self.bookmarks.len() <= line {
+            self.bookmarks.resize(line + 1, false);
+        }
+        self.bookmarks[line] = !self.bookmarks[line];
+    }
+
+    pub fn set_bookmarks(&mut self, lines: &[usize]) {
+        self.clear_bookmarks();
+        for line in lines {
+            self.toggle_bookmark(*line);
+        }
+    }
+
+    pub fn clear_bookmarks(&mut self) {
+        self.bookmarks.iter_mut().for_each(|b| *b = false);
+    }
+}
+
+pub fn attach_line_mark_tracking(buf: &Rc<RefCell<TextBuffer>>, state: &Rc<RefCell<AppState>>) {
+    let state = Rc::clone(state);
+    buf.borrow_mut()
+        .add_modify_callback2(move |b, pos, inserted, deleted, _, deleted_text| {
+            if inserted == 0 && deleted == 0 {
+                return;
+            }
+            let line = b.count_lines(0, pos) as usize;
+            let added = b.count_lines(pos, pos + inserted) as usize;
+            let removed = deleted_text.matches('\n').count();
+            if let Ok(mut s) = state.try_borrow_mut() {
+                s.document.marks.edit(line, added, removed);
+            }
+        });
+}
+
+pub fn create_marker_strip(x: i32, y: i32, h: i32) -> MarkerStrip {
+    Rc::new(RefCell::new(Frame::new(x, y, MARKER_STRIP_WIDTH, h, "")))
+}
+
+/// Maps ascending document lines to rows of the filtered view, dropping hidden ones.
+fn visible_rows(lines: &[usize], view: Option<&FilterView>) -> Vec<Option<usize>> {
+    let Some(view) = view else {
+        return lines.iter().map(|l| Some(*l)).collect();
+    };
+
+    let mut row = 0;
+    lines
+        .iter()
+        .map(|line| {
+            while row < view.line_map.len() && view.line_map[row].is_none_or(|l| l < *line) {
+                row += 1;
+            }
+            (view.line_map.get(row).copied().flatten() == Some(*line)).then_some(row)
+        })
+        .collect()
+}
+
+fn collect_markers(state: &AppState, buf: &TextBuffer) -> (Vec<Marker>, usize) {
+    let view = state.document.filter.view.as_ref();
+    let rows = match view {
+        Some(view) => view.line_map.len(),
+        None => buf.count_lines(0, buf.length()) as usize + 1,
+    };
+    let mut markers = vec![];
+
+    let modified: Vec<usize> = state.document.marks.modified_lines().collect();
+    for row in visible_rows(&modified, view).into_iter().flatten() {
+        markers.push(Marker {
+            row,
+            kind: MarkerKind::Modified,
+            result: None,
+        });
+    }
+
+    let bookmarks: Vec<usize> = state.document.marks.bookmarked_lines().collect();
+    for row in visible_rows(&bookmarks, view).into_iter().flatten() {
+        markers.push(Marker {
+            row,
+            kind: MarkerKind::Bookmark,
+            result: None,
+        });
+    }
+
+    let mut line = 0;
+    let mut prev = 0;
+    let match_lines: Vec<usize> = state
+        .search
+        .results
+        .iter()
+        .map(|(start, _)| {
+            line += buf.count_lines(prev, *start) as usize;
+            prev = *start;
+            line
+        })
+        .collect();
+    for (idx, row) in visible_rows(&match_lines, view).into_iter().enumerate() {
+        let Some(row) = row else {
+            continue;
+        };
+        let kind = if idx == state.search.current {
+            MarkerKind::CurrentMatch
+        } else {
+            MarkerKind::Match
+        };
+        markers.push(Marker {
+            row,
+            kind,
+            result: Some(idx),
+        });
+    }
+
+    // Draw the current match last so it stays visible on top of its neighbours.
+    markers.sort_by_key(|m| m.kind == MarkerKind::CurrentMatch);
+    (markers, rows.max(1))
+}
+
+/// The track the ticks are spread over, leaving out the scrollbar arrow buttons.
+fn track(f: &Frame, editor: &TextEditor) -> (i32, i32) {
+    let arrow = editor.scrollbar_size();
+    (f.y() + arrow, (f.h() - 2 * arrow).max(1))
+}
+
+fn marker_y(row: usize, rows: usize, track_y: i32, track_h: i32) -> i32 {
+    track_y + (row as f64 / rows as f64 * track_h as f64) as i32
+}
+
+pub fn attach_marker_strip_logic(
+    strip: &MarkerStrip,
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+    goto: GotoMatch,
+    update_status: UpdateStatus,
+) {
+    // Search refreshes restyle the style buffer and edits touch the text buffer,
+    // so either one changing means the ticks may have moved.
+    let stylebuf = highlighter.borrow().stylebuf();
+    for mut b in [buf.borrow().clone(), stylebuf] {
+        let strip = Rc::clone(strip);
+        b.add_modify_callback(move |_, _, _, _, _| {
+            if let Ok(mut f) = strip.try_borrow_mut() {
+                f.redraw();
+            }
+        });
+    }
+
+    {
+        let editor = editor.clone();
+        let buf = Rc::clone(buf);
+        let state = Rc::clone(state);
+
+        strip.borrow_mut().draw(move |f| {
+            draw::draw_rect_fill(f.x(), f.y(), f.w(), f.h(), f.color());
+            let (Ok(s), Ok(b)) = (state.try_borrow(), buf.try_borrow()) else {
+                return;
+            };
+
+            let (markers, rows) = collect_markers(&s, &b);
+            let (track_y, track_h) = track(f, &editor);
+            for m in markers {
+                let y = marker_y(m.row, rows, track_y, track_h);
+                let (x, w) = match m.kind {
+                    MarkerKind::Modified => (f.x(), 3),
+                    _ => (f.x() + 2, f.w() - 3),
+                };
+                draw::draw_rect_fill(x, y, w, 3, m.kind.color(&s.theme));
+            }
+        });
+    }
+
+    let editor = editor.clone();
+    let buf = Rc::clone(buf);
+    let state = Rc::clone(state);
+
+    strip.borrow_mut().handle(move |f, ev| {
+        if ev != Event::Push || app::event_mouse_button() != app::MouseButton::Left {
+            return false;
+        }
+
+        let (track_y, track_h) = track(f, &editor);
+        let click_y = app::event_y();
+        let (markers, rows) = collect_markers(&state.borrow(), &buf.borrow());
+        let Some(nearest) = markers
+            .iter()
+            .min_by_key(|m| (marker_y(m.row, rows, track_y, track_h) - click_y).abs())
+            .filter(|m| (marker_y(m.row, rows, track_y, track_h) - click_y).abs() <= CLICK_SLOP)
+        else {
+            return true;
+        };
+
+        if let Some(idx) = nearest.result {
+            let target = {
+                let mut s = state.borrow_mut();
+                s.search.current = idx;
+                s.search.results[idx]
+            };
+            goto(target.0, target.1);
+        } else {
+            let mut ed = editor.clone();
+            let pos = match &state.borrow().document.filter.view {
+                Some(view) => view.line_starts[nearest.row],
+                // The editor would count wrapped rows; go by buffer lines.
+                None => {
+                    let text = buf.borrow().text();
+                    resolve_goto(&text, 0, &GotoTarget::Line(nearest.row + 1, None)) as i32
+                }
+            };
+            ed.set_insert_position(pos);
+            ed.show_insert_position();
+            update_status();
+        }
+        let _ = editor.clone().take_focus();
+        true
+    });
+}
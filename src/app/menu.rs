@@ -1,53 +1,71 @@
 use fltk::prelude::MenuExt;
-use fltk::{enums::*, menu::*, prelude::*, text::TextBuffer};
+use fltk::{
+    enums::*,
+    menu::*,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
 use rfd::FileDialog;
 use std::{
     cell::RefCell,
+    collections::HashSet,
     env, fs,
     path::{Path, PathBuf},
     rc::Rc,
 };
 
-use super::load_as_utf8;
+use super::config::{config_file, write_config_file};
+use super::edit::replace_changed_range;
+use super::editorconfig::EditorConfig;
+use super::encoding::{encode_text, load_as_utf8};
 use super::{
-    SearchState,
-    search::{SearchControls, update_result_status},
-    status::{hide_search_controls, show_search_controls},
+    bookmarks::{
+        BookmarksUi, clear_bookmarks, load_bookmarks, save_current_bookmarks, step_bookmark,
+        toggle_bookmark,
+    },
+    brackets::{jump_to_match, select_to_match, update_bracket_match},
+    data_format::{format_data, validate_data},
+    dock::{Relayout, SharedDock, toggle_dock_page},
+    encode::{Codec, convert_selection},
+    filter::{FilterUi, LineFilter, apply_filters},
+    find_in_files::{FindInFilesUi, show_find_in_files_dialog},
+    fonts::{FontUi, show_font_dialog, zoom},
+    goto::GotoLine,
+    gutter::{SharedGutter, update_gutter_width},
+    highlight::SharedHighlighter,
+    history::HistoryKind,
+    indent::{Indent, TAB_WIDTHS, apply_indent, convert_document, detect_indent, document_indent},
+    lines::{
+        LineCommand, SortOrder, delete_lines, duplicate_lines, join_lines, move_lines,
+        remove_duplicate_lines, reverse_lines, shuffle_lines, sort_lines, split_selection,
+    },
+    palette::show_command_palette,
+    preferences::{
+        MAX_WRAP_COLUMN, MIN_WRAP_COLUMN, PreferencesUi, apply_file_settings,
+        show_preferences_dialog,
+    },
+    results::ResultsPanel,
+    save_actions::SaveActions,
+    search::{GotoMatch, SearchControls, SearchOptions, apply_search, build_regex, step_match},
+    settings::{EditorSettings, Wrap},
+    state::AppState,
+    status::{UpdateStatus, hide_search_controls, show_search_controls},
+    theme::{ThemeUi, apply_current_theme},
+    transform::{Transform, transform_selection},
 };
 
-const MAX_RECENT_FILES: usize = 10;
+pub type OpenPath = Rc<dyn Fn(&str) -> bool>;
 
 fn recent_files_store_path() -> PathBuf {
-    #[cfg(target_os = "windows")]
-    {
-        if let Ok(appdata) = env::var("APPDATA") {
-            return Path::new(&appdata)
-                .join("plain_text")
-                .join("recent_files.txt");
-        }
-    }
-
-    if let Ok(home) = env::var("HOME") {
-        return Path::new(&home)
-            .join(".config")
-            .join("plain_text")
-            .join("recent_files.txt");
-    }
-
-    Path::new("recent_files.txt").to_path_buf()
+    config_file("recent_files.txt")
 }
 
-fn save_recent_files(state: &Rc<RefCell<SearchState>>) {
-    let path = recent_files_store_path();
-    if let Some(parent) = path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-
+fn save_recent_files(state: &Rc<RefCell<AppState>>) {
     let content = state.borrow().recent_files.join("\n");
-    let _ = fs::write(path, content);
+    write_config_file("recent_files.txt", &content);
 }
 
-pub fn load_recent_files_into_state(state: &Rc<RefCell<SearchState>>) {
+pub fn load_recent_files_into_state(state: &Rc<RefCell<AppState>>) {
     let path = recent_files_store_path();
     let Ok(content) = fs::read_to_string(path) else {
         return;
@@ -61,11 +79,9 @@ pub fn load_recent_files_into_state(state: &Rc<RefCell<SearchState>>) {
         .collect();
 
     recents.dedup();
-    if recents.len() > MAX_RECENT_FILES {
-        recents.truncate(MAX_RECENT_FILES);
-    }
-
-    state.borrow_mut().recent_files = recents;
+    let mut s = state.borrow_mut();
+    recents.truncate(s.settings.files.max_recent_files);
+    s.recent_files = recents;
 }
 
 fn recent_item_label(path: &str, index: usize) -> String {
@@ -78,13 +94,12 @@ fn recent_item_label(path: &str, index: usize) -> String {
     format!("{}. {}", index + 1, name)
 }
 
-fn remember_recent_path(state: &Rc<RefCell<SearchState>>, path: &str) {
+fn remember_recent_path(state: &Rc<RefCell<AppState>>, path: &str) {
     let mut s = state.borrow_mut();
     s.recent_files.retain(|p| p != path);
     s.recent_files.insert(0, path.to_string());
-    if s.recent_files.len() > MAX_RECENT_FILES {
-        s.recent_files.truncate(MAX_RECENT_FILES);
-    }
+    let max = s.settings.files.max_recent_files;
+    s.recent_files.truncate(max);
     drop(s);
     save_recent_files(state);
 }
@@ -92,28 +107,77 @@ fn remember_recent_path(state: &Rc<RefCell<SearchState>>, path: &str) {
 fn open_path_into_editor(
     path: &str,
     buf: &Rc<RefCell<TextBuffer>>,
-    stylebuf: &Rc<RefCell<TextBuffer>>,
-    state: &Rc<RefCell<SearchState>>,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
     update_status: &dyn Fn(),
 ) -> bool {
     if let Some(text) = load_as_utf8(path) {
-        let len = text.len();
-
+        highlighter.borrow_mut().select_language(path, &text);
         buf.borrow_mut().set_text(&text);
-        stylebuf.borrow_mut().set_text(&"A".repeat(len.max(1)));
 
-        state.borrow_mut().filepath = path.to_string();
+        let mut s = state.borrow_mut();
+        s.document.indent = document_indent(&s.settings.editor_for(path), &text);
+        s.document.editorconfig = EditorConfig::for_path(path);
+        let editorconfig = s.document.editorconfig.clone();
+        editorconfig.apply_indent(&mut s.document.indent);
+        s.document.filepath = path.to_string();
+        s.document.modified = false;
+        s.document.marks.reset(text.matches('\n').count() + 1);
+        s.document.marks.set_bookmarks(&load_bookmarks(path));
+        drop(s);
         update_status();
         return true;
     }
     false
 }
 
+/// Saves the document to `path`, first running the save actions for it on the
+/// buffer as one undo step so what is on screen matches what is written.
+fn write_document(
+    path: &str,
+    buf: &Rc<RefCell<TextBuffer>>,
+    state: &Rc<RefCell<AppState>>,
+) -> bool {
+    let editorconfig = EditorConfig::for_path(path);
+    let text = {
+        let s = state.borrow();
+        let actions = SaveActions::new(&s.settings.on_save_for(path), &editorconfig);
+        let modified: HashSet<usize> = s.document.marks.modified_lines().collect();
+        drop(s);
+
+        let mut b = buf.borrow_mut();
+        let text = actions.apply(&b.text(), &|line| modified.contains(&line));
+        replace_changed_range(&mut b, &text);
+        text
+    };
+
+    let bytes = match editorconfig.charset {
+        Some((encoding, bom)) => encode_text(&text, encoding, bom),
+        None => text.into_bytes(),
+    };
+    let written = fs::write(path, bytes).is_ok();
+    state.borrow_mut().document.editorconfig = editorconfig;
+    written
+}
+
+pub fn attach_modified_tracking(buf: &Rc<RefCell<TextBuffer>>, state: &Rc<RefCell<AppState>>) {
+    let state = Rc::clone(state);
+    buf.borrow_mut()
+        .add_modify_callback(move |_, inserted, deleted, _, _| {
+            if inserted == 0 && deleted == 0 {
+                return;
+            }
+            if let Ok(mut s) = state.try_borrow_mut() {
+                s.document.modified = true;
+            }
+        });
+}
+
 fn refresh_recent_menu<F>(
     menu: &mut MenuBar,
     buf: &Rc<RefCell<TextBuffer>>,
-    stylebuf: &Rc<RefCell<TextBuffer>>,
-    state: &Rc<RefCell<SearchState>>,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
     update_status: &F,
 ) where
     F: Fn() + Clone + 'static,
@@ -143,7 +207,7 @@ fn refresh_recent_menu<F>(
         let label = recent_item_label(&path, idx);
 
         let buf = Rc::clone(buf);
-        let stylebuf = Rc::clone(stylebuf);
+        let highlighter = Rc::clone(highlighter);
         let state = Rc::clone(state);
         let update_status = update_status.clone();
         let mut menu_ref = menu.clone();
@@ -153,9 +217,9 @@ fn refresh_recent_menu<F>(
             Shortcut::None,
             MenuFlag::Normal,
             move |_| {
-                if open_path_into_editor(&path, &buf, &stylebuf, &state, &update_status) {
+                if open_path_into_editor(&path, &buf, &highlighter, &state, &update_status) {
                     remember_recent_path(&state, &path);
-                    refresh_recent_menu(&mut menu_ref, &buf, &stylebuf, &state, &update_status);
+                    refresh_recent_menu(&mut menu_ref, &buf, &highlighter, &state, &update_status);
                 }
             },
         );
@@ -165,31 +229,56 @@ fn refresh_recent_menu<F>(
 pub fn remember_recent_and_refresh<F>(
     menu: &mut MenuBar,
     buf: &Rc<RefCell<TextBuffer>>,
-    stylebuf: &Rc<RefCell<TextBuffer>>,
-    state: &Rc<RefCell<SearchState>>,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
     update_status: &F,
     path: &str,
 ) where
     F: Fn() + Clone + 'static,
 {
     remember_recent_path(state, path);
-    refresh_recent_menu(menu, buf, stylebuf, state, update_status);
+    refresh_recent_menu(menu, buf, highlighter, state, update_status);
+}
+
+pub fn make_open_path<F>(
+    menu: &MenuBar,
+    buf: &Rc<RefCell<TextBuffer>>,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+    update_status: F,
+) -> OpenPath
+where
+    F: Fn() + Clone + 'static,
+{
+    let menu = menu.clone();
+    let buf = Rc::clone(buf);
+    let highlighter = Rc::clone(highlighter);
+    let state = Rc::clone(state);
+
+    Rc::new(move |path: &str| {
+        if !open_path_into_editor(path, &buf, &highlighter, &state, &update_status) {
+            return false;
+        }
+        let mut menu = menu.clone();
+        remember_recent_and_refresh(&mut menu, &buf, &highlighter, &state, &update_status, path);
+        true
+    })
 }
 
 pub fn add_file_menu_items<F>(
     menu: &mut MenuBar,
     buf: &Rc<RefCell<TextBuffer>>,
-    stylebuf: &Rc<RefCell<TextBuffer>>,
-    state: &Rc<RefCell<SearchState>>,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
     update_status: F,
 ) where
     F: Fn() + Clone + 'static,
 {
-    refresh_recent_menu(menu, buf, stylebuf, state, &update_status);
+    refresh_recent_menu(menu, buf, highlighter, state, &update_status);
 
     {
         let buf = Rc::clone(buf);
-        let stylebuf = Rc::clone(stylebuf);
+        let highlighter = Rc::clone(highlighter);
         let state = Rc::clone(state);
         let update_status_open = update_status.clone();
         let mut menu_ref = menu.clone();
@@ -201,11 +290,12 @@ pub fn add_file_menu_items<F>(
             move |_| {
                 if let Some(path) = FileDialog::new().pick_file() {
                     let path = path.to_string_lossy().to_string();
-                    if open_path_into_editor(&path, &buf, &stylebuf, &state, &update_status_open) {
+                    if open_path_into_editor(&path, &buf, &highlighter, &state, &update_status_open)
+                    {
                         remember_recent_and_refresh(
                             &mut menu_ref,
                             &buf,
-                            &stylebuf,
+                            &highlighter,
                             &state,
                             &update_status_open,
                             &path,
@@ -226,7 +316,7 @@ pub fn add_file_menu_items<F>(
             Shortcut::Ctrl | 's',
             MenuFlag::Normal,
             move |_| {
-                let current_path = state.borrow().filepath.clone();
+                let current_path = state.borrow().document.filepath.clone();
 
                 let path = if current_path.is_empty() {
                     FileDialog::new()
@@ -237,11 +327,12 @@ pub fn add_file_menu_items<F>(
                 };
 
                 if let Some(path) = path {
-                    let text = buf.borrow().text();
-                    let utf8 = text.as_bytes();
-                    fs::write(&path, utf8).ok();
+                    if write_document(&path, &buf, &state) {
+                        state.borrow_mut().document.modified = false;
+                    }
 
-                    state.borrow_mut().filepath = path;
+                    state.borrow_mut().document.filepath = path;
+                    save_current_bookmarks(&state);
                     update_status_save();
                 }
             },
@@ -250,6 +341,7 @@ pub fn add_file_menu_items<F>(
 
     {
         let buf = Rc::clone(buf);
+        let highlighter = Rc::clone(highlighter);
         let state = Rc::clone(state);
         let update_status_saveas = update_status.clone();
 
@@ -259,11 +351,20 @@ pub fn add_file_menu_items<F>(
             MenuFlag::Normal,
             move |_| {
                 if let Some(path) = FileDialog::new().save_file() {
+                    let path = path.to_string_lossy().to_string();
+                    if write_document(&path, &buf, &state) {
+                        state.borrow_mut().document.modified = false;
+                    }
+
                     let text = buf.borrow().text();
-                    let utf8 = text.as_bytes();
-                    fs::write(&*path.to_string_lossy(), utf8).ok();
+                    let mut h = highlighter.borrow_mut();
+                    if h.select_language(&path, &text) {
+                        h.rehighlight(&buf.borrow());
+                    }
+                    drop(h);
 
-                    state.borrow_mut().filepath = path.to_string_lossy().to_string();
+                    state.borrow_mut().document.filepath = path;
+                    save_current_bookmarks(&state);
                     update_status_saveas();
                 }
             },
@@ -272,7 +373,7 @@ pub fn add_file_menu_items<F>(
 
     {
         let buf = Rc::clone(buf);
-        let stylebuf = Rc::clone(stylebuf);
+        let highlighter = Rc::clone(highlighter);
         let state = Rc::clone(state);
         let update_status_new = update_status.clone();
 
@@ -281,9 +382,15 @@ pub fn add_file_menu_items<F>(
             Shortcut::Ctrl | 'n',
             MenuFlag::Normal,
             move |_| {
+                highlighter.borrow_mut().select_language("", "");
                 buf.borrow_mut().set_text("");
-                stylebuf.borrow_mut().set_text("A");
-                state.borrow_mut().filepath.clear(); // mark as new file
+                let mut s = state.borrow_mut();
+                s.document.indent = Indent::from_settings(&s.settings.editor);
+                s.document.editorconfig = EditorConfig::default();
+                s.document.filepath.clear(); // mark as new file
+                s.document.modified = false;
+                s.document.marks.reset(1);
+                drop(s);
                 update_status_new();
             },
         );
@@ -299,10 +406,9 @@ pub fn add_file_menu_items<F>(
 
 pub fn add_search_menu<F>(
     menu: &mut MenuBar,
-    state: &Rc<RefCell<SearchState>>,
+    state: &Rc<RefCell<AppState>>,
     controls: &Rc<RefCell<SearchControls>>,
-    editor: &fltk::text::TextEditor,
-    buf: &Rc<RefCell<fltk::text::TextBuffer>>,
+    goto: &GotoMatch,
     update_status: F,
 ) where
     F: Fn() + Clone + 'static,
@@ -318,13 +424,18 @@ pub fn add_search_menu<F>(
             MenuFlag::Normal,
             move |_| {
                 let mut s = state.borrow_mut();
-                s.visible = !s.visible;
-                if s.visible {
-                    s.current = 0;
+                s.search.visible = !s.search.visible;
+                if s.search.visible {
+                    s.search.current = 0;
                     let mut sc = controls.borrow_mut();
                     show_search_controls(&mut sc);
                     sc.input.take_focus().ok();
                 } else {
+                    let pattern = s.search.pattern.clone();
+                    let options = s.search.options;
+                    s.search
+                        .history
+                        .record(HistoryKind::Search, &pattern, options);
                     hide_search_controls(&mut controls.borrow_mut());
                 }
                 update_status();
@@ -332,31 +443,28 @@ pub fn add_search_menu<F>(
         );
     }
 
+    add_search_option_toggle(menu, "Search/Match Case", state, controls, |o, v| {
+        o.case_sensitive = v
+    });
+    add_search_option_toggle(
+        menu,
+        "Search/Regular Expression",
+        state,
+        controls,
+        |o, v| o.regex = v,
+    );
+
     {
         let state = Rc::clone(state);
-        let controls = Rc::clone(controls);
-        let mut editor = editor.clone();
-        let buf = Rc::clone(buf);
-        let update_status = update_status.clone();
+        let goto = Rc::clone(goto);
 
         menu.add(
             "Search/Previous Match\t",
             Shortcut::Ctrl | 'j',
             MenuFlag::Normal,
             move |_| {
-                let mut s = state.borrow_mut();
-                if !s.results.is_empty() {
-                    if s.current == 0 {
-                        s.current = s.results.len() - 1;
-                    } else {
-                        s.current -= 1;
-                    }
-                    let (start, end) = s.results[s.current];
-                    editor.set_insert_position(start);
-                    editor.show_insert_position();
-                    buf.borrow_mut().select(start, end);
-                    update_result_status(&controls.borrow().results, &s);
-                    update_status();
+                if let Some((start, end)) = step_match(&state, false) {
+                    goto(start, end);
                 }
             },
         );
@@ -364,27 +472,1054 @@ pub fn add_search_menu<F>(
 
     {
         let state = Rc::clone(state);
-        let controls = Rc::clone(controls);
-        let mut editor = editor.clone();
-        let buf = Rc::clone(buf);
-        let update_status = update_status;
+        let goto = Rc::clone(goto);
 
         menu.add(
             "Search/Next Match\t",
             Shortcut::Ctrl | 'k',
             MenuFlag::Normal,
             move |_| {
-                let mut s = state.borrow_mut();
-                if !s.results.is_empty() {
-                    s.current = (s.current + 1) % s.results.len();
-                    let (start, end) = s.results[s.current];
-                    editor.set_insert_position(start);
-                    editor.show_insert_position();
-                    buf.borrow_mut().select(start, end);
-                    update_result_status(&controls.borrow().results, &s);
-                    update_status();
+                if let Some((start, end)) = step_match(&state, true) {
+                    goto(start, end);
+                }
+            },
+        );
+    }
+
+    refresh_saved_searches_menu(menu, state, controls);
+}
+
+pub fn sync_search_option_items(menu: &MenuBar, options: &SearchOptions) {
+    let items = [
+        ("Search/Match Case", options.case_sensitive),
+        ("Search/Regular Expression", options.regex),
+    ];
+    for (label, on) in items {
+        if let Some(mut item) = menu.find_item(label) {
+            if on {
+                item.set();
+            } else {
+                item.clear();
+            }
+        }
+    }
+}
+
+fn saved_item_label(name: &str) -> String {
+    name.replace('&', "&&").replace('/', "\\/")
+}
+
+fn refresh_saved_searches_menu(
+    menu: &mut MenuBar,
+    state: &Rc<RefCell<AppState>>,
+    controls: &Rc<RefCell<SearchControls>>,
+) {
+    let mut submenu_idx = menu.find_index("Search/Saved Searches");
+    if submenu_idx < 0 {
+        menu.add(
+            "Search/Saved Searches",
+            Shortcut::None,
+            MenuFlag::Submenu,
+            |_| {},
+        );
+        submenu_idx = menu.find_index("Search/Saved Searches");
+    }
+    if submenu_idx >= 0 {
+        let _ = menu.clear_submenu(submenu_idx);
+    }
+
+    {
+        let state = Rc::clone(state);
+        let controls = Rc::clone(controls);
+        let mut menu_ref = menu.clone();
+
+        menu.add(
+            "Search/Saved Searches/Save Current Search...",
+            Shortcut::None,
+            MenuFlag::MenuDivider,
+            move |_| {
+                let (pattern, options) = {
+                    let s = state.borrow();
+                    (s.search.pattern.clone(), s.search.options)
+                };
+                if pattern.is_empty() {
+                    fltk::dialog::alert_default("Type a search first.");
+                    return;
+                }
+
+                let Some(name) = fltk::dialog::input_default("Name for this search:", &pattern)
+                else {
+                    return;
+                };
+                let name = name.trim();
+                if name.is_empty() {
+                    return;
+                }
+
+                state
+                    .borrow_mut()
+                    .search
+                    .history
+                    .save_search(name, &pattern, options);
+                refresh_saved_searches_menu(&mut menu_ref, &state, &controls);
+            },
+        );
+    }
+
+    let saved = state.borrow().search.history.saved.clone();
+    if saved.is_empty() {
+        menu.add(
+            "Search/Saved Searches/(No saved searches)",
+            Shortcut::None,
+            MenuFlag::Inactive,
+            |_| {},
+        );
+        return;
+    }
+
+    for (idx, search) in saved.iter().enumerate() {
+        // The first nine saved searches are pinned to Ctrl+Alt+1..9.
+        let shortcut = match char::from_digit(idx as u32 + 1, 10) {
+            Some(digit) if idx < 9 => Shortcut::Ctrl | Shortcut::Alt | digit,
+            _ => Shortcut::None,
+        };
+
+        let state = Rc::clone(state);
+        let controls = Rc::clone(controls);
+        let menu_ref = menu.clone();
+        let search = search.clone();
+
+        menu.add(
+            &format!("Search/Saved Searches/{}", saved_item_label(&search.name)),
+            shortcut,
+            MenuFlag::Normal,
+            move |_| {
+                apply_search(&state, &controls, &search.pattern, search.options);
+                sync_search_option_items(&menu_ref, &search.options);
+            },
+        );
+    }
+
+    for search in saved {
+        let state = Rc::clone(state);
+        let controls = Rc::clone(controls);
+        let mut menu_ref = menu.clone();
+
+        menu.add(
+            &format!(
+                "Search/Saved Searches/Remove/{}",
+                saved_item_label(&search.name)
+            ),
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                state.borrow_mut().search.history.remove_saved(&search.name);
+                refresh_saved_searches_menu(&mut menu_ref, &state, &controls);
+            },
+        );
+    }
+}
+
+/// Reads a toggle from its own item; `mvalue()` is stale when the callback is
+/// run from the command palette.
+fn is_checked(menu: &MenuBar, label: &str) -> bool {
+    menu.find_item(label).is_some_and(|item| item.value())
+}
+
+fn add_search_option_toggle(
+    menu: &mut MenuBar,
+    label: &str,
+    state: &Rc<RefCell<AppState>>,
+    controls: &Rc<RefCell<SearchControls>>,
+    set: fn(&mut SearchOptions, bool),
+) {
+    let state = Rc::clone(state);
+    let controls = Rc::clone(controls);
+    let path = label.to_string();
+
+    menu.add(label, Shortcut::None, MenuFlag::Toggle, move |m| {
+        let checked = is_checked(m, &path);
+        set(&mut state.borrow_mut().search.options, checked);
+
+        // Re-run the search so the new option applies to the current pattern.
+        let mut input = controls.borrow().input.clone();
+        if !input.value().is_empty() {
+            input.do_callback();
+        }
+    });
+}
+
+pub fn add_find_in_files_menu(
+    menu: &mut MenuBar,
+    ui: &FindInFilesUi,
+    state: &Rc<RefCell<AppState>>,
+    buf: &Rc<RefCell<TextBuffer>>,
+) {
+    {
+        let ui = ui.clone();
+        let state = Rc::clone(state);
+        let buf = Rc::clone(buf);
+
+        menu.add(
+            "Search/Find in Files...\t",
+            Shortcut::Ctrl | Shortcut::Shift | 'f',
+            MenuFlag::Normal,
+            move |_| {
+                let s = state.borrow();
+                let root = Path::new(&s.document.filepath)
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(Path::to_path_buf)
+                    .or_else(|| env::current_dir().ok())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                let selection = buf.borrow().selection_text();
+                let pattern = if !selection.is_empty() && !selection.contains('\n') {
+                    selection
+                } else {
+                    s.search.pattern.clone()
+                };
+
+                show_find_in_files_dialog(&ui, &root, &pattern, s.search.options);
+            },
+        );
+    }
+
+    {
+        let ui = ui.clone();
+
+        menu.add(
+            "Search/Find in Files Panel",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let browser = ui.panel.borrow().clone();
+                toggle_dock_page(&ui.dock, &browser, &ui.relayout);
+            },
+        );
+    }
+}
+
+pub fn add_results_panel_menu(
+    menu: &mut MenuBar,
+    dock: &SharedDock,
+    panel: &ResultsPanel,
+    relayout: &Relayout,
+) {
+    let dock = Rc::clone(dock);
+    let panel = Rc::clone(panel);
+    let relayout = Rc::clone(relayout);
+
+    menu.add(
+        "Search/Results Panel\t",
+        Shortcut::Ctrl | Shortcut::Shift | 'r',
+        MenuFlag::Normal,
+        move |_| {
+            let browser = panel.borrow().clone();
+            toggle_dock_page(&dock, &browser, &relayout);
+        },
+    );
+}
+
+fn add_filter_item(
+    menu: &mut MenuBar,
+    label: &str,
+    shortcut: Shortcut,
+    ui: &FilterUi,
+    hide_matching: bool,
+) {
+    let ui = ui.clone();
+
+    menu.add(label, shortcut, MenuFlag::Normal, move |_| {
+        let (pattern, options) = {
+            let s = ui.state.borrow();
+            (s.search.pattern.clone(), s.search.options)
+        };
+        if pattern.is_empty() {
+            fltk::dialog::alert_default("Type a search first.");
+            return;
+        }
+        if build_regex(&pattern, &options).is_err() {
+            fltk::dialog::alert_default("The search pattern is not a valid regular expression.");
+            return;
+        }
+
+        ui.state
+            .borrow_mut()
+            .document
+            .filter
+            .filters
+            .push(LineFilter {
+                pattern,
+                options,
+                hide_matching,
+            });
+        apply_filters(&ui);
+        (ui.update_status)();
+    });
+}
+
+pub fn add_filter_menu(menu: &mut MenuBar, ui: &FilterUi) {
+    add_filter_item(
+        menu,
+        "Search/Filter/Show Only Matching Lines\t",
+        Shortcut::Ctrl | Shortcut::Shift | 'l',
+        ui,
+        false,
+    );
+    add_filter_item(
+        menu,
+        "Search/Filter/Hide Matching Lines\t",
+        Shortcut::Ctrl | Shortcut::Shift | 'h',
+        ui,
+        true,
+    );
+
+    {
+        let ui = ui.clone();
+
+        menu.add(
+            "Search/Filter/Collapse Hidden Lines",
+            Shortcut::None,
+            MenuFlag::Toggle,
+            move |m| {
+                let checked = is_checked(m, "Search/Filter/Collapse Hidden Lines");
+                let active = {
+                    let mut s = ui.state.borrow_mut();
+                    s.document.filter.collapsed = checked;
+                    s.document.filter.view.is_some()
+                };
+                if active {
+                    apply_filters(&ui);
+                    (ui.update_status)();
+                }
+            },
+        );
+    }
+
+    {
+        let ui = ui.clone();
+
+        menu.add(
+            "Search/Filter/Remove Last Filter",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                ui.state.borrow_mut().document.filter.filters.pop();
+                apply_filters(&ui);
+                (ui.update_status)();
+            },
+        );
+    }
+
+    {
+        let ui = ui.clone();
+
+        menu.add(
+            "Search/Filter/Clear Filters",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                ui.state.borrow_mut().document.filter.filters.clear();
+                apply_filters(&ui);
+                (ui.update_status)();
+            },
+        );
+    }
+}
+
+pub fn add_goto_menu(menu: &mut MenuBar, goto_line: &GotoLine) {
+    let goto_line = Rc::clone(goto_line);
+
+    menu.add(
+        "Search/Go to Line...\t",
+        Shortcut::Ctrl | 'g',
+        MenuFlag::Normal,
+        move |_| goto_line(),
+    );
+}
+
+pub fn add_bookmark_menu(menu: &mut MenuBar, ui: &BookmarksUi) {
+    {
+        let ui = ui.clone();
+        menu.add(
+            "Search/Bookmarks/Toggle Bookmark\t",
+            Shortcut::Ctrl | Key::F2,
+            MenuFlag::Normal,
+            move |_| toggle_bookmark(&ui),
+        );
+    }
+
+    {
+        let ui = ui.clone();
+        menu.add(
+            "Search/Bookmarks/Next Bookmark\t",
+            Shortcut::None | Key::F2,
+            MenuFlag::Normal,
+            move |_| step_bookmark(&ui, true),
+        );
+    }
+
+    {
+        let ui = ui.clone();
+        menu.add(
+            "Search/Bookmarks/Previous Bookmark\t",
+            Shortcut::Shift | Key::F2,
+            MenuFlag::Normal,
+            move |_| step_bookmark(&ui, false),
+        );
+    }
+
+    {
+        let ui = ui.clone();
+        menu.add(
+            "Search/Bookmarks/Clear Bookmarks",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| clear_bookmarks(&ui),
+        );
+    }
+
+    {
+        let ui = ui.clone();
+        menu.add(
+            "Search/Bookmarks/Bookmarks Panel",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let browser = ui.panel.borrow().clone();
+                toggle_dock_page(&ui.dock, &browser, &ui.relayout);
+            },
+        );
+    }
+}
+
+const LINE_NUMBERS_ITEM: &str = "View/Line Numbers";
+const RELATIVE_LINE_NUMBERS_ITEM: &str = "View/Relative Line Numbers";
+const WRAP_ITEMS: [(&str, Wrap); 3] = [
+    ("View/Word Wrap/No Wrap", Wrap::None),
+    ("View/Word Wrap/Wrap at Window", Wrap::Window),
+    ("View/Word Wrap/Wrap at Column...", Wrap::Column),
+];
+const RULER_ITEM: &str = "View/Ruler";
+const WHITESPACE_ITEM: &str = "View/Show Whitespace";
+
+pub fn add_view_menu(
+    menu: &mut MenuBar,
+    gutter: &SharedGutter,
+    editor: &TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+) {
+    {
+        let gutter = Rc::clone(gutter);
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+
+        menu.add(
+            LINE_NUMBERS_ITEM,
+            Shortcut::None,
+            MenuFlag::Toggle | MenuFlag::Value,
+            move |m| {
+                let checked = is_checked(m, LINE_NUMBERS_ITEM);
+                gutter.borrow_mut().visible = checked;
+                update_gutter_width(&gutter, &editor);
+
+                let settings = &mut state.borrow_mut().settings;
+                settings.editor.line_numbers = checked;
+                settings.save();
+            },
+        );
+    }
+
+    {
+        let gutter = Rc::clone(gutter);
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+
+        menu.add(
+            RELATIVE_LINE_NUMBERS_ITEM,
+            Shortcut::None,
+            MenuFlag::Toggle,
+            move |m| {
+                let checked = is_checked(m, RELATIVE_LINE_NUMBERS_ITEM);
+                gutter.borrow_mut().relative = checked;
+                editor.clone().redraw();
+
+                let settings = &mut state.borrow_mut().settings;
+                settings.editor.relative_line_numbers = checked;
+                settings.save();
+            },
+        );
+    }
+
+    for (label, wrap) in WRAP_ITEMS {
+        let gutter = Rc::clone(gutter);
+        let editor = editor.clone();
+        let highlighter = Rc::clone(highlighter);
+        let state = Rc::clone(state);
+
+        menu.add(label, Shortcut::None, MenuFlag::Radio, move |m| {
+            let path = state.borrow().document.filepath.clone();
+            let mut column = state.borrow().settings.editor_for(&path).wrap_column;
+            if wrap == Wrap::Column {
+                let input = fltk::dialog::input_default("Wrap at column:", &column.to_string());
+                let Some(n) = input.and_then(|v| v.trim().parse::<i32>().ok()) else {
+                    apply_file_settings(&state, &editor, &gutter, &highlighter, m);
+                    return;
+                };
+                column = n.clamp(MIN_WRAP_COLUMN, MAX_WRAP_COLUMN);
+            }
+
+            {
+                let settings = &mut state.borrow_mut().settings;
+                settings.remember_for(&path, |e| {
+                    e.wrap = wrap;
+                    e.wrap_column = column;
+                });
+                settings.save();
+            }
+            apply_file_settings(&state, &editor, &gutter, &highlighter, m);
+        });
+    }
+
+    {
+        let gutter = Rc::clone(gutter);
+        let editor = editor.clone();
+        let highlighter = Rc::clone(highlighter);
+        let state = Rc::clone(state);
+
+        menu.add(RULER_ITEM, Shortcut::None, MenuFlag::Toggle, move |m| {
+            let checked = is_checked(m, RULER_ITEM);
+            {
+                let path = state.borrow().document.filepath.clone();
+                let settings = &mut state.borrow_mut().settings;
+                settings.remember_for(&path, |e| e.ruler = checked);
+                settings.save();
+            }
+            apply_file_settings(&state, &editor, &gutter, &highlighter, m);
+        });
+    }
+
+    {
+        let gutter = Rc::clone(gutter);
+        let editor = editor.clone();
+        let highlighter = Rc::clone(highlighter);
+        let state = Rc::clone(state);
+
+        menu.add(
+            WHITESPACE_ITEM,
+            Shortcut::None,
+            MenuFlag::Toggle,
+            move |m| {
+                let checked = is_checked(m, WHITESPACE_ITEM);
+                {
+                    let settings = &mut state.borrow_mut().settings;
+                    settings.editor.show_whitespace = checked;
+                    settings.save();
                 }
+                apply_file_settings(&state, &editor, &gutter, &highlighter, m);
             },
         );
     }
 }
+
+pub fn sync_view_menu(menu: &MenuBar, settings: &EditorSettings) {
+    let items = [
+        (LINE_NUMBERS_ITEM, settings.line_numbers),
+        (RELATIVE_LINE_NUMBERS_ITEM, settings.relative_line_numbers),
+        (WRAP_ITEMS[0].0, settings.wrap == Wrap::None),
+        (WRAP_ITEMS[1].0, settings.wrap == Wrap::Window),
+        (WRAP_ITEMS[2].0, settings.wrap == Wrap::Column),
+        (RULER_ITEM, settings.ruler),
+        (WHITESPACE_ITEM, settings.show_whitespace),
+    ];
+    for (label, on) in items {
+        if let Some(mut item) = menu.find_item(label) {
+            if on {
+                item.set();
+            } else {
+                item.clear();
+            }
+        }
+    }
+}
+
+const FOLLOW_SYSTEM_ITEM: &str = "View/Theme/Follow System Dark Mode";
+
+fn theme_item_path(name: &str) -> String {
+    format!("View/Theme/{}", name.replace('/', "-"))
+}
+
+pub fn add_theme_menu(menu: &mut MenuBar, themes: &ThemeUi) {
+    let names: Vec<String> = themes.themes.iter().map(|t| t.name.clone()).collect();
+    let (current, follow_system) = {
+        let appearance = &themes.state.borrow().settings.appearance;
+        (appearance.theme.clone(), appearance.follow_system)
+    };
+
+    for (idx, name) in names.iter().enumerate() {
+        let mut flags = MenuFlag::Radio;
+        if *name == current && !follow_system {
+            flags |= MenuFlag::Value;
+        }
+        if idx + 1 == names.len() {
+            flags |= MenuFlag::MenuDivider;
+        }
+
+        let themes = themes.clone();
+        let name = name.clone();
+        menu.add(&theme_item_path(&name), Shortcut::None, flags, move |m| {
+            {
+                let settings = &mut themes.state.borrow_mut().settings;
+                settings.appearance.theme = name.clone();
+                settings.appearance.follow_system = false;
+                settings.save();
+            }
+            if let Some(mut item) = m.find_item(FOLLOW_SYSTEM_ITEM) {
+                item.clear();
+            }
+            apply_current_theme(&themes);
+        });
+    }
+
+    let themes = themes.clone();
+    let mut flags = MenuFlag::Toggle;
+    if follow_system {
+        flags |= MenuFlag::Value;
+    }
+    menu.add(FOLLOW_SYSTEM_ITEM, Shortcut::None, flags, move |m| {
+        let checked = is_checked(m, FOLLOW_SYSTEM_ITEM);
+        {
+            let settings = &mut themes.state.borrow_mut().settings;
+            settings.appearance.follow_system = checked;
+            settings.save();
+        }
+        apply_current_theme(&themes);
+    });
+}
+
+/// Checks the radio item of the chosen theme, or only the follow-system toggle.
+pub fn sync_theme_menu(menu: &MenuBar, themes: &ThemeUi) {
+    let (current, follow_system) = {
+        let appearance = &themes.state.borrow().settings.appearance;
+        (appearance.theme.clone(), appearance.follow_system)
+    };
+
+    for theme in themes.themes.iter() {
+        if let Some(mut item) = menu.find_item(&theme_item_path(&theme.name)) {
+            if theme.name == current && !follow_system {
+                item.set();
+            } else {
+                item.clear();
+            }
+        }
+    }
+    if let Some(mut item) = menu.find_item(FOLLOW_SYSTEM_ITEM) {
+        if follow_system {
+            item.set();
+        } else {
+            item.clear();
+        }
+    }
+}
+
+pub fn add_font_menu(menu: &mut MenuBar, fonts: &FontUi) {
+    {
+        let fonts = fonts.clone();
+        menu.add(
+            "View/Font...",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| show_font_dialog(&fonts),
+        );
+    }
+
+    for (label, key, steps) in [
+        ("View/Zoom In\t", '=', 1),
+        ("View/Zoom Out\t", '-', -1),
+        ("View/Reset Zoom\t", '0', 0),
+    ] {
+        let fonts = fonts.clone();
+        menu.add(label, Shortcut::Ctrl | key, MenuFlag::Normal, move |_| {
+            zoom(&fonts, steps)
+        });
+    }
+}
+
+pub fn add_preferences_menu(menu: &mut MenuBar, preferences: &PreferencesUi) {
+    let preferences = preferences.clone();
+    // FLTK ignores the index for labels with a path, so insert by position alone.
+    let quit = menu.find_index("File/Quit");
+    menu.insert(
+        quit,
+        "Preferences...",
+        Shortcut::None,
+        MenuFlag::MenuDivider,
+        move |_| show_preferences_dialog(&preferences),
+    );
+}
+
+/// Adds the top-level Edit menu ahead of Search, once.
+fn ensure_edit_menu(menu: &mut MenuBar) {
+    if menu.find_index("Edit") < 0 {
+        let search = menu.find_index("Search");
+        menu.insert(search, "Edit", Shortcut::None, MenuFlag::Submenu, |_| {});
+    }
+}
+
+const INDENT_SPACES_ITEM: &str = "Edit/Indentation/Indent Using Spaces";
+const INDENT_TABS_ITEM: &str = "Edit/Indentation/Indent Using Tabs";
+
+fn tab_width_item(width: usize) -> String {
+    format!("Edit/Indentation/Tab Width: {}", width)
+}
+
+pub fn sync_indent_menu(menu: &MenuBar, indent: Indent) {
+    let mut items = vec![
+        (INDENT_SPACES_ITEM.to_string(), !indent.tabs),
+        (INDENT_TABS_ITEM.to_string(), indent.tabs),
+    ];
+    items.extend(
+        TAB_WIDTHS
+            .iter()
+            .map(|w| (tab_width_item(*w), *w == indent.width)),
+    );
+    for (label, on) in items {
+        if let Some(mut item) = menu.find_item(&label) {
+            if on {
+                item.set();
+            } else {
+                item.clear();
+            }
+        }
+    }
+}
+
+pub fn add_indentation_menu(
+    menu: &mut MenuBar,
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) {
+    ensure_edit_menu(menu);
+
+    // Changes the document's indentation, then refreshes tab stops, menu and status.
+    let change = {
+        let editor = editor.clone();
+        let buf = Rc::clone(buf);
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        Rc::new(move |m: &MenuBar, f: &dyn Fn(&mut Indent, &str)| {
+            {
+                let text = buf.borrow().text();
+                f(&mut state.borrow_mut().document.indent, &text);
+            }
+            apply_indent(&state, &buf, &editor, m);
+            update_status();
+        })
+    };
+
+    for (label, tabs) in [(INDENT_SPACES_ITEM, false), (INDENT_TABS_ITEM, true)] {
+        let change = Rc::clone(&change);
+        menu.add(label, Shortcut::None, MenuFlag::Radio, move |m| {
+            change(m, &|indent, _| indent.tabs = tabs)
+        });
+    }
+
+    for (idx, width) in TAB_WIDTHS.into_iter().enumerate() {
+        let change = Rc::clone(&change);
+        let mut flags = MenuFlag::Radio;
+        if idx + 1 == TAB_WIDTHS.len() {
+            flags |= MenuFlag::MenuDivider;
+        }
+        menu.add(&tab_width_item(width), Shortcut::None, flags, move |m| {
+            change(m, &|indent, _| indent.width = width)
+        });
+    }
+
+    {
+        let change = Rc::clone(&change);
+        menu.add(
+            "Edit/Indentation/Detect from Content",
+            Shortcut::None,
+            MenuFlag::MenuDivider,
+            move |m| change(m, &|indent, text| *indent = detect_indent(text, *indent)),
+        );
+    }
+
+    for (label, tabs) in [
+        ("Edit/Indentation/Convert Indentation to Spaces", false),
+        ("Edit/Indentation/Convert Indentation to Tabs", true),
+    ] {
+        let editor = editor.clone();
+        let buf = Rc::clone(buf);
+        let state = Rc::clone(state);
+        let change = Rc::clone(&change);
+        menu.add(label, Shortcut::None, MenuFlag::Normal, move |m| {
+            convert_document(&mut editor.clone(), &buf, &state, tabs);
+            change(m, &|_, _| {});
+        });
+    }
+}
+
+pub fn add_bracket_menu(
+    menu: &mut MenuBar,
+    editor: &TextEditor,
+    highlighter: &SharedHighlighter,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) {
+    ensure_edit_menu(menu);
+
+    // Runs a bracket command, then refreshes the match highlight and status.
+    let run = {
+        let editor = editor.clone();
+        let highlighter = Rc::clone(highlighter);
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        Rc::new(
+            move |action: fn(&mut TextEditor, &SharedHighlighter, &Rc<RefCell<AppState>>)| {
+                let mut editor = editor.clone();
+                action(&mut editor, &highlighter, &state);
+                update_bracket_match(&editor, &highlighter, &state);
+                update_status();
+            },
+        )
+    };
+
+    {
+        let run = Rc::clone(&run);
+        menu.add(
+            "Edit/Go to Matching Bracket\t",
+            Shortcut::Ctrl | 'm',
+            MenuFlag::Normal,
+            move |_| run(jump_to_match),
+        );
+    }
+
+    menu.add(
+        "Edit/Select to Matching Bracket\t",
+        Shortcut::Ctrl | Shortcut::Shift | 'm',
+        MenuFlag::MenuDivider,
+        move |_| run(select_to_match),
+    );
+}
+
+pub fn add_line_menu(
+    menu: &mut MenuBar,
+    editor: &TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) {
+    ensure_edit_menu(menu);
+
+    // Runs a line command on the editor, then refreshes the status bar.
+    let run = {
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        Rc::new(
+            move |command: &dyn Fn(&mut TextEditor, &Rc<RefCell<AppState>>)| {
+                command(&mut editor.clone(), &state);
+                update_status();
+            },
+        )
+    };
+
+    let commands: [(&str, Shortcut, LineCommand); 5] = [
+        (
+            "Edit/Duplicate Line\t",
+            Shortcut::Ctrl | Shortcut::Shift | 'd',
+            duplicate_lines,
+        ),
+        (
+            "Edit/Delete Line\t",
+            Shortcut::Ctrl | Shortcut::Shift | 'k',
+            delete_lines,
+        ),
+        ("Edit/Move Line Up\t", Shortcut::Alt | Key::Up, |e, s| {
+            move_lines(e, s, true)
+        }),
+        (
+            "Edit/Move Line Down\t",
+            Shortcut::Alt | Key::Down,
+            |e, s| move_lines(e, s, false),
+        ),
+        (
+            "Edit/Join Lines\t",
+            Shortcut::Ctrl | Shortcut::Shift | 'j',
+            join_lines,
+        ),
+    ];
+    for (label, shortcut, command) in commands {
+        let run = Rc::clone(&run);
+        menu.add(label, shortcut, MenuFlag::Normal, move |_| run(&command));
+    }
+
+    {
+        let run = Rc::clone(&run);
+        menu.add(
+            "Edit/Split Selection into Lines...",
+            Shortcut::None,
+            MenuFlag::MenuDivider,
+            move |_| {
+                if let Some(separator) = fltk::dialog::input_default("Split at:", ",") {
+                    run(&|e, s| split_selection(e, s, &separator));
+                }
+            },
+        );
+    }
+
+    for (label, order) in [
+        ("Edit/Sort Lines/Ascending", SortOrder::Ascending),
+        ("Edit/Sort Lines/Descending", SortOrder::Descending),
+        ("Edit/Sort Lines/Natural", SortOrder::Natural),
+        (
+            "Edit/Sort Lines/Case-Insensitive",
+            SortOrder::CaseInsensitive,
+        ),
+        ("Edit/Sort Lines/Numeric", SortOrder::Numeric),
+    ] {
+        let run = Rc::clone(&run);
+        menu.add(label, Shortcut::None, MenuFlag::Normal, move |_| {
+            run(&|e, s| sort_lines(e, s, order))
+        });
+    }
+
+    let commands: [(&str, LineCommand); 3] = [
+        ("Edit/Reverse Lines", reverse_lines),
+        ("Edit/Remove Duplicate Lines", remove_duplicate_lines),
+        ("Edit/Shuffle Lines", shuffle_lines),
+    ];
+    for (idx, (label, command)) in commands.into_iter().enumerate() {
+        let run = Rc::clone(&run);
+        let flags = if idx + 1 == commands.len() {
+            MenuFlag::MenuDivider
+        } else {
+            MenuFlag::Normal
+        };
+        menu.add(label, Shortcut::None, flags, move |_| run(&command));
+    }
+}
+
+pub fn add_convert_menu(
+    menu: &mut MenuBar,
+    editor: &TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) {
+    ensure_edit_menu(menu);
+
+    for (transform, ends_group) in Transform::ALL {
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        let flags = if ends_group {
+            MenuFlag::MenuDivider
+        } else {
+            MenuFlag::Normal
+        };
+        menu.add(
+            &format!("Edit/Convert/{}", transform.label()),
+            Shortcut::None,
+            flags,
+            move |_| {
+                transform_selection(&mut editor.clone(), &state, transform);
+                update_status();
+            },
+        );
+    }
+}
+
+pub fn add_encode_menu(
+    menu: &mut MenuBar,
+    editor: &TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) {
+    ensure_edit_menu(menu);
+
+    for (codec, ends_group) in Codec::ALL {
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        let flags = if ends_group {
+            MenuFlag::MenuDivider
+        } else {
+            MenuFlag::Normal
+        };
+        menu.add(
+            &format!("Edit/Encode and Decode/{}", codec.label()),
+            Shortcut::None,
+            flags,
+            move |_| {
+                convert_selection(&mut editor.clone(), &state, codec);
+                update_status();
+            },
+        );
+    }
+}
+
+pub fn add_data_format_menu(
+    menu: &mut MenuBar,
+    editor: &TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    update_status: &UpdateStatus,
+) {
+    ensure_edit_menu(menu);
+
+    {
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        menu.add(
+            "Edit/JSON, XML and TOML/Format\t",
+            Shortcut::Ctrl | Shortcut::Alt | 'f',
+            MenuFlag::Normal,
+            move |_| {
+                format_data(&mut editor.clone(), &state, false);
+                update_status();
+            },
+        );
+    }
+    {
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        menu.add(
+            "Edit/JSON, XML and TOML/Minify\t",
+            Shortcut::None,
+            MenuFlag::MenuDivider,
+            move |_| {
+                format_data(&mut editor.clone(), &state, true);
+                update_status();
+            },
+        );
+    }
+    {
+        let editor = editor.clone();
+        let state = Rc::clone(state);
+        let update_status = Rc::clone(update_status);
+        menu.add(
+            "Edit/JSON, XML and TOML/Validate\t",
+            Shortcut::Ctrl | Shortcut::Alt | 'v',
+            MenuFlag::Normal,
+            move |_| {
+                validate_data(&mut editor.clone(), &state);
+                update_status();
+            },
+        );
+    }
+}
+
+pub fn add_command_palette_menu(menu: &mut MenuBar) {
+    menu.add(
+        "View/Command Palette...\t",
+        Shortcut::Ctrl | Shortcut::Shift | 'p',
+        MenuFlag::MenuDivider,
+        |m| show_command_palette(m),
+    );
+}
@@ -0,0 +1,189 @@
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    enums::{CallbackTrigger, Event, Key},
+    group::Group,
+    input::Input,
+    menu::MenuBar,
+    prelude::*,
+    window::Window,
+};
+use std::{cell::RefCell, rc::Rc};
+
+const PALETTE_W: i32 = 520;
+const PALETTE_H: i32 = 340;
+
+/// A menu item the palette can run: its index in the menu bar and its path.
+struct Command {
+    index: i32,
+    label: String,
+}
+
+/// Every active leaf item in the menu bar, labelled with its full path.
+fn menu_commands(menu: &MenuBar) -> Vec<Command> {
+    (0..menu.size())
+        .filter_map(|index| {
+            let item = menu.at(index)?;
+            // Submenu ends have no label.
+            item.label()?;
+            if item.is_submenu() || !item.visible() || !item.active() {
+                return None;
+            }
+            let path = menu.item_pathname(Some(&item)).ok()?;
+            let label = path.split('\t').next().unwrap_or_default();
+            Some(Command {
+                index,
+                label: label.replace('/', " / "),
+            })
+        })
+        .collect()
+}
+
+/// Every word of the query appears in the label, ignoring case.
+fn matches(label: &str, query: &str) -> bool {
+    let label = label.to_lowercase();
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| label.contains(word))
+}
+
+/// Runs a menu item as if it were picked, flipping toggles first.
+fn run_command(menu: &MenuBar, index: i32) {
+    let Some(mut item) = menu.at(index) else {
+        return;
+    };
+    if item.is_radio() {
+        set_only(menu, index);
+    } else if item.is_checkbox() {
+        if item.value() {
+            item.clear();
+        } else {
+            item.set();
+        }
+    }
+    item.do_callback(menu);
+}
+
+/// Checks a radio item and unchecks the radio items next to it, as FLTK does
+/// when one is picked from the menu itself.
+fn set_only(menu: &MenuBar, index: i32) {
+    for step in [-1, 1] {
+        let mut i = index + step;
+        while let Some(mut other) = menu.at(i) {
+            if other.label().is_none() || !other.is_radio() {
+                break;
+            }
+            other.clear();
+            i += step;
+        }
+    }
+    if let Some(mut item) = menu.at(index) {
+        item.set();
+    }
+}
+
+fn move_selection(list: &mut HoldBrowser, step: i32) {
+    if list.size() > 0 {
+        let line = (list.value() + step).clamp(1, list.size());
+        list.select(line);
+        list.middle_line(line);
+    }
+}
+
+/// Shows a searchable list of every menu command; Enter runs the highlighted one.
+pub fn show_command_palette(menu: &MenuBar) {
+    Group::set_current(None::<&Group>);
+
+    let commands = Rc::new(menu_commands(menu));
+    // Menu index of each line in the list.
+    let shown: Rc<RefCell<Vec<i32>>> = Rc::new(RefCell::new(vec![]));
+
+    let mut win = Window::new(0, 0, PALETTE_W, PALETTE_H, "Command Palette");
+    let mut input = Input::new(10, 10, PALETTE_W - 20, 25, None);
+    let list = HoldBrowser::new(10, 40, PALETTE_W - 20, PALETTE_H - 50, None);
+    win.end();
+    win.make_modal(true);
+
+    let refill = {
+        let commands = Rc::clone(&commands);
+        let shown = Rc::clone(&shown);
+        let list = list.clone();
+        move |query: &str| {
+            let mut list = list.clone();
+            list.clear();
+            let mut shown = shown.borrow_mut();
+            shown.clear();
+            for command in commands.iter().filter(|c| matches(&c.label, query)) {
+                list.add(&command.label);
+                shown.push(command.index);
+            }
+            if list.size() > 0 {
+                list.select(1);
+            }
+        }
+    };
+    refill("");
+
+    let run_selected = {
+        let menu = menu.clone();
+        let list = list.clone();
+        let win = win.clone();
+        move || {
+            let line = list.value();
+            if line < 1 {
+                return;
+            }
+            let index = shown.borrow().get(line as usize - 1).copied();
+            if let Some(index) = index {
+                win.clone().hide();
+                run_command(&menu, index);
+            }
+        }
+    };
+
+    input.set_trigger(CallbackTrigger::Changed);
+    input.set_callback(move |i| refill(&i.value()));
+
+    {
+        let mut list = list.clone();
+        let run_selected = run_selected.clone();
+        input.handle(move |_, ev| {
+            if ev != Event::KeyDown {
+                return false;
+            }
+            match app::event_key() {
+                Key::Down => move_selection(&mut list, 1),
+                Key::Up => move_selection(&mut list, -1),
+                Key::PageDown => move_selection(&mut list, 10),
+                Key::PageUp => move_selection(&mut list, -10),
+                Key::Enter | Key::KPEnter => run_selected(),
+                _ => return false,
+            }
+            true
+        });
+    }
+
+    {
+        let mut list = list.clone();
+        list.set_callback(move |_| {
+            if app::event_clicks() {
+                run_selected();
+            }
+        });
+    }
+
+    win.handle(|w, ev| {
+        if ev == Event::KeyDown && app::event_key() == Key::Escape {
+            w.hide();
+            return true;
+        }
+        false
+    });
+
+    if let Some(parent) = menu.window() {
+        win.set_pos(parent.x() + (parent.w() - PALETTE_W) / 2, parent.y() + 40);
+    }
+    win.show();
+    input.take_focus().ok();
+}
@@ -0,0 +1,498 @@
+use fltk::{
+    app,
+    button::{Button, CheckButton, ReturnButton},
+    dialog,
+    enums::Align,
+    frame::Frame,
+    group::{Group, Tabs},
+    menu::{Choice, MenuBar},
+    misc::Spinner,
+    prelude::*,
+    text::{TextEditor, WrapMode},
+    window::Window,
+};
+use std::{cell::RefCell, fs, rc::Rc, time::SystemTime};
+
+use super::config::config_file;
+use super::fonts::{FontUi, MAX_FONT_SIZE, MIN_FONT_SIZE, apply_font, monospace_fonts};
+use super::gutter::{SharedGutter, update_gutter_width};
+use super::highlight::SharedHighlighter;
+use super::menu::{OpenPath, sync_search_option_items, sync_theme_menu, sync_view_menu};
+use super::settings::{LineEndings, SETTINGS_FILE, Settings, Wrap};
+use super::state::AppState;
+use super::theme::{ThemeUi, apply_current_theme};
+
+const SETTINGS_POLL_INTERVAL: f64 = 1.0;
+pub const MIN_WRAP_COLUMN: i32 = 10;
+pub const MAX_WRAP_COLUMN: i32 = 500;
+const WRAP_MODES: [(Wrap, &str); 3] = [
+    (Wrap::None, "No wrap"),
+    (Wrap::Window, "At window edge"),
+    (Wrap::Column, "At column"),
+];
+const LINE_ENDINGS: [(LineEndings, &str); 4] = [
+    (LineEndings::Keep, "Keep as is"),
+    (LineEndings::Lf, "LF (Unix)"),
+    (LineEndings::Crlf, "CRLF (Windows)"),
+    (LineEndings::Cr, "CR (Classic Mac)"),
+];
+
+#[derive(Clone)]
+pub struct PreferencesUi {
+    pub state: Rc<RefCell<AppState>>,
+    pub editor: TextEditor,
+    pub gutter: SharedGutter,
+    pub highlighter: SharedHighlighter,
+    pub menu: MenuBar,
+    pub themes: ThemeUi,
+    pub fonts: FontUi,
+    pub open_path: OpenPath,
+}
+
+/// Dialog widgets, read back into a `Settings` on OK or Apply.
+#[derive(Clone)]
+struct PreferencesForm {
+    wrap: Choice,
+    wrap_column: Spinner,
+    ruler: CheckButton,
+    ruler_column: Spinner,
+    show_whitespace: CheckButton,
+    tab_width: Spinner,
+    insert_spaces: CheckButton,
+    detect_indentation: CheckButton,
+    auto_indent: CheckButton,
+    auto_close_brackets: CheckButton,
+    scrollbar_size: Spinner,
+    line_numbers: CheckButton,
+    relative_line_numbers: CheckButton,
+    max_recent_files: Spinner,
+    trim_trailing_whitespace: CheckButton,
+    trim_modified_lines_only: CheckButton,
+    final_newline: CheckButton,
+    normalize_nfc: CheckButton,
+    line_endings: Choice,
+    case_sensitive: CheckButton,
+    regex: CheckButton,
+    max_history: Spinner,
+    theme: Choice,
+    follow_system: CheckButton,
+    font: Choice,
+    font_size: Spinner,
+}
+
+/// Applies the editor settings for the current file, including its extension's overrides.
+pub fn apply_file_settings(
+    state: &Rc<RefCell<AppState>>,
+    editor: &TextEditor,
+    gutter: &SharedGutter,
+    highlighter: &SharedHighlighter,
+    menu: &MenuBar,
+) {
+    let settings = {
+        let s = state.borrow();
+        let mut settings = s.settings.editor_for(&s.document.filepath);
+        if let Some(column) = s.document.editorconfig.max_line_length {
+            settings.ruler_column = column;
+            settings.wrap_column = column;
+        }
+        settings
+    };
+
+    let mut ed = editor.clone();
+    match settings.wrap {
+        Wrap::None => ed.wrap_mode(WrapMode::None, 0),
+        Wrap::Window => ed.wrap_mode(WrapMode::AtBounds, 0),
+        Wrap::Column => ed.wrap_mode(WrapMode::AtColumn, settings.wrap_column),
+    }
+    ed.set_scrollbar_size(settings.scrollbar_size);
+
+    {
+        let mut g = gutter.borrow_mut();
+        g.visible = settings.line_numbers;
+        g.relative = settings.relative_line_numbers;
+        g.ruler = settings.ruler.then_some(settings.ruler_column);
+        g.whitespace = settings.show_whitespace;
+    }
+    highlighter
+        .borrow_mut()
+        .set_show_whitespace(settings.show_whitespace);
+    update_gutter_width(gutter, editor);
+    sync_view_menu(menu, &settings);
+    ed.redraw();
+}
+
+/// Applies `state.settings` everywhere; search toggles follow only when their defaults changed.
+pub fn apply_settings(ui: &PreferencesUi, previous: &Settings) {
+    let options = {
+        let mut s = ui.state.borrow_mut();
+        let s = &mut *s;
+        s.search.history.limit = s.settings.search.max_history;
+        if s.settings.search != previous.search {
+            s.search.options.case_sensitive = s.settings.search.case_sensitive;
+            s.search.options.regex = s.settings.search.regex;
+        }
+        s.search.options
+    };
+    sync_search_option_items(&ui.menu, &options);
+
+    apply_file_settings(&ui.state, &ui.editor, &ui.gutter, &ui.highlighter, &ui.menu);
+    if ui.state.borrow().settings.appearance != previous.appearance {
+        apply_current_theme(&ui.themes);
+        apply_font(&ui.fonts);
+        sync_theme_menu(&ui.menu, &ui.themes);
+    }
+}
+
+fn update_settings(ui: &PreferencesUi, settings: Settings) {
+    let previous = std::mem::replace(&mut ui.state.borrow_mut().settings, settings);
+    ui.state.borrow().settings.save();
+    apply_settings(ui, &previous);
+}
+
+fn check_button(x: i32, y: i32, label: &str, value: bool) -> CheckButton {
+    let button = CheckButton::new(x, y, 300, 25, None).with_label(label);
+    button.set_checked(value);
+    button
+}
+
+fn spinner(x: i32, y: i32, label: &str, range: (i32, i32), value: i32) -> Spinner {
+    let mut spinner = Spinner::new(x, y, 80, 25, None).with_label(label);
+    spinner.set_range(range.0 as f64, range.1 as f64);
+    spinner.set_step(1.0);
+    spinner.set_value(value as f64);
+    spinner
+}
+
+fn choice(x: i32, y: i32, label: &str, items: &[String], current: &str) -> Choice {
+    let mut choice = Choice::new(x, y, 220, 25, None).with_label(label);
+    for item in items {
+        choice.add_choice(&item.replace('/', "\\/"));
+    }
+    let selected = items.iter().position(|i| i == current).unwrap_or(0);
+    choice.set_value(selected as i32);
+    choice
+}
+
+fn read_form(
+    form: &PreferencesForm,
+    current: &Settings,
+    themes: &[String],
+    fonts: &[String],
+) -> Settings {
+    let mut settings = current.clone();
+
+    settings.editor.wrap = WRAP_MODES[form.wrap.value().clamp(0, 2) as usize].0;
+    settings.editor.wrap_column = form.wrap_column.value() as i32;
+    settings.editor.ruler = form.ruler.is_checked();
+    settings.editor.ruler_column = form.ruler_column.value() as i32;
+    settings.editor.show_whitespace = form.show_whitespace.is_checked();
+    settings.editor.tab_width = form.tab_width.value() as i32;
+    settings.editor.insert_spaces = form.insert_spaces.is_checked();
+    settings.editor.detect_indentation = form.detect_indentation.is_checked();
+    settings.editor.auto_indent = form.auto_indent.is_checked();
+    settings.editor.auto_close_brackets = form.auto_close_brackets.is_checked();
+    settings.editor.scrollbar_size = form.scrollbar_size.value() as i32;
+    settings.editor.line_numbers = form.line_numbers.is_checked();
+    settings.editor.relative_line_numbers = form.relative_line_numbers.is_checked();
+
+    settings.files.max_recent_files = form.max_recent_files.value() as usize;
+
+    settings.on_save.trim_trailing_whitespace = form.trim_trailing_whitespace.is_checked();
+    settings.on_save.trim_modified_lines_only = form.trim_modified_lines_only.is_checked();
+    settings.on_save.final_newline = form.final_newline.is_checked();
+    settings.on_save.normalize_nfc = form.normalize_nfc.is_checked();
+    settings.on_save.line_endings = LINE_ENDINGS[form.line_endings.value().clamp(0, 3) as usize].0;
+
+    settings.search.case_sensitive = form.case_sensitive.is_checked();
+    settings.search.regex = form.regex.is_checked();
+    settings.search.max_history = form.max_history.value() as usize;
+
+    if let Some(theme) = themes.get(form.theme.value().max(0) as usize) {
+        settings.appearance.theme = theme.clone();
+    }
+    settings.appearance.follow_system = form.follow_system.is_checked();
+    if let Some(font) = fonts.get(form.font.value().max(0) as usize) {
+        settings.appearance.font = font.clone();
+    }
+    settings.appearance.font_size = form.font_size.value() as i32;
+    settings
+}
+
+pub fn show_preferences_dialog(ui: &PreferencesUi) {
+    Group::set_current(None::<&Group>);
+
+    let settings = ui.state.borrow().settings.clone();
+    let themes: Rc<Vec<String>> =
+        Rc::new(ui.themes.themes.iter().map(|t| t.name.clone()).collect());
+    let fonts: Rc<Vec<String>> = Rc::new(monospace_fonts().into_iter().map(|f| f.0).collect());
+
+    let mut win = Window::new(0, 0, 460, 330, "Preferences");
+    let tabs = Tabs::new(10, 10, 440, 275, None);
+
+    let editor_tab = Group::new(10, 35, 440, 250, "Editor");
+    let wrap_modes: Vec<String> = WRAP_MODES.iter().map(|m| m.1.to_string()).collect();
+    let current_wrap = WRAP_MODES
+        .iter()
+        .find(|m| m.0 == settings.editor.wrap)
+        .map_or("", |m| m.1);
+    let wrap = choice(200, 45, "Wrap:", &wrap_modes, current_wrap);
+    let wrap_column = spinner(
+        200,
+        75,
+        "Wrap column:",
+        (MIN_WRAP_COLUMN, MAX_WRAP_COLUMN),
+        settings.editor.wrap_column,
+    );
+    let ruler = check_button(20, 105, "Show ruler", settings.editor.ruler);
+    let ruler_column = spinner(
+        200,
+        135,
+        "Ruler column:",
+        (MIN_WRAP_COLUMN, MAX_WRAP_COLUMN),
+        settings.editor.ruler_column,
+    );
+    let show_whitespace = check_button(20, 165, "Show whitespace", settings.editor.show_whitespace);
+    let scrollbar_size = spinner(
+        200,
+        195,
+        "Scrollbar size:",
+        (8, 40),
+        settings.editor.scrollbar_size,
+    );
+    let line_numbers = check_button(20, 225, "Show line numbers", settings.editor.line_numbers);
+    let relative_line_numbers = check_button(
+        20,
+        255,
+        "Relative line numbers",
+        settings.editor.relative_line_numbers,
+    );
+    editor_tab.end();
+
+    let indentation_tab = Group::new(10, 35, 440, 250, "Indentation");
+    let tab_width = spinner(200, 45, "Tab width:", (1, 16), settings.editor.tab_width);
+    let insert_spaces = check_button(20, 75, "Indent with spaces", settings.editor.insert_spaces);
+    let detect_indentation = check_button(
+        20,
+        105,
+        "Detect indentation from file content",
+        settings.editor.detect_indentation,
+    );
+    let auto_indent = check_button(
+        20,
+        135,
+        "Auto-indent and continue comments and lists",
+        settings.editor.auto_indent,
+    );
+    let auto_close_brackets = check_button(
+        20,
+        165,
+        "Auto-close brackets and quotes",
+        settings.editor.auto_close_brackets,
+    );
+    indentation_tab.end();
+
+    let files_tab = Group::new(10, 35, 440, 250, "Files");
+    let max_recent_files = spinner(
+        200,
+        45,
+        "Recent files to keep:",
+        (1, 50),
+        settings.files.max_recent_files as i32,
+    );
+    let mut note = Frame::new(20, 80, 420, 60, None).with_label(
+        "Editor options can be overridden per file extension\n\
+         with [overrides.<ext>] sections in settings.toml,\n\
+         and save actions with [overrides.<ext>.on_save].",
+    );
+    note.set_align(Align::Left | Align::Inside | Align::Wrap);
+    let mut edit_file = Button::new(20, 145, 180, 25, "Open settings.toml");
+    files_tab.end();
+
+    let save_tab = Group::new(10, 35, 440, 250, "Save");
+    let trim_trailing_whitespace = check_button(
+        20,
+        45,
+        "Trim trailing whitespace",
+        settings.on_save.trim_trailing_whitespace,
+    );
+    let trim_modified_lines_only = check_button(
+        40,
+        75,
+        "Only on modified lines",
+        settings.on_save.trim_modified_lines_only,
+    );
+    let final_newline = check_button(
+        20,
+        105,
+        "End with exactly one newline",
+        settings.on_save.final_newline,
+    );
+    let normalize_nfc = check_button(
+        20,
+        135,
+        "Normalize Unicode to NFC",
+        settings.on_save.normalize_nfc,
+    );
+    let line_ending_names: Vec<String> = LINE_ENDINGS.iter().map(|l| l.1.to_string()).collect();
+    let current_line_ending = LINE_ENDINGS
+        .iter()
+        .find(|l| l.0 == settings.on_save.line_endings)
+        .map_or("", |l| l.1);
+    let line_endings = choice(
+        200,
+        165,
+        "Line endings:",
+        &line_ending_names,
+        current_line_ending,
+    );
+    save_tab.end();
+
+    let search_tab = Group::new(10, 35, 440, 250, "Search");
+    let case_sensitive = check_button(
+        20,
+        45,
+        "Match case by default",
+        settings.search.case_sensitive,
+    );
+    let regex = check_button(
+        20,
+        75,
+        "Regular expressions by default",
+        settings.search.regex,
+    );
+    let max_history = spinner(
+        200,
+        105,
+        "History entries:",
+        (1, 500),
+        settings.search.max_history as i32,
+    );
+    search_tab.end();
+
+    let appearance_tab = Group::new(10, 35, 440, 250, "Appearance");
+    let theme = choice(120, 45, "Theme:", &themes, &settings.appearance.theme);
+    let follow_system = check_button(
+        120,
+        75,
+        "Follow system dark mode",
+        settings.appearance.follow_system,
+    );
+    let font = choice(120, 105, "Font:", &fonts, &settings.appearance.font);
+    let font_size = spinner(
+        120,
+        135,
+        "Font size:",
+        (MIN_FONT_SIZE, MAX_FONT_SIZE),
+        settings.appearance.font_size,
+    );
+    appearance_tab.end();
+    tabs.end();
+
+    let mut apply = Button::new(190, 295, 80, 25, "Apply");
+    let mut ok = ReturnButton::new(280, 295, 80, 25, "OK");
+    let mut cancel = Button::new(370, 295, 80, 25, "Cancel");
+    win.end();
+    win.make_modal(true);
+
+    let form = PreferencesForm {
+        wrap,
+        wrap_column,
+        ruler,
+        ruler_column,
+        show_whitespace,
+        tab_width,
+        insert_spaces,
+        detect_indentation,
+        auto_indent,
+        auto_close_brackets,
+        scrollbar_size,
+        line_numbers,
+        relative_line_numbers,
+        max_recent_files,
+        trim_trailing_whitespace,
+        trim_modified_lines_only,
+        final_newline,
+        normalize_nfc,
+        line_endings,
+        case_sensitive,
+        regex,
+        max_history,
+        theme,
+        follow_system,
+        font,
+        font_size,
+    };
+
+    {
+        let ui = ui.clone();
+        let form = form.clone();
+        let themes = Rc::clone(&themes);
+        let fonts = Rc::clone(&fonts);
+        apply.set_callback(move |_| {
+            let settings = read_form(&form, &ui.state.borrow().settings, &themes, &fonts);
+            update_settings(&ui, settings);
+        });
+    }
+
+    {
+        let ui = ui.clone();
+        let mut win = win.clone();
+        ok.set_callback(move |_| {
+            let settings = read_form(&form, &ui.state.borrow().settings, &themes, &fonts);
+            update_settings(&ui, settings);
+            win.hide();
+        });
+    }
+
+    {
+        let ui = ui.clone();
+        let mut win = win.clone();
+        edit_file.set_callback(move |_| {
+            let path = config_file(SETTINGS_FILE);
+            if !path.exists() {
+                ui.state.borrow().settings.save();
+            }
+            win.hide();
+            if !(ui.open_path)(&path.to_string_lossy()) {
+                dialog::alert_default(&format!("Could not open {}", path.display()));
+            }
+        });
+    }
+
+    {
+        let mut win = win.clone();
+        cancel.set_callback(move |_| win.hide());
+    }
+
+    let (x, y) = app::event_coords();
+    win.set_pos(x.max(0), y.max(0));
+    win.show();
+}
+
+fn settings_modified() -> Option<SystemTime> {
+    fs::metadata(config_file(SETTINGS_FILE))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Re-applies `settings.toml` whenever it is edited outside the dialog.
+pub fn attach_settings_reload(ui: &PreferencesUi) {
+    let ui = ui.clone();
+    let mut last_modified = settings_modified();
+
+    app::add_timeout3(SETTINGS_POLL_INTERVAL, move |handle| {
+        let modified = settings_modified();
+        if modified != last_modified {
+            last_modified = modified;
+            match Settings::load() {
+                Ok(settings) if settings != ui.state.borrow().settings => {
+                    let previous = std::mem::replace(&mut ui.state.borrow_mut().settings, settings);
+                    apply_settings(&ui, &previous);
+                }
+                Ok(_) => {}
+                Err(e) => dialog::alert_default(&e),
+            }
+        }
+        app::repeat_timeout3(SETTINGS_POLL_INTERVAL, handle);
+    });
+}
@@ -0,0 +1,444 @@
+use encoding_rs::Encoding;
+use fltk::{
+    browser::CheckBrowser,
+    button::{Button, ReturnButton},
+    enums::{Align, Color, Font},
+    frame::Frame,
+    group::Group,
+    prelude::*,
+    text::{TextBuffer, TextDisplay},
+    window::Window,
+};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::edit::replace_changed_range;
+use super::encoding::{decode_with_encoding, detect_line_ending, encode_text};
+use super::find_in_files::{FileQuery, FindInFilesUi, clear_find_in_files, set_status};
+use super::results::context_text;
+use super::search::build_regex;
+
+#[derive(Clone, Copy)]
+enum PlanSource {
+    Disk {
+        encoding: &'static Encoding,
+        bom: bool,
+    },
+    OpenDocument {
+        encoding: &'static Encoding,
+        bom: bool,
+    },
+}
+
+struct PlannedHit {
+    start: usize,
+    end: usize,
+    line: usize,
+    replacement: String,
+}
+
+struct PlannedFile {
+    path: PathBuf,
+    source: PlanSource,
+    original: String,
+    hits: Vec<PlannedHit>,
+}
+
+struct ReplacePreview {
+    win: Window,
+    list: CheckBrowser,
+    diff: TextBuffer,
+    status: Frame,
+    files: Vec<PlannedFile>,
+    /// Maps each list row (0-based) to `(file index, hit index)`.
+    rows: Vec<(usize, usize)>,
+}
+
+fn disk_encoding(path: &Path) -> Option<(&'static Encoding, bool, String)> {
+    let bytes = fs::read(path).ok()?;
+    let decoded = decode_with_encoding(&bytes);
+    Some((decoded.encoding, decoded.bom, decoded.text))
+}
+
+fn plan_file(
+    path: &Path,
+    text: String,
+    source: PlanSource,
+    query: &FileQuery,
+    replacement: &str,
+) -> Result<PlannedFile, String> {
+    let re = build_regex(&query.pattern, &query.options).map_err(|e| e.to_string())?;
+    let eol = detect_line_ending(&text);
+
+    let mut hits = vec![];
+    let mut line = 1;
+    let mut last = 0;
+    for caps in re.captures_iter(&text) {
+        let Some(m) = caps.get(0).filter(|m| !m.is_empty()) else {
+            continue;
+        };
+        line += text[last..m.start()].matches('\n').count();
+        last = m.start();
+
+        let mut expanded = String::new();
+        if query.options.regex {
+            caps.expand(replacement, &mut expanded);
+        } else {
+            expanded.push_str(replacement);
+        }
+        let expanded = expanded.replace("\r\n", "\n").replace('\n', eol.as_str());
+
+        hits.push(PlannedHit {
+            start: m.start(),
+            end: m.end(),
+            line,
+            replacement: expanded,
+        });
+    }
+
+    Ok(PlannedFile {
+        path: path.to_path_buf(),
+        source,
+        original: text,
+        hits,
+    })
+}
+
+fn plan_replacements(ui: &FindInFilesUi, replacement: &str) -> Result<Vec<PlannedFile>, String> {
+    let st = ui.state.borrow();
+    let Some(query) = st.query.clone() else {
+        return Err("Run Find in Files first".into());
+    };
+
+    let mut paths: Vec<PathBuf> = vec![];
+    for hit in &st.hits {
+        if paths.last() != Some(&hit.path) && !paths.contains(&hit.path) {
+            paths.push(hit.path.clone());
+        }
+    }
+    drop(st);
+
+    let open_path = ui.app_state.borrow().document.filepath.clone();
+    let mut files = vec![];
+    for path in paths {
+        let is_open = !open_path.is_empty() && Path::new(&open_path) == path;
+        let disk = disk_encoding(&path);
+
+        let (text, source) = if is_open {
+            let (encoding, bom) = disk
+                .map(|(e, b, _)| (e, b))
+                .unwrap_or((encoding_rs::UTF_8, false));
+            (
+                ui.buf.borrow().text(),
+                PlanSource::OpenDocument { encoding, bom },
+            )
+        } else {
+            let Some((encoding, bom, text)) = disk else {
+                continue;
+            };
+            (text, PlanSource::Disk { encoding, bom })
+        };
+
+        let planned = plan_file(&path, text, source, &query, replacement)?;
+        if !planned.hits.is_empty() {
+            files.push(planned);
+        }
+    }
+    Ok(files)
+}
+
+fn apply_hits(text: &str, hits: &[PlannedHit], enabled: &[bool]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (hit, on) in hits.iter().zip(enabled) {
+        if !on || hit.start < last {
+            continue;
+        }
+        out.push_str(&text[last..hit.start]);
+        out.push_str(&hit.replacement);
+        last = hit.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+fn enabled_hits(preview: &ReplacePreview, file_idx: usize) -> Vec<bool> {
+    let mut enabled = vec![false; preview.files[file_idx].hits.len()];
+    for (row, (f, h)) in preview.rows.iter().enumerate() {
+        if *f == file_idx {
+            enabled[*h] = preview.list.checked(row as i32 + 1);
+        }
+    }
+    enabled
+}
+
+fn line_bounds(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let line_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[end..]
+        .find('\n')
+        .map(|i| end + i)
+        .unwrap_or(text.len());
+    (line_start, line_end)
+}
+
+fn file_diff(file: &PlannedFile, enabled: &[bool]) -> String {
+    let text = &file.original;
+    let mut out = format!("{}\n", file.path.to_string_lossy());
+
+    let mut i = 0;
+    while i < file.hits.len() {
+        let (seg_start, mut seg_end) = line_bounds(text, file.hits[i].start, file.hits[i].end);
+        let mut j = i + 1;
+        while j < file.hits.len() && file.hits[j].start <= seg_end {
+            seg_end = seg_end.max(line_bounds(text, file.hits[j].start, file.hits[j].end).1);
+            j += 1;
+        }
+
+        if enabled[i..j].iter().any(|on| *on) {
+            let shifted: Vec<PlannedHit> = file.hits[i..j]
+                .iter()
+                .map(|h| PlannedHit {
+                    start: h.start - seg_start,
+                    end: h.end - seg_start,
+                    line: h.line,
+                    replacement: h.replacement.clone(),
+                })
+                .collect();
+            let old = &text[seg_start..seg_end];
+            let new = apply_hits(old, &shifted, &enabled[i..j]);
+
+            out.push_str(&format!("@@ line {}\n", file.hits[i].line));
+            for l in old.lines() {
+                out.push_str(&format!("- {}\n", l.trim_end_matches('\r')));
+            }
+            for l in new.lines() {
+                out.push_str(&format!("+ {}\n", l.trim_end_matches('\r')));
+            }
+        }
+        i = j;
+    }
+    out
+}
+
+fn show_diff_for_row(preview: &mut ReplacePreview, row: i32) {
+    let Some((file_idx, _)) = preview.rows.get((row.max(1) - 1) as usize).copied() else {
+        preview.diff.set_text("");
+        return;
+    };
+    let enabled = enabled_hits(preview, file_idx);
+    let diff = file_diff(&preview.files[file_idx], &enabled);
+    preview.diff.set_text(&diff);
+}
+
+fn update_preview_status(preview: &mut ReplacePreview) {
+    let checked = preview.list.nchecked();
+    preview.status.set_label(&format!(
+        "{} of {} replacements selected",
+        checked,
+        preview.rows.len()
+    ));
+}
+
+fn write_file(ui: &FindInFilesUi, file: &PlannedFile, new_text: &str) -> Result<(), String> {
+    match file.source {
+        PlanSource::Disk { encoding, bom } => {
+            fs::write(&file.path, encode_text(new_text, encoding, bom)).map_err(|e| e.to_string())
+        }
+        PlanSource::OpenDocument { encoding, bom } => {
+            let unsaved = ui.app_state.borrow().document.modified;
+            replace_changed_range(&mut ui.buf.borrow_mut(), new_text);
+            if unsaved {
+                // Unsaved edits stay in the buffer; the user saves them as usual.
+                return Ok(());
+            }
+            fs::write(&file.path, encode_text(new_text, encoding, bom))
+                .map_err(|e| e.to_string())?;
+            ui.app_state.borrow_mut().document.modified = false;
+            Ok(())
+        }
+    }
+}
+
+fn run_replace(ui: &FindInFilesUi, preview: &Rc<RefCell<Option<ReplacePreview>>>) {
+    let mut slot = preview.borrow_mut();
+    let Some(p) = slot.as_mut() else {
+        return;
+    };
+
+    let mut replaced = 0;
+    let mut files = 0;
+    let mut errors = vec![];
+    for idx in 0..p.files.len() {
+        let enabled = enabled_hits(p, idx);
+        let count = enabled.iter().filter(|on| **on).count();
+        if count == 0 {
+            continue;
+        }
+
+        let file = &p.files[idx];
+        let new_text = apply_hits(&file.original, &file.hits, &enabled);
+        match write_file(ui, file, &new_text) {
+            Ok(()) => {
+                replaced += count;
+                files += 1;
+            }
+            Err(e) => errors.push(format!("{}: {}", file.path.to_string_lossy(), e)),
+        }
+    }
+
+    p.win.hide();
+    drop(slot);
+    *preview.borrow_mut() = None;
+
+    // Offsets of the remaining hits are stale now; make the user search again.
+    clear_find_in_files(ui);
+
+    let message = if errors.is_empty() {
+        format!("Replaced {} occurrences in {} files", replaced, files)
+    } else {
+        format!(
+            "Replaced {} occurrences in {} files; failed: {}",
+            replaced,
+            files,
+            errors.join(", ")
+        )
+    };
+    set_status(ui, &message);
+}
+
+fn build_preview(ui: &FindInFilesUi, files: Vec<PlannedFile>) {
+    Group::set_current(None::<&Group>);
+
+    let mut win = Window::new(0, 0, 720, 520, "Replace in Files - Preview");
+    let mut list = CheckBrowser::new(10, 10, 700, 210, "");
+    list.set_text_size(12);
+    let mut display = TextDisplay::new(10, 230, 700, 240, "");
+    display.set_text_font(Font::Courier);
+    display.set_text_size(12);
+    let diff = TextBuffer::default();
+    display.set_buffer(diff.clone());
+    let mut check_all = Button::new(10, 485, 90, 25, "Check All");
+    let mut check_none = Button::new(105, 485, 90, 25, "Uncheck All");
+    let mut status = Frame::new(200, 485, 320, 25, "");
+    status.set_align(Align::Left | Align::Inside);
+    status.set_label_color(Color::from_rgb(100, 100, 100));
+    let mut replace = ReturnButton::new(525, 485, 90, 25, "Replace");
+    let mut cancel = Button::new(620, 485, 90, 25, "Cancel");
+    win.end();
+    win.make_modal(true);
+
+    let root = ui
+        .state
+        .borrow()
+        .query
+        .as_ref()
+        .map(|q| q.root.clone())
+        .unwrap_or_default();
+
+    let mut rows = vec![];
+    for (f, file) in files.iter().enumerate() {
+        let shown = file.path.strip_prefix(&root).unwrap_or(&file.path);
+        for (h, hit) in file.hits.iter().enumerate() {
+            let old = context_text(&file.original[hit.start..hit.end]);
+            let new = context_text(&hit.replacement);
+            list.add(
+                &format!(
+                    "{}:{}  {} -> {}",
+                    shown.to_string_lossy(),
+                    hit.line,
+                    old,
+                    new
+                ),
+                true,
+            );
+            rows.push((f, h));
+        }
+    }
+
+    let preview: Rc<RefCell<Option<ReplacePreview>>> = Rc::new(RefCell::new(None));
+
+    {
+        let preview = Rc::clone(&preview);
+        list.set_callback(move |l| {
+            if let Some(p) = preview.borrow_mut().as_mut() {
+                show_diff_for_row(p, l.value());
+                update_preview_status(p);
+            }
+        });
+    }
+
+    {
+        let preview = Rc::clone(&preview);
+        check_all.set_callback(move |_| {
+            if let Some(p) = preview.borrow_mut().as_mut() {
+                p.list.check_all();
+                let row = p.list.value();
+                show_diff_for_row(p, row);
+                update_preview_status(p);
+            }
+        });
+    }
+
+    {
+        let preview = Rc::clone(&preview);
+        check_none.set_callback(move |_| {
+            if let Some(p) = preview.borrow_mut().as_mut() {
+                p.list.check_none();
+                let row = p.list.value();
+                show_diff_for_row(p, row);
+                update_preview_status(p);
+            }
+        });
+    }
+
+    {
+        let ui = ui.clone();
+        let preview = Rc::clone(&preview);
+        replace.set_callback(move |_| run_replace(&ui, &preview));
+    }
+
+    {
+        let preview = Rc::clone(&preview);
+        cancel.set_callback(move |_| {
+            if let Some(p) = preview.borrow_mut().take() {
+                let mut win = p.win;
+                win.hide();
+            }
+        });
+    }
+
+    let mut p = ReplacePreview {
+        win: win.clone(),
+        list,
+        diff,
+        status,
+        files,
+        rows,
+    };
+    show_diff_for_row(&mut p, 1);
+    update_preview_status(&mut p);
+    *preview.borrow_mut() = Some(p);
+
+    win.show();
+}
+
+pub fn show_replace_preview(ui: &FindInFilesUi, replacement: &str) {
+    if ui.state.borrow().running {
+        set_status(ui, "Wait for the search to finish before replacing");
+        return;
+    }
+
+    match plan_replacements(ui, replacement) {
+        Ok(files) if files.is_empty() => {
+            set_status(ui, "Nothing to replace");
+        }
+        Ok(files) => {
+            build_preview(ui, files);
+        }
+        Err(e) => set_status(ui, &e),
+    }
+}
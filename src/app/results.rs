@@ -0,0 +1,93 @@
+use fltk::{browser::HoldBrowser, prelude::*, text::TextBuffer};
+use std::{cell::RefCell, rc::Rc};
+
+use super::dock::{SharedDock, add_dock_page};
+use super::search::{GotoMatch, SearchState};
+use super::state::AppState;
+
+const MAX_CONTEXT_CHARS: usize = 200;
+
+pub type ResultsPanel = Rc<RefCell<HoldBrowser>>;
+
+pub fn create_results_panel(dock: &SharedDock) -> ResultsPanel {
+    let mut browser = add_dock_page(dock, "Search Results");
+    browser.set_column_widths(&[60]);
+    browser.set_column_char('\t');
+    Rc::new(RefCell::new(browser))
+}
+
+/// Formats a line of text for display in a dock list column.
+pub fn context_text(text: &str) -> String {
+    let mut context: String = text
+        .trim_end_matches(['\r', '\n'])
+        .replace('\t', "    ")
+        .chars()
+        .take(MAX_CONTEXT_CHARS)
+        .collect();
+    if context.starts_with('@') {
+        context.insert(0, '@');
+    }
+    context
+}
+
+pub fn refresh_results_panel(panel: &ResultsPanel, buf: &TextBuffer, search: &SearchState) {
+    let mut browser = panel.borrow_mut();
+    browser.clear();
+
+    let mut line = 0;
+    let mut last = 0;
+    for (start, _) in &search.results {
+        line += buf.count_lines(last, *start);
+        last = buf.line_start(*start);
+        browser.add(&format!(
+            "{}\t{}",
+            line + 1,
+            context_text(&buf.line_text(*start))
+        ));
+    }
+    drop(browser);
+
+    select_current_result(panel, search);
+}
+
+pub fn select_current_result(panel: &ResultsPanel, search: &SearchState) {
+    let mut browser = panel.borrow_mut();
+    if search.results.is_empty() || search.current >= browser.size() as usize {
+        return;
+    }
+
+    let row = search.current as i32 + 1;
+    browser.select(row);
+    if !browser.displayed(row) {
+        browser.middle_line(row);
+    }
+}
+
+pub fn attach_results_panel_logic(
+    panel: &ResultsPanel,
+    state: Rc<RefCell<AppState>>,
+    goto: GotoMatch,
+) {
+    panel.borrow_mut().set_callback(move |b| {
+        let row = b.value();
+        if row <= 0 {
+            return;
+        }
+
+        let target = {
+            let mut st = state.borrow_mut();
+            let idx = (row - 1) as usize;
+            match st.search.results.get(idx).copied() {
+                Some(m) => {
+                    st.search.current = idx;
+                    Some(m)
+                }
+                None => None,
+            }
+        };
+
+        if let Some((s, e)) = target {
+            goto(s, e);
+        }
+    });
+}
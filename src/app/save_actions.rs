@@ -0,0 +1,230 @@
+use unicode_normalization::UnicodeNormalization;
+
+use super::editorconfig::EditorConfig;
+use super::encoding::{LineEnding, detect_line_ending};
+use super::settings::{LineEndings, SaveSettings};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FinalNewline {
+    Keep,
+    AtLeastOne,
+    ExactlyOne,
+    Remove,
+}
+
+/// What Save does to the text before writing it.
+pub struct SaveActions {
+    pub trim_trailing_whitespace: bool,
+    /// Trim only lines changed since the file was opened.
+    pub trim_modified_lines_only: bool,
+    pub final_newline: FinalNewline,
+    pub normalize_nfc: bool,
+    pub line_ending: Option<LineEnding>,
+}
+
+impl SaveActions {
+    /// The actions from settings, with `.editorconfig` properties taking precedence.
+    pub fn new(settings: &SaveSettings, editorconfig: &EditorConfig) -> SaveActions {
+        let mut actions = SaveActions {
+            trim_trailing_whitespace: settings.trim_trailing_whitespace,
+            trim_modified_lines_only: settings.trim_modified_lines_only,
+            final_newline: if settings.final_newline {
+                FinalNewline::ExactlyOne
+            } else {
+                FinalNewline::Keep
+            },
+            normalize_nfc: settings.normalize_nfc,
+            line_ending: match settings.line_endings {
+                LineEndings::Keep => None,
+                LineEndings::Lf => Some(LineEnding::Lf),
+                LineEndings::Crlf => Some(LineEnding::CrLf),
+                LineEndings::Cr => Some(LineEnding::Cr),
+            },
+        };
+
+        if let Some(trim) = editorconfig.trim_trailing_whitespace {
+            actions.trim_trailing_whitespace = trim;
+        }
+        if editorconfig.end_of_line.is_some() {
+            actions.line_ending = editorconfig.end_of_line;
+        }
+        match editorconfig.insert_final_newline {
+            Some(true) if actions.final_newline != FinalNewline::ExactlyOne => {
+                actions.final_newline = FinalNewline::AtLeastOne;
+            }
+            Some(false) => actions.final_newline = FinalNewline::Remove,
+            _ => {}
+        }
+        actions
+    }
+
+    /// Applies the actions to `text`; `is_modified` tells which lines were edited.
+    pub fn apply(&self, text: &str, is_modified: &dyn Fn(usize) -> bool) -> String {
+        let mut out = if self.normalize_nfc {
+            text.nfc().collect()
+        } else {
+            text.to_string()
+        };
+
+        if self.trim_trailing_whitespace {
+            let lines: Vec<String> = out
+                .split('\n')
+                .enumerate()
+                .map(|(idx, line)| {
+                    if self.trim_modified_lines_only && !is_modified(idx) {
+                        return line.to_string();
+                    }
+                    let (content, cr) = match line.strip_suffix('\r') {
+                        Some(content) => (content, "\r"),
+                        None => (line, ""),
+                    };
+                    format!("{}{}", content.trim_end_matches([' ', '\t']), cr)
+                })
+                .collect();
+            out = lines.join("\n");
+        }
+
+        if let Some(eol) = self.line_ending {
+            out = out
+                .replace("\r\n", "\n")
+                .replace('\r', "\n")
+                .replace('\n', eol.as_str());
+        }
+
+        let content_len = out.trim_end_matches(['\n', '\r']).len();
+        let has_newline = content_len < out.len();
+        let eol = self
+            .line_ending
+            .unwrap_or_else(|| detect_line_ending(&out))
+            .as_str();
+        match self.final_newline {
+            FinalNewline::AtLeastOne if !has_newline && !out.is_empty() => out.push_str(eol),
+            FinalNewline::ExactlyOne if !out.is_empty() => {
+                out.truncate(content_len);
+                out.push_str(eol);
+            }
+            FinalNewline::Remove => out.truncate(content_len),
+            _ => {}
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: SaveActions = SaveActions {
+        trim_trailing_whitespace: false,
+        trim_modified_lines_only: false,
+        final_newline: FinalNewline::Keep,
+        normalize_nfc: false,
+        line_ending: None,
+    };
+
+    fn apply(actions: SaveActions, text: &str) -> String {
+        actions.apply(text, &|_| true)
+    }
+
+    fn final_newline(final_newline: FinalNewline, text: &str) -> String {
+        apply(
+            SaveActions {
+                final_newline,
+                ..NONE
+            },
+            text,
+        )
+    }
+
+    #[test]
+    fn trims_trailing_whitespace_and_keeps_crlf() {
+        let trim = SaveActions {
+            trim_trailing_whitespace: true,
+            ..NONE
+        };
+        assert_eq!(apply(trim, "a  \r\nb\t\r\n \r\nc "), "a\r\nb\r\n\r\nc");
+    }
+
+    #[test]
+    fn trims_only_modified_lines() {
+        let trim = SaveActions {
+            trim_trailing_whitespace: true,
+            trim_modified_lines_only: true,
+            ..NONE
+        };
+        assert_eq!(trim.apply("a \nb \nc ", &|line| line == 1), "a \nb\nc ");
+    }
+
+    #[test]
+    fn final_newline_modes() {
+        assert_eq!(final_newline(FinalNewline::Keep, "x\n\n"), "x\n\n");
+        assert_eq!(final_newline(FinalNewline::AtLeastOne, "x"), "x\n");
+        assert_eq!(final_newline(FinalNewline::AtLeastOne, "x\n\n"), "x\n\n");
+        assert_eq!(final_newline(FinalNewline::ExactlyOne, "x\n\n\n"), "x\n");
+        assert_eq!(final_newline(FinalNewline::ExactlyOne, "x"), "x\n");
+        assert_eq!(final_newline(FinalNewline::Remove, "x\r\n\r\n"), "x");
+        // An empty file stays empty.
+        assert_eq!(final_newline(FinalNewline::ExactlyOne, ""), "");
+        assert_eq!(final_newline(FinalNewline::AtLeastOne, ""), "");
+    }
+
+    #[test]
+    fn final_newline_follows_the_file() {
+        assert_eq!(
+            final_newline(FinalNewline::ExactlyOne, "a\r\nb"),
+            "a\r\nb\r\n"
+        );
+        assert_eq!(final_newline(FinalNewline::AtLeastOne, "a\rb"), "a\rb\r");
+    }
+
+    #[test]
+    fn converts_line_endings() {
+        let lf = SaveActions {
+            line_ending: Some(LineEnding::Lf),
+            ..NONE
+        };
+        assert_eq!(apply(lf, "a\r\nb\rc\n"), "a\nb\nc\n");
+        let crlf = SaveActions {
+            line_ending: Some(LineEnding::CrLf),
+            final_newline: FinalNewline::ExactlyOne,
+            ..NONE
+        };
+        assert_eq!(apply(crlf, "a\nb\r\n\n"), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn normalizes_to_nfc() {
+        let nfc = SaveActions {
+            normalize_nfc: true,
+            ..NONE
+        };
+        assert_eq!(apply(nfc, "e\u{301}"), "\u{e9}");
+    }
+
+    #[test]
+    fn editorconfig_takes_precedence() {
+        let settings = SaveSettings {
+            final_newline: true,
+            ..SaveSettings::default()
+        };
+        let editorconfig = EditorConfig {
+            trim_trailing_whitespace: Some(true),
+            insert_final_newline: Some(false),
+            end_of_line: Some(LineEnding::CrLf),
+            ..EditorConfig::default()
+        };
+        let actions = SaveActions::new(&settings, &editorconfig);
+        assert!(actions.trim_trailing_whitespace);
+        assert!(actions.final_newline == FinalNewline::Remove);
+        assert!(actions.line_ending == Some(LineEnding::CrLf));
+
+        let editorconfig = EditorConfig {
+            insert_final_newline: Some(true),
+            ..EditorConfig::default()
+        };
+        let actions = SaveActions::new(&SaveSettings::default(), &editorconfig);
+        assert!(actions.final_newline == FinalNewline::AtLeastOne);
+        let actions = SaveActions::new(&settings, &editorconfig);
+        assert!(actions.final_newline == FinalNewline::ExactlyOne);
+    }
+}
@@ -2,32 +2,56 @@ use fltk::{
     enums::{Align, CallbackTrigger, Event, EventState, Key},
     frame::Frame,
     input::Input,
+    menu::MenuBar,
     prelude::*,
     text::{TextBuffer, TextEditor},
 };
-use std::{cell::RefCell, rc::Rc};
+use regex::{Regex, RegexBuilder};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use super::filter::select_source_range;
+use super::highlight::SharedHighlighter;
+use super::history::{HistoryKind, SearchHistory};
+use super::menu::sync_search_option_items;
+use super::results::{ResultsPanel, refresh_results_panel, select_current_result};
+use super::state::AppState;
+use super::status::show_search_controls;
+
+const LIVE_REFRESH_DELAY: f64 = 0.15;
+
+#[derive(Clone, Copy, Default)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub regex: bool,
+}
 
 pub struct SearchState {
+    pub pattern: String,
+    pub options: SearchOptions,
     pub results: Vec<(i32, i32)>,
     pub current: usize,
     pub visible: bool,
-    pub filepath: String,
-    pub recent_files: Vec<String>,
+    pub history: SearchHistory,
 }
 
+pub type GotoMatch = Rc<dyn Fn(i32, i32)>;
+
 pub struct SearchControls {
     pub input: Input,
     pub results: Rc<RefCell<Frame>>,
 }
 
-pub fn update_result_status(results: &Rc<RefCell<Frame>>, state: &SearchState) {
-    if state.results.is_empty() {
+pub fn update_result_status(results: &Rc<RefCell<Frame>>, search: &SearchState) {
+    if search.results.is_empty() {
         results.borrow_mut().set_label("");
     } else {
         results.borrow_mut().set_label(&format!(
             "{} of {}",
-            state.current + 1,
-            state.results.len()
+            search.current + 1,
+            search.results.len()
         ));
     }
 }
@@ -38,16 +62,9 @@ pub fn create_search_controls(status_bar_y: i32, status_bar_w: i32) -> SearchCon
     let _ = sb_x;
 
     let results = Rc::new(RefCell::new(Frame::new(sb_x + 5, sb_y, 70, 20, "")));
-    results
-        .borrow_mut()
-        .set_color(fltk::enums::Color::from_rgb(240, 240, 240));
-    results
-        .borrow_mut()
-        .set_label_color(fltk::enums::Color::from_rgb(100, 100, 100));
     results.borrow_mut().set_align(Align::Left | Align::Inside);
 
     let mut input = Input::new(sb_x + 80, sb_y, 200, 20, "");
-    input.set_text_color(fltk::enums::Color::Black);
     input.set_text_size(12);
     input.hide();
     results.borrow_mut().hide();
@@ -55,145 +72,187 @@ pub fn create_search_controls(status_bar_y: i32, status_bar_w: i32) -> SearchCon
     SearchControls { input, results }
 }
 
-pub fn attach_search_logic(
-    ui: &mut SearchControls,
-    state: Rc<RefCell<SearchState>>,
-    buf: Rc<RefCell<TextBuffer>>,
-    stylebuf: Rc<RefCell<TextBuffer>>,
-    editor: &mut TextEditor,
+pub fn build_regex(pattern: &str, options: &SearchOptions) -> Result<Regex, regex::Error> {
+    let source = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexBuilder::new(&source)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+}
+
+pub fn find_matches(text: &str, pattern: &str, options: &SearchOptions) -> Vec<(i32, i32)> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+
+    let Ok(re) = build_regex(pattern, options) else {
+        return vec![];
+    };
+
+    re.find_iter(text)
+        .filter(|m| !m.is_empty())
+        .map(|m| (m.start() as i32, m.end() as i32))
+        .collect()
+}
+
+/// Moves `state.current` one match forward or backward and returns that match.
+pub fn step_match(state: &Rc<RefCell<AppState>>, forward: bool) -> Option<(i32, i32)> {
+    let mut st = state.borrow_mut();
+    if st.search.results.is_empty() {
+        return None;
+    }
+
+    if forward {
+        st.search.current = (st.search.current + 1) % st.search.results.len();
+    } else if st.search.current == 0 {
+        st.search.current = st.search.results.len() - 1;
+    } else {
+        st.search.current -= 1;
+    }
+    Some(st.search.results[st.search.current])
+}
+
+pub fn make_goto_match(
+    editor: &TextEditor,
+    buf: &Rc<RefCell<TextBuffer>>,
+    controls: &SearchControls,
+    state: &Rc<RefCell<AppState>>,
+    panel: &ResultsPanel,
     update_status: Rc<dyn Fn()>,
-) {
-    let do_search: Rc<dyn Fn(String, bool) -> Vec<(i32, i32)>> = {
-        let buf = Rc::clone(&buf);
-        let stylebuf = Rc::clone(&stylebuf);
-        let status = Rc::clone(&ui.results);
+) -> GotoMatch {
+    let buf = Rc::clone(buf);
+    let editor = editor.clone();
+    let status = Rc::clone(&controls.results);
+    let state = Rc::clone(state);
+    let panel = Rc::clone(panel);
 
-        Rc::new(move |pattern: String, cs: bool| -> Vec<(i32, i32)> {
-            let text = buf.borrow().text();
-            let len = text.len();
-            stylebuf.borrow_mut().set_text(&"A".repeat(len.max(1)));
-            if pattern.is_empty() {
-                status.borrow_mut().set_label("");
-                return vec![];
-            }
+    Rc::new(move |s: i32, e: i32| {
+        select_source_range(&editor, &buf, &state, s, e);
+        update_result_status(&status, &state.borrow().search);
+        select_current_result(&panel, &state.borrow().search);
+        update_status();
+    })
+}
 
-            let hay = if cs {
-                text.clone()
-            } else {
-                text.to_lowercase()
-            };
-            let needle = if cs {
-                pattern.clone()
-            } else {
-                pattern.to_lowercase()
-            };
-
-            let mut out = vec![];
-            let mut pos = 0;
-
-            while let Some(found) = hay[pos..].find(&needle) {
-                let s = (pos + found) as i32;
-                let e = s + pattern.len() as i32;
-                out.push((s, e));
-                pos = e as usize;
-            }
+/// Re-runs the current pattern without moving the cursor, e.g. after the buffer changed.
+fn refresh_search(
+    state: &Rc<RefCell<AppState>>,
+    buf: &Rc<RefCell<TextBuffer>>,
+    highlighter: &SharedHighlighter,
+    status: &Rc<RefCell<Frame>>,
+    panel: &ResultsPanel,
+) {
+    let text = buf.borrow().text();
+    let mut st = state.borrow_mut();
+    st.search.results = find_matches(&text, &st.search.pattern, &st.search.options);
+    if st.search.current >= st.search.results.len() {
+        st.search.current = st.search.results.len().saturating_sub(1);
+    }
+    highlighter.borrow_mut().set_hits(&st.search.results);
+    update_result_status(status, &st.search);
+    refresh_results_panel(panel, &buf.borrow(), &st.search);
+}
 
-            {
-                let mut sb = stylebuf.borrow_mut();
-                for (s, e) in &out {
-                    for i in *s..*e {
-                        if (i as usize) < len {
-                            sb.replace(i, i + 1, "B");
-                        }
-                    }
-                }
-            }
+fn attach_live_refresh(
+    state: &Rc<RefCell<AppState>>,
+    buf: &Rc<RefCell<TextBuffer>>,
+    highlighter: &SharedHighlighter,
+    status: &Rc<RefCell<Frame>>,
+    panel: &ResultsPanel,
+) {
+    let pending = Rc::new(Cell::new(false));
+    let state = Rc::clone(state);
+    let buf_ref = Rc::clone(buf);
+    let highlighter = Rc::clone(highlighter);
+    let status = Rc::clone(status);
+    let panel = Rc::clone(panel);
 
-            if out.is_empty() {
-                status.borrow_mut().set_label("");
-            } else {
-                status
-                    .borrow_mut()
-                    .set_label(&format!("{} of {}", 1, out.len()));
+    // The buffer may still be mutably borrowed while it notifies us, so the
+    // actual refresh is deferred to a timeout.
+    buf.borrow_mut()
+        .add_modify_callback(move |_, inserted, deleted, _, _| {
+            if (inserted == 0 && deleted == 0) || pending.get() {
+                return;
             }
-            out
-        })
-    };
 
-    let goto_match = {
-        let buf = Rc::clone(&buf);
-        let mut ed = editor.clone();
-        let update_status = update_status.clone();
-        let status = Rc::clone(&ui.results);
-        let state = Rc::clone(&state);
+            pending.set(true);
+            let pending = Rc::clone(&pending);
+            let state = Rc::clone(&state);
+            let buf = Rc::clone(&buf_ref);
+            let highlighter = Rc::clone(&highlighter);
+            let status = Rc::clone(&status);
+            let panel = Rc::clone(&panel);
 
-        move |s: i32, e: i32| {
-            ed.set_insert_position(s);
-            ed.show_insert_position();
-            buf.borrow_mut().select(s, e);
-            update_result_status(&status, &state.borrow());
-            update_status();
-        }
-    };
+            fltk::app::add_timeout3(LIVE_REFRESH_DELAY, move |_| {
+                pending.set(false);
+                if state.borrow().search.pattern.is_empty() {
+                    return;
+                }
+                refresh_search(&state, &buf, &highlighter, &status, &panel);
+            });
+        });
+}
+
+pub fn attach_search_logic(
+    ui: &mut SearchControls,
+    state: Rc<RefCell<AppState>>,
+    buf: Rc<RefCell<TextBuffer>>,
+    highlighter: SharedHighlighter,
+    panel: &ResultsPanel,
+    goto_match: GotoMatch,
+    menu: &MenuBar,
+) {
+    attach_live_refresh(&state, &buf, &highlighter, &ui.results, panel);
 
     {
-        let do_search = Rc::clone(&do_search);
         let state = Rc::clone(&state);
-        let mut ed = editor.clone();
         let buf = Rc::clone(&buf);
-        let update_status = update_status.clone();
+        let highlighter = Rc::clone(&highlighter);
         let status = Rc::clone(&ui.results);
+        let panel = Rc::clone(panel);
+        let goto = goto_match.clone();
 
         ui.input.set_trigger(CallbackTrigger::Changed);
         ui.input.set_callback(move |inp| {
-            let res = do_search(inp.value(), false);
-            let mut st = state.borrow_mut();
-            st.results = res;
-            st.current = 0;
-            update_result_status(&status, &st);
-
-            if let Some((s, e)) = st.results.get(0) {
-                ed.set_insert_position(*s);
-                ed.show_insert_position();
-                buf.borrow_mut().select(*s, *e);
+            {
+                let mut st = state.borrow_mut();
+                st.search.pattern = inp.value();
+                st.search.current = 0;
             }
+            refresh_search(&state, &buf, &highlighter, &status, &panel);
 
-            update_status();
+            let first = state.borrow().search.results.first().copied();
+            match first {
+                Some((s, e)) => goto(s, e),
+                None => update_result_status(&status, &state.borrow().search),
+            }
         });
     }
 
     {
         let state = Rc::clone(&state);
-        let mut goto = goto_match.clone();
+        let goto = goto_match;
+        let menu = menu.clone();
 
-        ui.input.handle(move |_, ev| match ev {
+        ui.input.handle(move |inp, ev| match ev {
             Event::KeyDown | Event::Shortcut => {
                 let key = fltk::app::event_key();
                 let st = fltk::app::event_state();
                 let command = st.contains(EventState::Ctrl) || st.contains(EventState::Meta);
                 let text = fltk::app::event_text();
-                let ctrl_j = command && (key == Key::from_char('j') || text == "\n");
-                let ctrl_k = command && (key == Key::from_char('k') || text == "\u{b}");
-
-                if ctrl_j {
-                    let mut st = state.borrow_mut();
-                    if !st.results.is_empty() {
-                        if st.current == 0 {
-                            st.current = st.results.len() - 1;
-                        } else {
-                            st.current -= 1;
-                        }
-                        let (s, e) = st.results[st.current];
-                        goto(s, e);
-                    }
-                    return true;
-                }
+                // Ctrl+Shift+J and Ctrl+Shift+K belong to the Edit menu.
+                let step = command && !st.contains(EventState::Shift);
+                let ctrl_j = step && (key == Key::from_char('j') || text == "\n");
+                let ctrl_k = step && (key == Key::from_char('k') || text == "\u{b}");
 
-                if ctrl_k {
-                    let mut st = state.borrow_mut();
-                    if !st.results.is_empty() {
-                        st.current = (st.current + 1) % st.results.len();
-                        let (s, e) = st.results[st.current];
+                if ctrl_j || ctrl_k {
+                    record_search(&state);
+                    if let Some((s, e)) = step_match(&state, ctrl_k) {
                         goto(s, e);
                     }
                     return true;
@@ -207,28 +266,39 @@ pub fn attach_search_logic(
                     return false;
                 }
 
+                if key == Key::Up || key == Key::Down {
+                    let recall = {
+                        let s = state.borrow();
+                        s.search.results.is_empty() || s.search.history.is_browsing()
+                    };
+                    if !recall {
+                        return false;
+                    }
+
+                    let entry = state
+                        .borrow_mut()
+                        .search
+                        .history
+                        .step(HistoryKind::Search, key == Key::Up);
+                    if let Some(entry) = entry {
+                        state.borrow_mut().search.options = entry.options;
+                        sync_search_option_items(&menu, &entry.options);
+                        inp.set_value(&entry.pattern);
+                        inp.set_position(inp.value().len() as i32).ok();
+                        inp.do_callback();
+                    }
+                    return true;
+                }
+
+                state.borrow_mut().search.history.reset_cursor();
+
                 if key == Key::Enter {
-                    if st.contains(EventState::Shift) {
-                        let mut st = state.borrow_mut();
-                        if !st.results.is_empty() {
-                            if st.current == 0 {
-                                st.current = st.results.len() - 1;
-                            } else {
-                                st.current -= 1;
-                            }
-                            let (s, e) = st.results[st.current];
-                            goto(s, e);
-                        }
-                        return true;
-                    } else {
-                        let mut st = state.borrow_mut();
-                        if !st.results.is_empty() {
-                            st.current = (st.current + 1) % st.results.len();
-                            let (s, e) = st.results[st.current];
-                            goto(s, e);
-                        }
-                        return true;
+                    record_search(&state);
+                    let forward = !st.contains(EventState::Shift);
+                    if let Some((s, e)) = step_match(&state, forward) {
+                        goto(s, e);
                     }
+                    return true;
                 }
 
                 false
@@ -237,3 +307,37 @@ pub fn attach_search_logic(
         });
     }
 }
+
+/// Adds the current pattern to the persisted search history.
+pub fn record_search(state: &Rc<RefCell<AppState>>) {
+    let mut s = state.borrow_mut();
+    let pattern = s.search.pattern.clone();
+    let options = s.search.options;
+    s.search
+        .history
+        .record(HistoryKind::Search, &pattern, options);
+}
+
+/// Shows the search bar and runs `pattern` with `options` as if typed.
+pub fn apply_search(
+    state: &Rc<RefCell<AppState>>,
+    controls: &Rc<RefCell<SearchControls>>,
+    pattern: &str,
+    options: SearchOptions,
+) {
+    {
+        let mut s = state.borrow_mut();
+        s.search.options = options;
+        s.search.visible = true;
+    }
+
+    let mut input = {
+        let mut sc = controls.borrow_mut();
+        show_search_controls(&mut sc);
+        sc.input.clone()
+    };
+    input.set_value(pattern);
+    input.do_callback();
+    input.take_focus().ok();
+    record_search(state);
+}
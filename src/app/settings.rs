@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+use super::config::{config_file, write_config_file};
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+/// Where a `settings.toml` that failed to parse is kept before it's overwritten.
+const SETTINGS_BACKUP_FILE: &str = "settings.toml.bak";
+/// Files the theme and font choices were kept in before `settings.toml`.
+const LEGACY_THEME_FILE: &str = "theme.txt";
+const LEGACY_FONT_FILE: &str = "font.txt";
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", try_from = "WrapSetting")]
+pub enum Wrap {
+    None,
+    Window,
+    Column,
+}
+
+/// `wrap` was a bool before there were wrap modes; `true` wrapped at the window.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WrapSetting {
+    Legacy(bool),
+    Mode(String),
+}
+
+impl TryFrom<WrapSetting> for Wrap {
+    type Error = String;
+
+    fn try_from(setting: WrapSetting) -> Result<Wrap, String> {
+        match setting {
+            WrapSetting::Legacy(true) => Ok(Wrap::Window),
+            WrapSetting::Legacy(false) => Ok(Wrap::None),
+            WrapSetting::Mode(mode) => match mode.as_str() {
+                "none" => Ok(Wrap::None),
+                "window" => Ok(Wrap::Window),
+                "column" => Ok(Wrap::Column),
+                _ => Err(format!("unknown wrap mode \"{}\"", mode)),
+            },
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    pub wrap: Wrap,
+    pub wrap_column: i32,
+    pub ruler: bool,
+    pub ruler_column: i32,
+    pub show_whitespace: bool,
+    pub tab_width: i32,
+    pub insert_spaces: bool,
+    /// Guess tabs vs spaces and the width from each opened file.
+    pub detect_indentation: bool,
+    /// Indent new lines and continue comments and lists on Enter.
+    pub auto_indent: bool,
+    /// Insert the closing bracket or quote when typing the opening one.
+    pub auto_close_brackets: bool,
+    pub scrollbar_size: i32,
+    pub line_numbers: bool,
+    pub relative_line_numbers: bool,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        EditorSettings {
+            wrap: Wrap::Window,
+            wrap_column: 80,
+            ruler: false,
+            ruler_column: 80,
+            show_whitespace: false,
+            tab_width: 4,
+            insert_spaces: true,
+            detect_indentation: true,
+            auto_indent: true,
+            auto_close_brackets: true,
+            scrollbar_size: 16,
+            line_numbers: true,
+            relative_line_numbers: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    Keep,
+    Lf,
+    Crlf,
+    Cr,
+}
+
+/// What File/Save does to the text before writing it.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveSettings {
+    pub trim_trailing_whitespace: bool,
+    pub trim_modified_lines_only: bool,
+    /// End the file with exactly one line ending.
+    pub final_newline: bool,
+    pub normalize_nfc: bool,
+    pub line_endings: LineEndings,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        SaveSettings {
+            trim_trailing_whitespace: false,
+            trim_modified_lines_only: false,
+            final_newline: false,
+            normalize_nfc: false,
+            line_endings: LineEndings::Keep,
+        }
+    }
+}
+
+/// Save actions a file extension may change, e.g. `[overrides.md.on_save]`.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SaveOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_trailing_whitespace: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_modified_lines_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_newline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize_nfc: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_endings: Option<LineEndings>,
+}
+
+impl SaveOverrides {
+    fn is_empty(&self) -> bool {
+        *self == SaveOverrides::default()
+    }
+}
+
+/// Editor settings a file extension may change, e.g. `[overrides.csv] wrap = "none"`.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<Wrap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap_column: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ruler: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ruler_column: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insert_spaces: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_numbers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_line_numbers: Option<bool>,
+    #[serde(skip_serializing_if = "SaveOverrides::is_empty")]
+    pub on_save: SaveOverrides,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileSettings {
+    pub max_recent_files: usize,
+}
+
+impl Default for FileSettings {
+    fn default() -> Self {
+        FileSettings {
+            max_recent_files: 10,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchSettings {
+    pub case_sensitive: bool,
+    pub regex: bool,
+    pub max_history: usize,
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        SearchSettings {
+            case_sensitive: false,
+            regex: false,
+            max_history: 50,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppearanceSettings {
+    pub theme: String,
+    pub follow_system: bool,
+    pub font: String,
+    pub font_size: i32,
+}
+
+impl Default for AppearanceSettings {
+    fn default() -> Self {
+        AppearanceSettings {
+            theme: "Light".into(),
+            follow_system: false,
+            font: "Courier".into(),
+            font_size: 14,
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub editor: EditorSettings,
+    pub files: FileSettings,
+    pub search: SearchSettings,
+    pub appearance: AppearanceSettings,
+    pub on_save: SaveSettings,
+    /// Keyed by file extension, without the dot.
+    pub overrides: BTreeMap<String, EditorOverrides>,
+}
+
+impl Settings {
+    /// Reads `settings.toml`, creating it from the older per-setting files the first time.
+    pub fn load() -> Result<Settings, String> {
+        let path = config_file(SETTINGS_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(_) => {
+                let settings = Settings::migrate();
+                settings.save();
+                Ok(settings)
+            }
+        }
+    }
+
+    fn migrate() -> Settings {
+        let mut settings = Settings::default();
+        let appearance = &mut settings.appearance;
+
+        if let Ok(content) = fs::read_to_string(config_file(LEGACY_THEME_FILE)) {
+            let mut lines = content.lines().map(str::trim);
+            if let Some(name) = lines.next().filter(|n| !n.is_empty()) {
+                appearance.theme = name.to_string();
+            }
+            appearance.follow_system = lines.any(|l| l == "follow-system");
+        }
+        if let Ok(content) = fs::read_to_string(config_file(LEGACY_FONT_FILE)) {
+            let mut lines = content.lines().map(str::trim);
+            if let Some(name) = lines.next().filter(|n| !n.is_empty()) {
+                appearance.font = name.to_string();
+            }
+            if let Some(size) = lines.next().and_then(|s| s.parse().ok()) {
+                appearance.font_size = size;
+            }
+        }
+        settings
+    }
+
+    /// Writes `settings.toml`. A file there that doesn't parse, such as one
+    /// the user is halfway through editing, is backed up first.
+    pub fn save(&self) {
+        let path = config_file(SETTINGS_FILE);
+        if let Ok(current) = fs::read_to_string(&path)
+            && toml::from_str::<Settings>(&current).is_err()
+            && fs::copy(&path, config_file(SETTINGS_BACKUP_FILE)).is_err()
+        {
+            return;
+        }
+        if let Ok(content) = toml::to_string_pretty(self) {
+            write_config_file(SETTINGS_FILE, &content);
+        }
+    }
+
+    /// Editor settings for `path`, with its extension's overrides applied.
+    pub fn editor_for(&self, path: &str) -> EditorSettings {
+        let mut editor = self.editor.clone();
+        let Some(o) = file_type(path).and_then(|ext| self.overrides.get(&ext)) else {
+            return editor;
+        };
+
+        editor.wrap = o.wrap.unwrap_or(editor.wrap);
+        editor.wrap_column = o.wrap_column.unwrap_or(editor.wrap_column);
+        editor.ruler = o.ruler.unwrap_or(editor.ruler);
+        editor.ruler_column = o.ruler_column.unwrap_or(editor.ruler_column);
+        editor.tab_width = o.tab_width.unwrap_or(editor.tab_width);
+        editor.insert_spaces = o.insert_spaces.unwrap_or(editor.insert_spaces);
+        editor.line_numbers = o.line_numbers.unwrap_or(editor.line_numbers);
+        editor.relative_line_numbers = o
+            .relative_line_numbers
+            .unwrap_or(editor.relative_line_numbers);
+        editor
+    }
+
+    /// Save actions for `path`, with its extension's overrides applied.
+    pub fn on_save_for(&self, path: &str) -> SaveSettings {
+        let mut on_save = self.on_save.clone();
+        let Some(o) = file_type(path).and_then(|ext| self.overrides.get(&ext)) else {
+            return on_save;
+        };
+        let o = &o.on_save;
+
+        on_save.trim_trailing_whitespace = o
+            .trim_trailing_whitespace
+            .unwrap_or(on_save.trim_trailing_whitespace);
+        on_save.trim_modified_lines_only = o
+            .trim_modified_lines_only
+            .unwrap_or(on_save.trim_modified_lines_only);
+        on_save.final_newline = o.final_newline.unwrap_or(on_save.final_newline);
+        on_save.normalize_nfc = o.normalize_nfc.unwrap_or(on_save.normalize_nfc);
+        on_save.line_endings = o.line_endings.unwrap_or(on_save.line_endings);
+        on_save
+    }
+
+    /// Records a wrap or ruler change as an override for the file's extension,
+    /// or as the default when the file has none.
+    pub fn remember_for(&mut self, path: &str, change: impl Fn(&mut EditorSettings)) {
+        let Some(ext) = file_type(path) else {
+            change(&mut self.editor);
+            return;
+        };
+
+        let before = self.editor_for(path);
+        let mut after = before.clone();
+        change(&mut after);
+        let o = self.overrides.entry(ext).or_default();
+        if after.wrap != before.wrap {
+            o.wrap = Some(after.wrap);
+        }
+        if after.wrap_column != before.wrap_column {
+            o.wrap_column = Some(after.wrap_column);
+        }
+        if after.ruler != before.ruler {
+            o.ruler = Some(after.ruler);
+        }
+        if after.ruler_column != before.ruler_column {
+            o.ruler_column = Some(after.ruler_column);
+        }
+    }
+}
+
+/// The lowercased extension that `[overrides]` are keyed by.
+fn file_type(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+}
@@ -0,0 +1,28 @@
+use super::editorconfig::EditorConfig;
+use super::filter::FilterState;
+use super::indent::Indent;
+use super::markers::LineMarks;
+use super::search::SearchState;
+use super::settings::Settings;
+use super::theme::Theme;
+
+/// The open file and what has been worked out about it.
+pub struct DocumentState {
+    pub filepath: String,
+    pub modified: bool,
+    pub filter: FilterState,
+    pub marks: LineMarks,
+    pub indent: Indent,
+    pub editorconfig: EditorConfig,
+}
+
+/// State shared by the window's widgets.
+pub struct AppState {
+    pub search: SearchState,
+    pub document: DocumentState,
+    pub recent_files: Vec<String>,
+    pub theme: Theme,
+    pub settings: Settings,
+    /// A note for the status bar and the cursor position it belongs to.
+    pub status_message: Option<(String, i32)>,
+}
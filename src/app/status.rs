@@ -1,5 +1,5 @@
 use fltk::{
-    app,
+    app, draw,
     enums::{Align, Event},
     frame::Frame,
     menu::MenuItem,
@@ -14,7 +14,8 @@ use std::{
     rc::Rc,
 };
 
-use super::SearchState;
+use super::goto::GotoLine;
+use super::state::AppState;
 
 pub type StatusBar = Rc<RefCell<Frame>>;
 pub type UpdateStatus = Rc<dyn Fn()>;
@@ -22,7 +23,6 @@ pub type UpdateStatus = Rc<dyn Fn()>;
 pub fn create_status_bar(x: i32, y: i32, w: i32, h: i32) -> StatusBar {
     let mut f = Frame::new(x, y, w, h, "");
     f.set_align(Align::Left | Align::Inside);
-    f.set_color(fltk::enums::Color::from_rgb(240, 240, 240));
     Rc::new(RefCell::new(f))
 }
 
@@ -84,16 +84,37 @@ fn copy_full_path(path: &str) {
     app::copy(path);
 }
 
-pub fn attach_status_path_actions(status_bar: &StatusBar, search_state: &Rc<RefCell<SearchState>>) {
-    let search_state = Rc::clone(search_state);
+/// True when `x` falls on the "Ln X, Col Y" part of the status label.
+fn over_position_label(f: &Frame, x: i32) -> bool {
+    let label = f.label();
+    let position = label.split("  |  ").next().unwrap_or_default();
+    draw::set_font(f.label_font(), f.label_size());
+    let (w, _) = draw::measure(position, false);
+    x >= f.x() && x <= f.x() + w + 6
+}
+
+pub fn attach_status_path_actions(
+    status_bar: &StatusBar,
+    app_state: &Rc<RefCell<AppState>>,
+    goto_line: &GotoLine,
+) {
+    let app_state = Rc::clone(app_state);
+    let goto_line = Rc::clone(goto_line);
     let menu = MenuItem::new(&["Copy Full Path", "Open File Location"]);
 
-    status_bar.borrow_mut().handle(move |_, ev| {
+    status_bar.borrow_mut().handle(move |f, ev| {
         if ev != Event::Push {
             return false;
         }
 
-        let path = search_state.borrow().filepath.clone();
+        if app::event_mouse_button() == app::MouseButton::Left
+            && over_position_label(f, app::event_x())
+        {
+            goto_line();
+            return true;
+        }
+
+        let path = app_state.borrow().document.filepath.clone();
         if path.is_empty() {
             return false;
         }
@@ -117,29 +138,69 @@ pub fn attach_status_path_actions(status_bar: &StatusBar, search_state: &Rc<RefC
 pub fn make_update_status(
     status_bar: &StatusBar,
     editor: &TextEditor,
-    search_state: &Rc<RefCell<SearchState>>,
+    app_state: &Rc<RefCell<AppState>>,
 ) -> UpdateStatus {
     let status_bar = Rc::clone(status_bar);
     let editor = editor.clone();
-    let search_state = Rc::clone(search_state);
+    let app_state = Rc::clone(app_state);
 
     Rc::new(move || {
         let pos = editor.insert_position();
-        let line = editor.count_lines(0, pos, false);
-        let col = pos - editor.line_start(pos);
+        // Count in the buffer rather than the display, which would count wrapped rows.
+        let (row, col) = editor
+            .buffer()
+            .map(|b| (b.count_lines(0, pos) as usize, pos - b.line_start(pos)))
+            .unwrap_or((0, 0));
+
+        // A message lasts until the cursor moves.
+        let stale = app_state
+            .borrow()
+            .status_message
+            .as_ref()
+            .is_some_and(|(_, at)| *at != pos);
+        if stale && let Ok(mut s) = app_state.try_borrow_mut() {
+            s.status_message = None;
+        }
 
-        let fp = search_state.borrow().filepath.clone();
-        let display = file_display_name(&fp);
+        let s = app_state.borrow();
+        let display = file_display_name(&s.document.filepath);
+        let message = match &s.status_message {
+            Some((message, at)) if *at == pos => format!("  |  {}", message),
+            _ => String::new(),
+        };
+
+        // In a filtered view, report the line number from the original document.
+        let (line, filtered) = match &s.document.filter.view {
+            Some(view) => (
+                view.source_line(row)
+                    .map(|l| (l + 1).to_string())
+                    .unwrap_or_else(|| "-".into()),
+                format!(
+                    "  |  Filtered: {} of {} lines",
+                    view.visible_lines(),
+                    view.total_lines
+                ),
+            ),
+            None => ((row + 1).to_string(), String::new()),
+        };
 
         status_bar.borrow_mut().set_label(&format!(
-            "Ln {}, Col {}  |  {}",
-            line + 1,
+            "Ln {}, Col {}  |  {}  |  {}{}{}",
+            line,
             col + 1,
-            display
+            s.document.indent.label(),
+            display,
+            filtered,
+            message
         ));
     })
 }
 
+/// Shows `message` in the status bar on its next update, until the cursor moves.
+pub fn show_status_message(app_state: &Rc<RefCell<AppState>>, editor: &TextEditor, message: &str) {
+    app_state.borrow_mut().status_message = Some((message.to_string(), editor.insert_position()));
+}
+
 pub fn show_search_controls(search: &mut super::search::SearchControls) {
     search.input.show();
     search.results.borrow_mut().show();
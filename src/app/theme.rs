@@ -0,0 +1,498 @@
+use fltk::{app, dialog, enums::Color, prelude::*, text::TextEditor};
+use serde::Deserialize;
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fs,
+    process::Command,
+    rc::Rc,
+    sync::mpsc,
+    thread,
+};
+
+use super::config::config_dir;
+use super::highlight::{SharedHighlighter, refresh_style_table};
+use super::languages::{TOKEN_KINDS, TokenKind};
+use super::markers::MarkerStrip;
+use super::search::SearchControls;
+use super::settings::AppearanceSettings;
+use super::state::AppState;
+use super::status::StatusBar;
+
+const THEMES_DIR: &str = "themes";
+/// How often (in seconds) the desktop's dark-mode setting is checked.
+const SYSTEM_POLL_INTERVAL: f64 = 5.0;
+/// How often (in seconds) to look for the answer while a check is running.
+const ANSWER_POLL_INTERVAL: f64 = 0.05;
+/// Keeps `reg` from flashing a console window from the GUI-subsystem app.
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    pub foreground: Color,
+    pub selection: Color,
+    pub cursor: Color,
+    pub ui_background: Color,
+    pub ui_background2: Color,
+    pub ui_foreground: Color,
+    pub status_background: Color,
+    pub status_foreground: Color,
+    /// Secondary text such as the search result count.
+    pub muted: Color,
+    pub gutter_background: Color,
+    pub line_number: Color,
+    pub current_line_number: Color,
+    pub current_line: Color,
+    pub ruler: Color,
+    /// Glyphs drawn for spaces, tabs and line endings.
+    pub whitespace: Color,
+    pub trailing_whitespace: Color,
+    pub search_hit: Color,
+    /// Background of the bracket or quote pairing with the one at the cursor.
+    pub bracket_match: Color,
+    pub bookmark: Color,
+    pub modified_marker: Color,
+    pub match_marker: Color,
+    pub current_match_marker: Color,
+    pub tokens: [Color; TOKEN_KINDS],
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::from_rgb(r, g, b)
+}
+
+impl Theme {
+    pub fn light() -> Theme {
+        Theme {
+            name: "Light".into(),
+            background: rgb(255, 255, 255),
+            foreground: rgb(0, 0, 0),
+            selection: rgb(180, 205, 240),
+            cursor: rgb(0, 0, 0),
+            ui_background: rgb(192, 192, 192),
+            ui_background2: rgb(255, 255, 255),
+            ui_foreground: rgb(0, 0, 0),
+            status_background: rgb(240, 240, 240),
+            status_foreground: rgb(0, 0, 0),
+            muted: rgb(100, 100, 100),
+            gutter_background: rgb(245, 245, 245),
+            line_number: rgb(150, 150, 150),
+            current_line_number: rgb(30, 30, 30),
+            current_line: rgb(225, 230, 240),
+            ruler: rgb(220, 220, 220),
+            whitespace: rgb(190, 190, 190),
+            trailing_whitespace: rgb(255, 200, 200),
+            search_hit: rgb(255, 230, 120),
+            bracket_match: rgb(200, 225, 200),
+            bookmark: rgb(60, 110, 210),
+            modified_marker: rgb(90, 160, 90),
+            match_marker: rgb(230, 160, 40),
+            current_match_marker: rgb(210, 40, 40),
+            tokens: [
+                rgb(0, 0, 0),
+                rgb(110, 120, 110),
+                rgb(30, 130, 50),
+                rgb(170, 80, 20),
+                rgb(140, 30, 140),
+                rgb(20, 100, 150),
+                rgb(40, 60, 170),
+                rgb(150, 120, 20),
+                rgb(160, 40, 40),
+                rgb(20, 60, 140),
+            ],
+        }
+    }
+
+    pub fn dark() -> Theme {
+        Theme {
+            name: "Dark".into(),
+            background: rgb(30, 31, 34),
+            foreground: rgb(220, 220, 215),
+            selection: rgb(60, 80, 120),
+            cursor: rgb(230, 230, 230),
+            ui_background: rgb(50, 52, 56),
+            ui_background2: rgb(38, 40, 44),
+            ui_foreground: rgb(220, 220, 220),
+            status_background: rgb(40, 42, 46),
+            status_foreground: rgb(210, 210, 210),
+            muted: rgb(150, 150, 150),
+            gutter_background: rgb(36, 37, 40),
+            line_number: rgb(110, 110, 110),
+            current_line_number: rgb(220, 220, 220),
+            current_line: rgb(50, 54, 62),
+            ruler: rgb(62, 64, 70),
+            whitespace: rgb(85, 88, 95),
+            trailing_whitespace: rgb(110, 45, 45),
+            search_hit: rgb(100, 85, 20),
+            bracket_match: rgb(50, 90, 70),
+            bookmark: rgb(90, 140, 230),
+            modified_marker: rgb(90, 170, 90),
+            match_marker: rgb(220, 160, 50),
+            current_match_marker: rgb(230, 70, 70),
+            tokens: [
+                rgb(220, 220, 215),
+                rgb(120, 130, 120),
+                rgb(150, 200, 120),
+                rgb(230, 160, 90),
+                rgb(200, 130, 220),
+                rgb(100, 180, 220),
+                rgb(110, 150, 240),
+                rgb(210, 190, 90),
+                rgb(230, 120, 110),
+                rgb(120, 170, 250),
+            ],
+        }
+    }
+
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "High Contrast".into(),
+            background: rgb(0, 0, 0),
+            foreground: rgb(255, 255, 255),
+            selection: rgb(0, 110, 220),
+            cursor: rgb(255, 255, 0),
+            ui_background: rgb(0, 0, 0),
+            ui_background2: rgb(0, 0, 0),
+            ui_foreground: rgb(255, 255, 255),
+            status_background: rgb(0, 0, 0),
+            status_foreground: rgb(255, 255, 255),
+            muted: rgb(200, 200, 200),
+            gutter_background: rgb(0, 0, 0),
+            line_number: rgb(200, 200, 200),
+            current_line_number: rgb(255, 255, 0),
+            current_line: rgb(40, 40, 40),
+            ruler: rgb(120, 120, 120),
+            whitespace: rgb(150, 150, 150),
+            trailing_whitespace: rgb(170, 0, 0),
+            search_hit: rgb(140, 70, 0),
+            bracket_match: rgb(0, 110, 140),
+            bookmark: rgb(0, 200, 255),
+            modified_marker: rgb(0, 255, 0),
+            match_marker: rgb(255, 200, 0),
+            current_match_marker: rgb(255, 60, 60),
+            tokens: [
+                rgb(255, 255, 255),
+                rgb(120, 230, 120),
+                rgb(255, 210, 100),
+                rgb(255, 160, 255),
+                rgb(120, 200, 255),
+                rgb(100, 255, 230),
+                rgb(255, 255, 120),
+                rgb(255, 170, 90),
+                rgb(255, 140, 140),
+                rgb(120, 200, 255),
+            ],
+        }
+    }
+
+    pub fn token(&self, kind: TokenKind) -> Color {
+        self.tokens[kind as usize]
+    }
+
+    fn color_mut(&mut self, key: &str) -> Option<&mut Color> {
+        Some(match key {
+            "background" => &mut self.background,
+            "foreground" => &mut self.foreground,
+            "selection" => &mut self.selection,
+            "cursor" => &mut self.cursor,
+            "ui_background" => &mut self.ui_background,
+            "ui_background2" => &mut self.ui_background2,
+            "ui_foreground" => &mut self.ui_foreground,
+            "status_background" => &mut self.status_background,
+            "status_foreground" => &mut self.status_foreground,
+            "muted" => &mut self.muted,
+            "gutter_background" => &mut self.gutter_background,
+            "line_number" => &mut self.line_number,
+            "current_line_number" => &mut self.current_line_number,
+            "current_line" => &mut self.current_line,
+            "ruler" => &mut self.ruler,
+            "whitespace" => &mut self.whitespace,
+            "trailing_whitespace" => &mut self.trailing_whitespace,
+            "search_hit" => &mut self.search_hit,
+            "bracket_match" => &mut self.bracket_match,
+            "bookmark" => &mut self.bookmark,
+            "modified_marker" => &mut self.modified_marker,
+            "match_marker" => &mut self.match_marker,
+            "current_match_marker" => &mut self.current_match_marker,
+            _ => return None,
+        })
+    }
+}
+
+pub fn builtin_themes() -> Vec<Theme> {
+    vec![Theme::light(), Theme::dark(), Theme::high_contrast()]
+}
+
+/// A theme file in `themes/`: a built-in `base` plus the colors it changes.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    base: Option<String>,
+    #[serde(default)]
+    colors: BTreeMap<String, String>,
+    #[serde(default)]
+    tokens: BTreeMap<String, String>,
+}
+
+fn parse_color(key: &str, value: &str) -> Result<Color, String> {
+    Color::from_hex_str(value.trim())
+        .map_err(|_| format!("{}: \"{}\" is not a #rrggbb color", key, value))
+}
+
+fn load_theme_file(path: &std::path::Path) -> Result<Theme, String> {
+    let fail = |e: String| format!("{}: {}", path.display(), e);
+    let content = fs::read_to_string(path).map_err(|e| fail(e.to_string()))?;
+    let file: ThemeFile = toml::from_str(&content).map_err(|e| fail(e.to_string()))?;
+
+    let base = file.base.as_deref().unwrap_or("Light");
+    let mut theme = builtin_themes()
+        .into_iter()
+        .find(|t| t.name.eq_ignore_ascii_case(base))
+        .ok_or_else(|| fail(format!("unknown base theme \"{}\"", base)))?;
+
+    theme.name = file.name.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    for (key, value) in &file.colors {
+        let color = parse_color(key, value).map_err(fail)?;
+        *theme
+            .color_mut(key)
+            .ok_or_else(|| fail(format!("unknown color \"{}\"", key)))? = color;
+    }
+    for (key, value) in &file.tokens {
+        let kind =
+            TokenKind::from_name(key).ok_or_else(|| fail(format!("unknown token \"{}\"", key)))?;
+        theme.tokens[kind as usize] = parse_color(key, value).map_err(fail)?;
+    }
+    Ok(theme)
+}
+
+/// Built-in themes followed by the user's `themes/*.toml`. Broken files are
+/// skipped and their errors returned.
+pub fn load_themes() -> (Vec<Theme>, Vec<String>) {
+    let mut themes = builtin_themes();
+    let mut errors = vec![];
+    let Ok(entries) = fs::read_dir(config_dir().join(THEMES_DIR)) else {
+        return (themes, errors);
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        match load_theme_file(&path) {
+            Ok(theme) => {
+                themes.retain(|t| t.name != theme.name);
+                themes.push(theme);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    (themes, errors)
+}
+
+fn report_theme_errors(errors: &[String]) {
+    if !errors.is_empty() {
+        dialog::alert_default(&format!(
+            "Some user themes could not be loaded:\n\n{}",
+            errors.join("\n")
+        ));
+    }
+}
+
+/// Asks the desktop whether it prefers dark windows.
+pub fn system_prefers_dark() -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+
+        Command::new("reg")
+            .creation_flags(CREATE_NO_WINDOW)
+            .args([
+                "query",
+                r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+                "/v",
+                "AppsUseLightTheme",
+            ])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains("0x0"))
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains("Dark"))
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let gsettings = |key: &str| {
+            Command::new("gsettings")
+                .args(["get", "org.gnome.desktop.interface", key])
+                .output()
+                .map(|out| String::from_utf8_lossy(&out.stdout).to_lowercase())
+                .unwrap_or_default()
+        };
+        gsettings("color-scheme").contains("dark") || gsettings("gtk-theme").contains("dark")
+    }
+}
+
+/// The theme to show: the chosen one, or Light/Dark when following the desktop.
+pub fn resolve_theme(
+    themes: &[Theme],
+    appearance: &AppearanceSettings,
+    system_dark: bool,
+) -> Theme {
+    if appearance.follow_system {
+        return if system_dark {
+            Theme::dark()
+        } else {
+            Theme::light()
+        };
+    }
+    themes
+        .iter()
+        .find(|t| t.name == appearance.theme)
+        .cloned()
+        .unwrap_or_else(Theme::light)
+}
+
+#[derive(Clone)]
+pub struct ThemeUi {
+    pub themes: Rc<Vec<Theme>>,
+    pub state: Rc<RefCell<AppState>>,
+    pub editor: TextEditor,
+    pub highlighter: SharedHighlighter,
+    pub status_bar: StatusBar,
+    pub search_controls: Rc<RefCell<SearchControls>>,
+    pub marker_strip: MarkerStrip,
+    /// The desktop's last reported dark-mode preference.
+    pub system_dark: Rc<Cell<bool>>,
+}
+
+fn set_app_color(set: fn(u8, u8, u8), color: Color) {
+    let (r, g, b) = color.to_rgb();
+    set(r, g, b);
+}
+
+/// Recolors every part of the window from `theme`.
+pub fn apply_theme(ui: &ThemeUi, theme: Theme) {
+    set_app_color(app::background, theme.ui_background);
+    set_app_color(app::background2, theme.ui_background2);
+    set_app_color(app::foreground, theme.ui_foreground);
+    set_app_color(app::set_selection_color, theme.selection);
+
+    let mut ed = ui.editor.clone();
+    ed.set_color(theme.background);
+    ed.set_text_color(theme.foreground);
+    ed.set_selection_color(theme.selection);
+    ed.set_cursor_color(theme.cursor);
+    ed.set_linenumber_bgcolor(theme.gutter_background);
+    ed.set_linenumber_fgcolor(theme.gutter_background);
+
+    {
+        let mut sb = ui.status_bar.borrow_mut();
+        sb.set_color(theme.status_background);
+        sb.set_label_color(theme.status_foreground);
+    }
+    {
+        let mut sc = ui.search_controls.borrow_mut();
+        let mut results = sc.results.borrow_mut();
+        results.set_color(theme.status_background);
+        results.set_label_color(theme.muted);
+        drop(results);
+        sc.input.set_color(theme.ui_background2);
+        sc.input.set_text_color(theme.ui_foreground);
+        sc.input.set_selection_color(theme.selection);
+    }
+    ui.marker_strip
+        .borrow_mut()
+        .set_color(theme.status_background);
+
+    ui.state.borrow_mut().theme = theme;
+    refresh_style_table(&ui.editor, &ui.highlighter, &ui.state.borrow());
+    app::redraw();
+}
+
+pub fn apply_current_theme(ui: &ThemeUi) {
+    let theme = resolve_theme(
+        &ui.themes,
+        &ui.state.borrow().settings.appearance,
+        ui.system_dark.get(),
+    );
+    apply_theme(ui, theme);
+}
+
+/// Re-applies the theme when the desktop switches between light and dark. The
+/// desktop is asked on a worker thread so a slow query can't stall the UI.
+fn attach_system_watch(ui: &ThemeUi) {
+    let ui = ui.clone();
+    let follows = |ui: &ThemeUi| ui.state.borrow().settings.appearance.follow_system;
+    let (tx, rx) = mpsc::channel();
+    let (mut asked, mut checking) = (false, false);
+
+    app::add_timeout3(0.0, move |handle| {
+        if let Ok(dark) = rx.try_recv() {
+            checking = false;
+            if dark != ui.system_dark.get() {
+                ui.system_dark.set(dark);
+                if follows(&ui) {
+                    apply_current_theme(&ui);
+                }
+            }
+        }
+        // Ask once up front so turning on Follow System has an answer ready.
+        if (follows(&ui) || !asked) && !checking {
+            (asked, checking) = (true, true);
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let _ = tx.send(system_prefers_dark());
+            });
+        }
+        let delay = if checking {
+            ANSWER_POLL_INTERVAL
+        } else {
+            SYSTEM_POLL_INTERVAL
+        };
+        app::repeat_timeout3(delay, handle);
+    });
+}
+
+pub fn create_themes(
+    state: &Rc<RefCell<AppState>>,
+    editor: &TextEditor,
+    highlighter: &SharedHighlighter,
+    status_bar: &StatusBar,
+    search_controls: &Rc<RefCell<SearchControls>>,
+    marker_strip: &MarkerStrip,
+) -> ThemeUi {
+    let (themes, errors) = load_themes();
+    // Wait for the main loop so the alert doesn't pop up before the window.
+    app::add_timeout3(0.0, move |_| report_theme_errors(&errors));
+
+    let ui = ThemeUi {
+        themes: Rc::new(themes),
+        state: Rc::clone(state),
+        editor: editor.clone(),
+        highlighter: Rc::clone(highlighter),
+        status_bar: Rc::clone(status_bar),
+        search_controls: Rc::clone(search_controls),
+        marker_strip: Rc::clone(marker_strip),
+        system_dark: Rc::new(Cell::new(false)),
+    };
+    apply_current_theme(&ui);
+    attach_system_watch(&ui);
+    ui
+}
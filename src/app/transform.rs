@@ -0,0 +1,376 @@
+use fltk::{prelude::*, text::TextEditor};
+use std::{cell::RefCell, rc::Rc};
+use unicode_normalization::UnicodeNormalization;
+
+use super::edit::editable_buffer;
+use super::state::AppState;
+
+/// Where full-width forms of printable ASCII start (U+FF01 is `！`).
+const FULL_WIDTH_OFFSET: u32 = 0xFF01 - 0x21;
+const IDEOGRAPHIC_SPACE: char = '\u{3000}';
+const THAI_ZERO: u32 = 0x0E50;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Transform {
+    Upper,
+    Lower,
+    Title,
+    Sentence,
+    Snake,
+    Camel,
+    Kebab,
+    Pascal,
+    Nfc,
+    Nfd,
+    Nfkc,
+    FullWidth,
+    HalfWidth,
+    ThaiDigits,
+    LatinDigits,
+    SmartQuotes,
+    StraightQuotes,
+}
+
+impl Transform {
+    /// In menu order; `true` ends a group.
+    pub const ALL: [(Transform, bool); 17] = [
+        (Transform::Upper, false),
+        (Transform::Lower, false),
+        (Transform::Title, false),
+        (Transform::Sentence, true),
+        (Transform::Snake, false),
+        (Transform::Camel, false),
+        (Transform::Kebab, false),
+        (Transform::Pascal, true),
+        (Transform::Nfc, false),
+        (Transform::Nfd, false),
+        (Transform::Nfkc, true),
+        (Transform::FullWidth, false),
+        (Transform::HalfWidth, true),
+        (Transform::ThaiDigits, false),
+        (Transform::LatinDigits, true),
+        (Transform::SmartQuotes, false),
+        (Transform::StraightQuotes, false),
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transform::Upper => "UPPER CASE",
+            Transform::Lower => "lower case",
+            Transform::Title => "Title Case",
+            Transform::Sentence => "Sentence case",
+            Transform::Snake => "snake_case",
+            Transform::Camel => "camelCase",
+            Transform::Kebab => "kebab-case",
+            Transform::Pascal => "PascalCase",
+            Transform::Nfc => "Unicode NFC",
+            Transform::Nfd => "Unicode NFD",
+            Transform::Nfkc => "Unicode NFKC",
+            Transform::FullWidth => "Full-Width Characters",
+            Transform::HalfWidth => "Half-Width Characters",
+            Transform::ThaiDigits => "Thai Digits",
+            Transform::LatinDigits => "Latin Digits",
+            Transform::SmartQuotes => "Smart Quotes",
+            Transform::StraightQuotes => "Straight Quotes",
+        }
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Transform::Upper => text.to_uppercase(),
+            Transform::Lower => text.to_lowercase(),
+            Transform::Title => title_case(text),
+            Transform::Sentence => sentence_case(text),
+            Transform::Snake | Transform::Camel | Transform::Kebab | Transform::Pascal => {
+                identifier_case(text, self)
+            }
+            Transform::Nfc => text.nfc().collect(),
+            Transform::Nfd => text.nfd().collect(),
+            Transform::Nfkc => text.nfkc().collect(),
+            Transform::FullWidth => text.chars().map(full_width).collect(),
+            Transform::HalfWidth => text.chars().map(half_width).collect(),
+            Transform::ThaiDigits => text
+                .chars()
+                .map(|c| match c.to_digit(10).filter(|_| c.is_ascii_digit()) {
+                    Some(d) => char::from_u32(THAI_ZERO + d).unwrap_or(c),
+                    None => c,
+                })
+                .collect(),
+            Transform::LatinDigits => text
+                .chars()
+                .map(
+                    |c| match (c as u32).checked_sub(THAI_ZERO).filter(|d| *d < 10) {
+                        Some(d) => char::from_digit(d, 10).unwrap_or(c),
+                        None => c,
+                    },
+                )
+                .collect(),
+            Transform::SmartQuotes => smart_quotes(text),
+            Transform::StraightQuotes => text
+                .chars()
+                .map(|c| match c {
+                    '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' => '\'',
+                    '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' => '"',
+                    _ => c,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Capitalizes each word; an apostrophe doesn't start a new one.
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_word = false;
+    for c in text.chars() {
+        if in_word {
+            out.extend(c.to_lowercase());
+        } else {
+            out.extend(c.to_uppercase());
+        }
+        in_word = c.is_alphanumeric() || (in_word && matches!(c, '\'' | '\u{2019}'));
+    }
+    out
+}
+
+/// Lowercases everything but the first letter of each sentence.
+fn sentence_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut start = true;
+    for c in text.chars() {
+        if start && c.is_alphabetic() {
+            out.extend(c.to_uppercase());
+            start = false;
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        if matches!(c, '.' | '!' | '?') {
+            start = true;
+        }
+    }
+    out
+}
+
+/// Splits an identifier or phrase into words at separators and case changes,
+/// keeping acronyms together: `parseHTTPResponse` gives parse, HTTP, Response.
+fn words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = vec![];
+    let mut word = String::new();
+    for (idx, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if let Some(&prev) = idx.checked_sub(1).and_then(|p| chars.get(p)) {
+            let next_lower = chars.get(idx + 1).is_some_and(|n| n.is_lowercase());
+            let boundary = c.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_uppercase() && next_lower));
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn join_words(words: &[String], case: Transform) -> String {
+    let lower: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+    match case {
+        Transform::Snake => lower.join("_"),
+        Transform::Kebab => lower.join("-"),
+        Transform::Camel => lower
+            .iter()
+            .enumerate()
+            .map(|(idx, w)| if idx == 0 { w.clone() } else { capitalize(w) })
+            .collect(),
+        _ => lower.iter().map(|w| capitalize(w)).collect(),
+    }
+}
+
+/// Converts `token` but keeps any leading or trailing `_` and `-`.
+fn convert_token(token: &str, case: Transform) -> String {
+    let core = token.trim_matches(['_', '-']);
+    let start = token.len() - token.trim_start_matches(['_', '-']).len();
+    let (lead, trail) = (&token[..start], &token[start + core.len()..]);
+    format!("{}{}{}", lead, join_words(&words(core), case), trail)
+}
+
+/// A single-line phrase of plain words converts as one identifier; anything
+/// else, such as code, converts each identifier in it separately.
+fn identifier_case(text: &str, case: Transform) -> String {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let phrase = text.trim();
+    if !phrase.is_empty()
+        && !text.contains('\n')
+        && phrase.chars().all(|c| is_word_char(c) || c == ' ')
+    {
+        let start = text.len() - text.trim_start().len();
+        return format!(
+            "{}{}{}",
+            &text[..start],
+            convert_token(phrase, case),
+            &text[start + phrase.len()..]
+        );
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut token = String::new();
+    for c in text.chars() {
+        if is_word_char(c) {
+            token.push(c);
+            continue;
+        }
+        if !token.is_empty() {
+            out.push_str(&convert_token(&std::mem::take(&mut token), case));
+        }
+        out.push(c);
+    }
+    if !token.is_empty() {
+        out.push_str(&convert_token(&token, case));
+    }
+    out
+}
+
+fn full_width(c: char) -> char {
+    match c {
+        ' ' => IDEOGRAPHIC_SPACE,
+        '!'..='~' => char::from_u32(c as u32 + FULL_WIDTH_OFFSET).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn half_width(c: char) -> char {
+    match c {
+        IDEOGRAPHIC_SPACE => ' ',
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - FULL_WIDTH_OFFSET).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Curls straight quotes: opening after whitespace or an opening bracket,
+/// closing (or an apostrophe) elsewhere.
+fn smart_quotes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        let opening = prev.is_none_or(|p| p.is_whitespace() || "([{<\u{2014}\u{2013}".contains(p));
+        out.push(match (c, opening) {
+            ('"', true) => '\u{201c}',
+            ('"', false) => '\u{201d}',
+            ('\'', true) => '\u{2018}',
+            ('\'', false) => '\u{2019}',
+            _ => c,
+        });
+        prev = Some(c);
+    }
+    out
+}
+
+/// Transforms the selection, or the word at the cursor, as one undo step.
+pub fn transform_selection(
+    editor: &mut TextEditor,
+    state: &Rc<RefCell<AppState>>,
+    transform: Transform,
+) {
+    let Some(mut buf) = editable_buffer(editor, state) else {
+        return;
+    };
+    let (start, end) = buf.selection_position().unwrap_or_else(|| {
+        let pos = editor.insert_position();
+        (buf.word_start(pos), buf.word_end(pos))
+    });
+    let text = buf.text_range(start, end).unwrap_or_default();
+    let new_text = transform.apply(&text);
+    if new_text == text {
+        return;
+    }
+
+    buf.replace(start, end, &new_text);
+    let new_end = start + new_text.len() as i32;
+    buf.select(start, new_end);
+    editor.set_insert_position(new_end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acronyms_stay_together() {
+        assert_eq!(words("parseHTTPResponse"), ["parse", "HTTP", "Response"]);
+        assert_eq!(words("XMLHttpRequest"), ["XML", "Http", "Request"]);
+        assert_eq!(words("getURL"), ["get", "URL"]);
+        assert_eq!(words("utf8Decoder"), ["utf8", "Decoder"]);
+        assert_eq!(
+            words("some_value-name here"),
+            ["some", "value", "name", "here"]
+        );
+    }
+
+    #[test]
+    fn identifier_cases() {
+        let text = "parseHTTPResponse";
+        assert_eq!(Transform::Snake.apply(text), "parse_http_response");
+        assert_eq!(Transform::Kebab.apply(text), "parse-http-response");
+        assert_eq!(Transform::Camel.apply(text), "parseHttpResponse");
+        assert_eq!(Transform::Pascal.apply(text), "ParseHttpResponse");
+        assert_eq!(Transform::Camel.apply("hello big world"), "helloBigWorld");
+    }
+
+    #[test]
+    fn leading_and_trailing_underscores_are_kept() {
+        assert_eq!(Transform::Camel.apply("__private_name"), "__privateName");
+        assert_eq!(Transform::Snake.apply("__initValue__"), "__init_value__");
+        assert_eq!(Transform::Pascal.apply("_tmp_"), "_Tmp_");
+        assert_eq!(Transform::Snake.apply("_"), "_");
+    }
+
+    #[test]
+    fn code_converts_each_identifier() {
+        assert_eq!(
+            Transform::Snake.apply("let fooBar = bazQux(1);"),
+            "let foo_bar = baz_qux(1);"
+        );
+    }
+
+    #[test]
+    fn title_case_keeps_apostrophes_inside_words() {
+        assert_eq!(
+            Transform::Title.apply("don't STOP me now"),
+            "Don't Stop Me Now"
+        );
+        assert_eq!(
+            Transform::Title.apply("it\u{2019}s here"),
+            "It\u{2019}s Here"
+        );
+        assert_eq!(Transform::Title.apply("'quoted' word"), "'Quoted' Word");
+    }
+
+    #[test]
+    fn sentence_case_starts_each_sentence() {
+        assert_eq!(
+            Transform::Sentence.apply("HELLO THERE. how ARE you? fine!"),
+            "Hello there. How are you? Fine!"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use fltk::{
+    draw,
+    enums::{Align, Color},
+    prelude::*,
+    text::{PositionType, TextBuffer, TextEditor},
+};
+
+use super::gutter::text_area;
+
+/// Characters that look like a plain space or take no room at all.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{a0}' | '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}'
+    )
+}
+
+fn glyph(c: char) -> Option<&'static str> {
+    Some(match c {
+        ' ' => "·",
+        '\t' => "→",
+        '\u{a0}' => "°",
+        '\r' => "←",
+        '\n' => "¶",
+        _ => return None,
+    })
+}
+
+/// Sets `style` on trailing whitespace and invisible characters in `text`,
+/// whose bytes line up with `out`. `text` should start and end on line boundaries.
+pub fn mark_whitespace(text: &str, out: &mut [u8], style: u8) {
+    if text.len() != out.len() {
+        return;
+    }
+
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let content = line.strip_suffix('\r').unwrap_or(line);
+        let trimmed = content.trim_end_matches([' ', '\t', '\u{a0}']);
+        out[line_start + trimmed.len()..line_start + content.len()].fill(style);
+
+        for (idx, c) in trimmed.char_indices().filter(|(_, c)| is_invisible(*c)) {
+            let at = line_start + idx;
+            out[at..at + c.len_utf8()].fill(style);
+        }
+        line_start += line.len() + 1;
+    }
+}
+
+/// Draws a glyph over each space, tab, invisible character and line ending on
+/// screen. Only the visible part of each row is scanned, so a long unwrapped
+/// line costs no more than a short one.
+pub fn draw_whitespace(e: &TextEditor, display: &TextBuffer, color: Color) {
+    let (x, y, w, h) = text_area(e);
+    draw::push_clip(x, y, w, h);
+    draw::set_font(e.text_font(), e.text_size());
+    draw::set_draw_color(color);
+    let line_h = draw::height();
+    let space_w = draw::width(" ") as i32;
+
+    // End of what earlier rows covered; rows past the text map to the same spot.
+    let mut scanned = 0;
+    for row_y in (y + 1..y + h).step_by(line_h.max(1) as usize) {
+        let start = e
+            .xy_to_position(x, row_y, PositionType::Cursor)
+            .max(scanned);
+        let right = e.xy_to_position(x + w, row_y, PositionType::Cursor);
+        // A little past the right edge, so a glyph cut by it still shows.
+        let end = display.utf8_align((right + 4).min(display.length()));
+        if end <= start {
+            continue;
+        }
+        scanned = end;
+        let Some(text) = display.text_range(start, end) else {
+            continue;
+        };
+
+        for (idx, c) in text.char_indices() {
+            if !c.is_whitespace() && !is_invisible(c) {
+                continue;
+            }
+            let (cx, cy) = e.position_to_xy(start + idx as i32);
+            if cy < y || cy >= y + h || cx > x + w {
+                continue;
+            }
+            match glyph(c) {
+                Some(g) => draw::draw_text2(g, cx, cy, space_w, line_h, Align::Center),
+                // Zero-width characters get a thin bar where they sit.
+                None => draw::draw_rect_fill(cx - 1, cy, 2, line_h, color),
+            }
+        }
+    }
+    draw::pop_clip();
+}